@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS "UserTimezone" (
+	"UserId"	INTEGER NOT NULL,
+	"Timezone"	TEXT NOT NULL,
+	PRIMARY KEY("UserId")
+);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS "ScheduledAnnouncement" (
+	"AnnouncementId"	INTEGER NOT NULL,
+	"GuildId"	INTEGER NOT NULL,
+	"ChannelId"	INTEGER NOT NULL,
+	"Content"	TEXT,
+	"EmbedId"	INTEGER,
+	"Recurrence"	TEXT,
+	"Timezone"	TEXT NOT NULL,
+	"MissedRunPolicy"	TEXT NOT NULL,
+	"NextRun"	INTEGER NOT NULL,
+	"CreatedBy"	INTEGER NOT NULL,
+	FOREIGN KEY("EmbedId") REFERENCES "Embed"("EmbedId") ON DELETE SET NULL,
+	PRIMARY KEY("AnnouncementId")
+);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS "Reminder" (
+	"ReminderId"	INTEGER NOT NULL,
+	"UserId"	INTEGER NOT NULL,
+	"GuildId"	INTEGER,
+	"ChannelId"	INTEGER NOT NULL,
+	"MessageId"	INTEGER,
+	"Content"	TEXT NOT NULL,
+	"Delivery"	TEXT NOT NULL,
+	"DueAt"	INTEGER NOT NULL,
+	"CreatedAt"	INTEGER NOT NULL,
+	"Delivered"	INTEGER NOT NULL DEFAULT 0,
+	PRIMARY KEY("ReminderId")
+);
+CREATE INDEX IF NOT EXISTS "ReminderDue" ON "Reminder" ("Delivered", "DueAt");
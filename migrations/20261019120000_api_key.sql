@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS "ApiKey" (
+	"ApiKeyId"	INTEGER NOT NULL,
+	"GuildId"	INTEGER NOT NULL,
+	"Name"	TEXT NOT NULL,
+	"KeyHash"	TEXT NOT NULL UNIQUE,
+	"CreatedBy"	INTEGER NOT NULL,
+	"CreatedAt"	INTEGER NOT NULL,
+	"LastUsedAt"	INTEGER,
+	PRIMARY KEY("ApiKeyId")
+);
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS "DashboardSession" (
+	"SessionHash"	TEXT NOT NULL,
+	"UserId"	INTEGER NOT NULL,
+	"Username"	TEXT NOT NULL,
+	"CreatedAt"	INTEGER NOT NULL,
+	"ExpiresAt"	INTEGER NOT NULL,
+	PRIMARY KEY("SessionHash")
+);
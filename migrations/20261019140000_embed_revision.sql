@@ -0,0 +1,4 @@
+-- The embeds as last posted, so the table is the source of truth rather than a list of ids
+ALTER TABLE Embed ADD COLUMN EmbedJson TEXT;
+ALTER TABLE Embed ADD COLUMN Revision INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE Embed ADD COLUMN UpdatedAt INTEGER;
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS "EmbedRevision" (
+	"EmbedId"	INTEGER NOT NULL,
+	"Revision"	INTEGER NOT NULL,
+	"EmbedJson"	TEXT NOT NULL,
+	"AuthorId"	INTEGER,
+	"AuthorName"	TEXT NOT NULL,
+	"CreatedAt"	INTEGER NOT NULL,
+	FOREIGN KEY("EmbedId") REFERENCES "Embed"("EmbedId") ON DELETE CASCADE,
+	PRIMARY KEY("EmbedId","Revision")
+);
+-- The current version of embeds saved before history was kept
+INSERT INTO EmbedRevision (EmbedId, Revision, EmbedJson, AuthorName, CreatedAt)
+	SELECT EmbedId, Revision, EmbedJson, 'Unknown', COALESCE(UpdatedAt, 0) FROM Embed WHERE EmbedJson IS NOT NULL;
@@ -0,0 +1,13 @@
+ALTER TABLE "AutoRoleMessage" ADD COLUMN "GuildId" INTEGER;
+ALTER TABLE "AutoRoleMessage" ADD COLUMN "ChannelId" INTEGER;
+ALTER TABLE "AutoRoleMessage" ADD COLUMN "MaxSelections" INTEGER;
+ALTER TABLE "AutoRoleMessage" ADD COLUMN "Style" TEXT NOT NULL DEFAULT 'menu';
+CREATE TABLE IF NOT EXISTS "AutoRoleOption" (
+	"AutoRoleMessageId"	INTEGER NOT NULL,
+	"Position"	INTEGER NOT NULL,
+	"RoleId"	INTEGER NOT NULL,
+	"Label"	TEXT NOT NULL,
+	"Description"	TEXT,
+	FOREIGN KEY("AutoRoleMessageId") REFERENCES "AutoRoleMessage"("AutoRoleMessageId") ON DELETE CASCADE,
+	PRIMARY KEY("AutoRoleMessageId","Position")
+);
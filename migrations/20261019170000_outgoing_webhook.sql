@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS "OutgoingWebhook" (
+	"OutgoingWebhookId"	INTEGER NOT NULL,
+	"GuildId"	INTEGER NOT NULL,
+	"Url"	TEXT NOT NULL,
+	"Secret"	TEXT NOT NULL,
+	"Events"	TEXT NOT NULL,
+	"CreatedBy"	INTEGER NOT NULL,
+	"CreatedAt"	INTEGER NOT NULL,
+	PRIMARY KEY("OutgoingWebhookId")
+);
+CREATE TABLE IF NOT EXISTS "WebhookDelivery" (
+	"WebhookDeliveryId"	INTEGER NOT NULL,
+	"OutgoingWebhookId"	INTEGER NOT NULL,
+	"DeliveryKey"	TEXT NOT NULL,
+	"Event"	TEXT NOT NULL,
+	"Attempt"	INTEGER NOT NULL,
+	"StatusCode"	INTEGER,
+	"Error"	TEXT,
+	"Succeeded"	INTEGER NOT NULL,
+	"AttemptedAt"	INTEGER NOT NULL,
+	FOREIGN KEY("OutgoingWebhookId") REFERENCES "OutgoingWebhook"("OutgoingWebhookId") ON DELETE CASCADE,
+	PRIMARY KEY("WebhookDeliveryId")
+);
+CREATE INDEX IF NOT EXISTS "WebhookDeliveryByWebhook" ON "WebhookDelivery" ("OutgoingWebhookId", "WebhookDeliveryId");
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS "Integration" (
+	"IntegrationId"	INTEGER NOT NULL,
+	"GuildId"	INTEGER NOT NULL,
+	"ChannelId"	INTEGER NOT NULL,
+	"Name"	TEXT NOT NULL,
+	"TokenHash"	TEXT NOT NULL UNIQUE,
+	"Template"	TEXT NOT NULL,
+	"CreatedBy"	TEXT NOT NULL,
+	"CreatedAt"	INTEGER NOT NULL,
+	"LastUsedAt"	INTEGER,
+	PRIMARY KEY("IntegrationId")
+);
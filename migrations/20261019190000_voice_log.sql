@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS "VoiceLog" (
+	"GuildId"	INTEGER NOT NULL,
+	"ChannelId"	INTEGER NOT NULL,
+	"Events"	TEXT NOT NULL,
+	PRIMARY KEY("GuildId")
+);
+-- The EDBH guild logged joins, leaves and moves before this was configurable
+INSERT OR IGNORE INTO "VoiceLog" ("GuildId", "ChannelId", "Events") VALUES (687876072045412560, 805186168647974964, 'join,leave,move');
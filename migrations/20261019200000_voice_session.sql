@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS "VoiceSession" (
+	"VoiceSessionId"	INTEGER NOT NULL,
+	"GuildId"	INTEGER NOT NULL,
+	"UserId"	INTEGER NOT NULL,
+	"ChannelId"	INTEGER NOT NULL,
+	"JoinedAt"	INTEGER NOT NULL,
+	"LeftAt"	INTEGER,
+	PRIMARY KEY("VoiceSessionId")
+);
+CREATE INDEX IF NOT EXISTS "VoiceSessionMember" ON "VoiceSession" ("GuildId", "UserId");
+CREATE INDEX IF NOT EXISTS "VoiceSessionOpen" ON "VoiceSession" ("LeftAt");
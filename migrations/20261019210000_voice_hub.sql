@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS "VoiceHub" (
+	"GuildId"	INTEGER NOT NULL,
+	"ChannelId"	INTEGER NOT NULL,
+	"NameTemplate"	TEXT NOT NULL,
+	"UserLimit"	INTEGER,
+	PRIMARY KEY("GuildId")
+);
+CREATE TABLE IF NOT EXISTS "HubVoiceChannel" (
+	"ChannelId"	INTEGER NOT NULL,
+	"GuildId"	INTEGER NOT NULL,
+	"OwnerId"	INTEGER NOT NULL,
+	"CreatedAt"	INTEGER NOT NULL,
+	PRIMARY KEY("ChannelId")
+);
@@ -0,0 +1,2 @@
+-- Failed posts of the current run, it's retried until this reaches the cap
+ALTER TABLE ScheduledAnnouncement ADD COLUMN FailedAttempts INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+-- Failed deliveries so far, the reminder is put off a little longer after each until the cap
+ALTER TABLE Reminder ADD COLUMN FailedAttempts INTEGER NOT NULL DEFAULT 0;
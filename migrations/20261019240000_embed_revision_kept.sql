@@ -0,0 +1,18 @@
+-- Revisions outlive their message, a deleted embed is when its history is needed most. The guild
+-- and channel move onto each revision so it can still be found once the Embed row is gone.
+CREATE TABLE IF NOT EXISTS "EmbedRevisionKept" (
+	"EmbedId"	INTEGER NOT NULL,
+	"GuildId"	INTEGER NOT NULL,
+	"ChannelId"	INTEGER NOT NULL,
+	"Revision"	INTEGER NOT NULL,
+	"EmbedJson"	TEXT NOT NULL,
+	"AuthorId"	INTEGER,
+	"AuthorName"	TEXT NOT NULL,
+	"CreatedAt"	INTEGER NOT NULL,
+	PRIMARY KEY("EmbedId","Revision")
+);
+INSERT INTO EmbedRevisionKept (EmbedId, GuildId, ChannelId, Revision, EmbedJson, AuthorId, AuthorName, CreatedAt)
+	SELECT r.EmbedId, e.GuildId, e.ChannelId, r.Revision, r.EmbedJson, r.AuthorId, r.AuthorName, r.CreatedAt
+	FROM EmbedRevision r JOIN Embed e ON e.EmbedId = r.EmbedId;
+DROP TABLE EmbedRevision;
+ALTER TABLE EmbedRevisionKept RENAME TO EmbedRevision;
@@ -0,0 +1,417 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use chrono_tz::Tz;
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    http::Http,
+    model::{
+        application::interaction::application_command::{
+            ApplicationCommandInteraction, CommandDataOptionValue,
+        },
+        id::ChannelId,
+    },
+    utils::Color,
+};
+use sqlx::{query, SqlitePool};
+use tracing::{error, info};
+
+use crate::utils::{
+    cron::CronSchedule,
+    database::DatabasePool,
+    interaction::{option_i64, option_resolved, option_str, respond_embed},
+    embeds::is_refused,
+    time::{discord_timestamp, get_user_timezone, parse_datetime, parse_timezone},
+};
+
+//a run this late means the bot was down, anything less is scheduler tick delay
+const MISSED_RUN_GRACE_SECONDS: i64 = 5 * 60;
+//ticks a run is retried on while Discord is failing, well within the grace period
+const MAX_POST_ATTEMPTS: i64 = 5;
+
+/// What to do with a run that should have happened while the bot was offline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MissedRunPolicy {
+    /// Post once as soon as the bot is back, however many runs were missed
+    CatchUp,
+    /// Drop missed runs and wait for the next one
+    Skip,
+}
+
+impl MissedRunPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MissedRunPolicy::CatchUp => "catch_up",
+            MissedRunPolicy::Skip => "skip",
+        }
+    }
+}
+
+impl FromStr for MissedRunPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "catch_up" => Ok(MissedRunPolicy::CatchUp),
+            "skip" => Ok(MissedRunPolicy::Skip),
+            _ => Err(anyhow!("Unknown missed run policy: {s}")),
+        }
+    }
+}
+
+impl Display for MissedRunPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MissedRunPolicy::CatchUp => f.write_str("Catch up"),
+            MissedRunPolicy::Skip => f.write_str("Skip"),
+        }
+    }
+}
+
+/// `/announce schedule`, `/announce list` and `/announce cancel`
+pub async fn announce(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let subcommand = command
+        .data
+        .options
+        .first()
+        .ok_or(anyhow!("No subcommand given for announce"))?;
+    let guild_id = command.guild_id.ok_or(anyhow!("announce used outside of a guild"))?;
+    let guild_id_i64 = *guild_id.as_u64() as i64;
+
+    let data = ctx.data.read().await;
+    let pool = data.get::<DatabasePool>().unwrap().clone();
+
+    let embed = match subcommand.name.as_str() {
+        "schedule" => {
+            let options = &subcommand.options;
+
+            let channel_id = match option_resolved(options, "channel") {
+                Some(CommandDataOptionValue::Channel(channel)) => channel.id,
+                _ => return Err(anyhow!("No channel given for announce schedule")),
+            };
+            let content = option_str(options, "message").map(str::to_string);
+            let embed_id = match option_str(options, "embed") {
+                Some(id) => match id.trim().parse::<u64>() {
+                    Ok(id) => Some(id as i64),
+                    Err(_) => {
+                        return respond_error(ctx, command, format!("`{id}` is not a message ID")).await;
+                    }
+                },
+                None => None,
+            };
+
+            if content.is_none() && embed_id.is_none() {
+                return respond_error(ctx, command, "Give a message, a saved embed, or both".to_string()).await;
+            }
+
+            if let Some(embed_id) = embed_id {
+                let saved = query!(
+                    "SELECT EmbedId FROM Embed WHERE EmbedId = ? AND GuildId = ?",
+                    embed_id,
+                    guild_id_i64
+                )
+                .fetch_optional(&pool)
+                .await?;
+                if saved.is_none() {
+                    return respond_error(ctx, command, "That message isn't a saved embed in this server".to_string()).await;
+                }
+            }
+
+            let timezone = match option_str(options, "timezone") {
+                Some(name) => match parse_timezone(name) {
+                    Some(tz) => tz,
+                    None => return respond_error(ctx, command, format!("`{name}` isn't a timezone")).await,
+                },
+                None => get_user_timezone(&pool, command.user.id).await?.unwrap_or(Tz::UTC),
+            };
+            let now = Utc::now().with_timezone(&timezone);
+
+            let recurrence = match option_str(options, "repeat") {
+                Some(expression) => match CronSchedule::parse(expression) {
+                    Ok(schedule) => Some(schedule),
+                    Err(why) => return respond_error(ctx, command, why.to_string()).await,
+                },
+                None => None,
+            };
+
+            let first_run = match option_str(options, "when") {
+                Some(when) => match parse_datetime(when, Utc::now(), timezone) {
+                    Ok(time) if time <= now => {
+                        return respond_error(ctx, command, "That time has already passed".to_string()).await;
+                    }
+                    Ok(time) => Some(time),
+                    Err(why) => return respond_error(ctx, command, why.to_string()).await,
+                },
+                None => None,
+            };
+
+            let next_run = match (first_run, &recurrence) {
+                (Some(time), _) => time,
+                (None, Some(schedule)) => match schedule.next_after(now) {
+                    Some(time) => time,
+                    None => return respond_error(ctx, command, "That schedule never runs".to_string()).await,
+                },
+                (None, None) => {
+                    return respond_error(ctx, command, "Give a time with `when`, a schedule with `repeat`, or both".to_string()).await;
+                }
+            };
+
+            let policy = option_str(options, "missed")
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(MissedRunPolicy::CatchUp);
+
+            let channel_id_i64 = *channel_id.as_u64() as i64;
+            let recurrence_text = recurrence.as_ref().map(|r| r.to_string());
+            let timezone_name = timezone.name();
+            let policy_text = policy.as_str();
+            let next_run_timestamp = next_run.timestamp();
+            let created_by = *command.user.id.as_u64() as i64;
+
+            let announcement_id = query!(
+                "INSERT INTO ScheduledAnnouncement
+                (GuildId, ChannelId, Content, EmbedId, Recurrence, Timezone, MissedRunPolicy, NextRun, CreatedBy)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                guild_id_i64,
+                channel_id_i64,
+                content,
+                embed_id,
+                recurrence_text,
+                timezone_name,
+                policy_text,
+                next_run_timestamp,
+                created_by
+            )
+            .execute(&pool)
+            .await?
+            .last_insert_rowid();
+
+            let mut embed = CreateEmbed::default();
+            embed.title(format!("Announcement #{announcement_id} scheduled"));
+            embed.description(format!(
+                "Posting in <#{}> {} ({})",
+                channel_id,
+                discord_timestamp(&next_run, 'F'),
+                discord_timestamp(&next_run, 'R')
+            ));
+            if let Some(recurrence) = &recurrence {
+                embed.field("Repeats", format!("`{recurrence}` in {}", timezone.name()), true);
+            }
+            embed.field("Missed runs", policy.to_string(), true);
+            embed.color(Color::DARK_GREEN);
+            embed
+        }
+        "list" => {
+            let rows = query!(
+                "SELECT AnnouncementId, ChannelId, Recurrence, Timezone, MissedRunPolicy, NextRun
+                FROM ScheduledAnnouncement
+                WHERE GuildId = ?
+                ORDER BY NextRun",
+                guild_id_i64
+            )
+            .fetch_all(&pool)
+            .await?;
+
+            let description = rows
+                .iter()
+                .take(20)
+                .map(|row| {
+                    let repeat = match &row.Recurrence {
+                        Some(recurrence) => format!(", repeats `{}` in {}", recurrence, row.Timezone),
+                        None => String::new(),
+                    };
+                    format!(
+                        "**#{}** <#{}> <t:{}:f>{}",
+                        row.AnnouncementId, row.ChannelId, row.NextRun, repeat
+                    )
+                })
+                .reduce(|a, b| a + "\n" + &b)
+                .unwrap_or("No announcements scheduled".to_string());
+
+            let mut embed = CreateEmbed::default();
+            embed.title("Scheduled announcements");
+            embed.description(description);
+            embed
+        }
+        "cancel" => {
+            let id = option_i64(&subcommand.options, "id").ok_or(anyhow!("No id given for announce cancel"))?;
+
+            let result = query!(
+                "DELETE FROM ScheduledAnnouncement WHERE AnnouncementId = ? AND GuildId = ?",
+                id,
+                guild_id_i64
+            )
+            .execute(&pool)
+            .await?;
+
+            let mut embed = CreateEmbed::default();
+            if result.rows_affected() > 0 {
+                embed.title(format!("Announcement #{id} cancelled"));
+                embed.color(Color::DARK_GREEN);
+            } else {
+                embed.title(format!("There is no announcement #{id} in this server"));
+                embed.color(Color::RED);
+            }
+            embed
+        }
+        _ => return Ok(()),
+    };
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
+
+async fn respond_error(ctx: &Context, command: &ApplicationCommandInteraction, message: String) -> Result<()> {
+    let mut embed = CreateEmbed::default();
+    embed.title("Unable to schedule announcement");
+    embed.description(message);
+    embed.color(Color::RED);
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
+
+/// Posts every announcement whose time has come and moves it to its next run, one-off
+/// announcements are removed once handled. A post that failed for a reason that may pass, such as
+/// an outage, keeps its run and is tried again on the next ticks.
+pub async fn run_due_announcements(http: &Http, pool: &SqlitePool) -> Result<()> {
+    let now = Utc::now();
+    let now_timestamp = now.timestamp();
+
+    let due = query!(
+        "SELECT AnnouncementId, ChannelId, Content, EmbedId, Recurrence, Timezone, MissedRunPolicy, NextRun, FailedAttempts
+        FROM ScheduledAnnouncement
+        WHERE NextRun <= ?",
+        now_timestamp
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for row in due {
+        let policy: MissedRunPolicy = row.MissedRunPolicy.parse().unwrap_or(MissedRunPolicy::CatchUp);
+        let missed = now_timestamp - row.NextRun > MISSED_RUN_GRACE_SECONDS;
+
+        let mut gone = false;
+        if missed && policy == MissedRunPolicy::Skip {
+            info!("Skipping missed announcement #{} due at {}", row.AnnouncementId, row.NextRun);
+        } else {
+            let posted = post_announcement(
+                http,
+                pool,
+                ChannelId(row.ChannelId as u64),
+                row.Content.as_deref(),
+                row.EmbedId,
+            )
+            .await;
+            match posted {
+                Ok(true) => {}
+                //its saved embed was deleted and it has no text, every later run would fail too
+                Ok(false) => {
+                    error!("Announcement #{} has nothing left to post, removing it", row.AnnouncementId);
+                    gone = true;
+                }
+                Err(why) => {
+                    let refused = why.downcast_ref().is_some_and(is_refused);
+                    let attempts = row.FailedAttempts + 1;
+                    error!("Unable to post announcement #{} (attempt {}): {}", row.AnnouncementId, attempts, why);
+
+                    if !refused && attempts < MAX_POST_ATTEMPTS {
+                        let retried = query!(
+                            "UPDATE ScheduledAnnouncement SET FailedAttempts = ? WHERE AnnouncementId = ?",
+                            attempts,
+                            row.AnnouncementId
+                        )
+                        .execute(pool)
+                        .await;
+                        if let Err(why) = retried {
+                            error!("Unable to count the failed post of announcement #{}: {}", row.AnnouncementId, why);
+                        }
+                        continue;
+                    }
+                }
+            }
+        }
+
+        let timezone = parse_timezone(&row.Timezone).unwrap_or(Tz::UTC);
+        let next_run = row
+            .Recurrence
+            .as_deref()
+            .filter(|_| !gone)
+            .and_then(|r| CronSchedule::parse(r).ok())
+            .and_then(|schedule| schedule.next_after(now.with_timezone(&timezone)));
+
+        let moved = match next_run {
+            Some(next_run) => {
+                let next_run_timestamp = next_run.timestamp();
+                query!(
+                    "UPDATE ScheduledAnnouncement SET NextRun = ?, FailedAttempts = 0 WHERE AnnouncementId = ?",
+                    next_run_timestamp,
+                    row.AnnouncementId
+                )
+                .execute(pool)
+                .await
+            }
+            None => {
+                query!(
+                    "DELETE FROM ScheduledAnnouncement WHERE AnnouncementId = ?",
+                    row.AnnouncementId
+                )
+                .execute(pool)
+                .await
+            }
+        };
+        //the rest of the batch still goes out, this one is posted again on the next tick
+        if let Err(why) = moved {
+            error!("Unable to move announcement #{} to its next run: {}", row.AnnouncementId, why);
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the text and a copy of the saved embed message's embeds, `false` when there's neither
+/// because the saved embed is gone
+async fn post_announcement(
+    http: &Http,
+    pool: &SqlitePool,
+    channel_id: ChannelId,
+    content: Option<&str>,
+    embed_id: Option<i64>,
+) -> Result<bool> {
+    let mut embeds: Vec<CreateEmbed> = Vec::new();
+
+    if let Some(embed_id) = embed_id {
+        let saved = query!("SELECT ChannelId FROM Embed WHERE EmbedId = ?", embed_id)
+            .fetch_optional(pool)
+            .await?;
+
+        match saved {
+            Some(saved) => {
+                let message = ChannelId(saved.ChannelId as u64)
+                    .message(http, embed_id as u64)
+                    .await?;
+                embeds.extend(message.embeds.into_iter().map(CreateEmbed::from));
+            }
+            None => error!("Saved embed {} no longer exists", embed_id),
+        }
+    }
+
+    if content.is_none() && embeds.is_empty() {
+        return Ok(false);
+    }
+
+    channel_id
+        .send_message(http, |m| {
+            if let Some(content) = content {
+                m.content(content);
+            }
+            m.set_embeds(embeds)
+        })
+        .await?;
+
+    Ok(true)
+}
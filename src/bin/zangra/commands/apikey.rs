@@ -0,0 +1,112 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serenity::{
+    builder::CreateEmbed, client::Context,
+    model::application::interaction::application_command::ApplicationCommandInteraction, utils::Color,
+};
+use sqlx::query;
+
+use crate::{
+    rest_api::auth::{generate_api_key, hash_token},
+    utils::{
+        database::DatabasePool,
+        interaction::{option_i64, option_str, respond_embed},
+    },
+};
+
+/// `/apikey create`, `/apikey list` and `/apikey revoke`, keys only ever reach the guild they're made in
+pub async fn apikey(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let subcommand = command
+        .data
+        .options
+        .first()
+        .ok_or(anyhow!("No subcommand given for apikey"))?;
+    let guild_id = command.guild_id.ok_or(anyhow!("apikey used outside of a guild"))?;
+    let guild_id_i64 = *guild_id.as_u64() as i64;
+
+    let data = ctx.data.read().await;
+    let pool = data.get::<DatabasePool>().unwrap().clone();
+
+    let mut embed = CreateEmbed::default();
+
+    match subcommand.name.as_str() {
+        "create" => {
+            let name = option_str(&subcommand.options, "name").unwrap_or("Unnamed").to_string();
+            let key = generate_api_key();
+            let key_hash = hash_token(&key);
+            let created_by = *command.user.id.as_u64() as i64;
+            let created_at = Utc::now().timestamp();
+
+            let key_id = query!(
+                "INSERT INTO ApiKey (GuildId, Name, KeyHash, CreatedBy, CreatedAt) VALUES (?, ?, ?, ?, ?)",
+                guild_id_i64,
+                name,
+                key_hash,
+                created_by,
+                created_at
+            )
+            .execute(&pool)
+            .await?
+            .last_insert_rowid();
+
+            embed.title(format!("API key #{key_id} created"));
+            embed.description(format!(
+                "||`{key}`||\nSend it as `Authorization: Bearer <key>`. It won't be shown again."
+            ));
+            embed.field("Name", name, true);
+            embed.color(Color::DARK_GREEN);
+        }
+        "list" => {
+            let rows = query!(
+                "SELECT ApiKeyId, Name, CreatedBy, CreatedAt, LastUsedAt FROM ApiKey
+                WHERE GuildId = ?
+                ORDER BY ApiKeyId",
+                guild_id_i64
+            )
+            .fetch_all(&pool)
+            .await?;
+
+            let description = rows
+                .iter()
+                .map(|row| {
+                    let last_used = match row.LastUsedAt {
+                        Some(last_used) => format!("last used <t:{last_used}:R>"),
+                        None => "never used".to_string(),
+                    };
+                    format!(
+                        "**#{}** {} by <@{}> <t:{}:d>, {}",
+                        row.ApiKeyId, row.Name, row.CreatedBy, row.CreatedAt, last_used
+                    )
+                })
+                .reduce(|a, b| a + "\n" + &b)
+                .unwrap_or("This server has no API keys".to_string());
+
+            embed.title("API keys");
+            embed.description(description);
+        }
+        "revoke" => {
+            let id = option_i64(&subcommand.options, "id").ok_or(anyhow!("No id given for apikey revoke"))?;
+
+            let result = query!(
+                "DELETE FROM ApiKey WHERE ApiKeyId = ? AND GuildId = ?",
+                id,
+                guild_id_i64
+            )
+            .execute(&pool)
+            .await?;
+
+            if result.rows_affected() > 0 {
+                embed.title(format!("API key #{id} revoked"));
+                embed.color(Color::DARK_GREEN);
+            } else {
+                embed.title(format!("There is no API key #{id} in this server"));
+                embed.color(Color::RED);
+            }
+        }
+        _ => return Ok(()),
+    }
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use anyhow::{anyhow, Result};
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    model::{application::interaction::application_command::ApplicationCommandInteraction, id::MessageId},
+    utils::Color,
+};
+
+use crate::utils::{
+    database::DatabasePool,
+    embeds::{embed_revisions, rollback_embed_message, saved_embed_channel, EmbedError, RevisionAuthor},
+    interaction::{option_i64, option_str, respond_embed},
+};
+
+//the newest ones, older revisions are still reachable through the API
+const HISTORY_LIMIT: usize = 20;
+
+/// `/embed history` and `/embed rollback` for the server's saved embeds
+pub async fn embed(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let subcommand = command
+        .data
+        .options
+        .first()
+        .ok_or(anyhow!("No subcommand given for embed"))?;
+    let options = &subcommand.options;
+    let guild_id = command.guild_id.ok_or(anyhow!("embed used outside of a guild"))?;
+
+    let data = ctx.data.read().await;
+    let pool = data.get::<DatabasePool>().unwrap().clone();
+
+    let message = option_str(options, "message").ok_or(anyhow!("No message given for embed"))?;
+    let message_id = match message.trim().parse::<u64>() {
+        Ok(id) => MessageId(id),
+        Err(_) => return respond_error(ctx, command, format!("`{message}` is not a message ID")).await,
+    };
+    let channel_id = match saved_embed_channel(&pool, guild_id, message_id).await? {
+        Some(channel_id) => channel_id,
+        None => {
+            return respond_error(ctx, command, "That message isn't a saved embed in this server".to_string()).await;
+        }
+    };
+    let link = format!("https://discord.com/channels/{guild_id}/{channel_id}/{message_id}");
+
+    let mut embed = CreateEmbed::default();
+
+    match subcommand.name.as_str() {
+        "history" => {
+            let revisions = embed_revisions(&pool, guild_id, channel_id, message_id).await?;
+
+            let description = revisions
+                .iter()
+                .rev()
+                .take(HISTORY_LIMIT)
+                .map(|r| {
+                    let author = match r.author.user_id {
+                        Some(user_id) => format!("<@{user_id}>"),
+                        None => r.author.name.clone(),
+                    };
+                    format!("**#{}** by {} <t:{}:f>", r.revision, author, r.created_at)
+                })
+                .reduce(|a, b| a + "\n" + &b)
+                .unwrap_or("No revisions have been saved".to_string());
+
+            embed.title("Embed history");
+            embed.url(link);
+            embed.description(description);
+            if revisions.len() > HISTORY_LIMIT {
+                embed.footer(|f| f.text(format!("Showing the newest {HISTORY_LIMIT} of {}", revisions.len())));
+            }
+        }
+        "rollback" => {
+            let revision = option_i64(options, "revision").ok_or(anyhow!("No revision given for embed rollback"))?;
+            let author = RevisionAuthor {
+                user_id: Some(command.user.id),
+                name: command.user.name.clone(),
+            };
+
+            match rollback_embed_message(ctx, &pool, guild_id, channel_id, message_id, revision, &author).await {
+                Ok(_) => {
+                    embed.title(format!("Embed rolled back to revision #{revision}"));
+                    embed.url(link);
+                    embed.description("The rollback is saved as a new revision, see `/embed history`");
+                    embed.color(Color::DARK_GREEN);
+                }
+                Err(EmbedError::UnknownRevision) => {
+                    return respond_error(ctx, command, format!("That embed has no revision #{revision}")).await;
+                }
+                Err(why) => return Err(why.into()),
+            }
+        }
+        _ => return Ok(()),
+    }
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
+
+async fn respond_error(ctx: &Context, command: &ApplicationCommandInteraction, message: String) -> Result<()> {
+    let mut embed = CreateEmbed::default();
+    embed.title("Unable to manage embed");
+    embed.description(message);
+    embed.color(Color::RED);
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
@@ -1,18 +1,867 @@
+use std::fmt::{self, Display, Formatter};
+
+use num_bigint::BigInt;
+use num_traits::{Pow, Signed, ToPrimitive, Zero};
 use serenity::framework::standard::{macros::command, Args, CommandResult};
 use serenity::client::Context;
+use serenity::model::application::interaction::{
+    application_command::ApplicationCommandInteraction, InteractionResponseType,
+};
 use serenity::model::channel::Message;
 
+use crate::utils::interaction::{option_bool, option_str};
+
 //Multiplies 2 f64
 #[command]
 pub async fn multiply(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let one = args.single::<f64>().unwrap();
-    let two = args.single::<f64>().unwrap();
-
-    let product = one * two;
+    let content = match (args.single::<f64>(), args.single::<f64>()) {
+        (Ok(one), Ok(two)) => product(one, two).to_string(),
+        _ => "Usage: ~multiply <number> <number>".to_string(),
+    };
 
-    if let Err(why) = msg.channel_id.say(&ctx.http, product).await {
+    if let Err(why) = msg.channel_id.say(&ctx.http, content).await {
         println!("Error with multiply: {why}");
     };
 
     Ok(())
 }
+
+pub async fn multiply_slash(ctx: &Context, command: &ApplicationCommandInteraction) -> serenity::Result<()> {
+    let number = |name: &str| {
+        command
+            .data
+            .options
+            .iter()
+            .find(|o| o.name == name)
+            .and_then(|o| o.value.as_ref())
+            .and_then(|v| v.as_f64())
+            .unwrap_or_default()
+    };
+
+    let content = product(number("first"), number("second")).to_string();
+
+    command
+        .create_interaction_response(&ctx, |response| {
+            response.kind(InteractionResponseType::ChannelMessageWithSource);
+            response.interaction_response_data(|data| data.content(content).ephemeral(true))
+        })
+        .await
+}
+
+fn product(one: f64, two: f64) -> f64 {
+    one * two
+}
+
+//Evaluates an arithmetic expression, `~calc --int <expression>` switches to big integer mode
+#[command]
+pub async fn calc(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let input = args.rest();
+    let content = match input.strip_prefix("--int") {
+        Some(expression) => calculate(expression.trim_start(), true),
+        None => calculate(input, false),
+    };
+
+    if let Err(why) = msg.channel_id.say(&ctx.http, content).await {
+        println!("Error with calc: {why}");
+    };
+
+    Ok(())
+}
+
+pub async fn calc_slash(ctx: &Context, command: &ApplicationCommandInteraction) -> serenity::Result<()> {
+    let expression = option_str(&command.data.options, "expression").unwrap_or_default();
+    let integer_mode = option_bool(&command.data.options, "integer").unwrap_or(false);
+
+    let content = calculate(expression, integer_mode);
+
+    command
+        .create_interaction_response(&ctx, |response| {
+            response.kind(InteractionResponseType::ChannelMessageWithSource);
+            response.interaction_response_data(|data| data.content(content).ephemeral(true))
+        })
+        .await
+}
+
+/// Evaluates `input` and formats either the result or the error for a Discord reply
+fn calculate(input: &str, integer_mode: bool) -> String {
+    let result = if integer_mode {
+        evaluate_integer(input).map(|n| truncate_digits(n.to_string()))
+    } else {
+        evaluate_float(input).map(format_float)
+    };
+
+    match result {
+        Ok(value) => format!("`{}` = **{}**", shorten(input.trim()), value),
+        Err(why) => why.render(input),
+    }
+}
+
+//Discord messages are capped at 2000 characters
+const MAX_RESULT_LENGTH: usize = 1800;
+//how much of the expression a reply repeats, the result gets the rest of the message
+const MAX_ECHO_LENGTH: usize = 100;
+//longer expressions aren't parsed at all
+const MAX_INPUT_LENGTH: usize = 500;
+//how deep parentheses, signs and exponents may nest, the parser and evaluator recurse on each
+const MAX_DEPTH: usize = 64;
+
+fn shorten(input: &str) -> String {
+    if input.chars().count() <= MAX_ECHO_LENGTH {
+        return input.to_string();
+    }
+
+    input.chars().take(MAX_ECHO_LENGTH).collect::<String>() + "…"
+}
+
+fn truncate_digits(digits: String) -> String {
+    if digits.len() <= MAX_RESULT_LENGTH {
+        return digits;
+    }
+
+    format!("{}… ({} digits)", &digits[..MAX_RESULT_LENGTH], digits.trim_start_matches('-').len())
+}
+
+fn format_float(value: f64) -> String {
+    if value == value.trunc() && value.abs() < 1e15 {
+        format!("{}", value as i64)
+    } else if value.abs() >= 1e15 || value.abs() < 1e-9 {
+        format!("{value:e}")
+    } else {
+        format!("{value}")
+    }
+}
+
+/// An error while parsing or evaluating, `position` is the character index it refers to
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalcError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl CalcError {
+    fn new<S: Into<String>>(position: usize, message: S) -> CalcError {
+        CalcError {
+            position,
+            message: message.into(),
+        }
+    }
+
+    /// Shows the expression with a caret under the offending character, long expressions are cut
+    /// down to the part around it
+    pub fn render(&self, input: &str) -> String {
+        let chars: Vec<char> = input.replace('`', "'").chars().collect();
+        let start = if self.position < MAX_ECHO_LENGTH { 0 } else { self.position - MAX_ECHO_LENGTH / 2 };
+        let end = chars.len().min(start + MAX_ECHO_LENGTH);
+
+        let mut shown: String = chars[start.min(end)..end].iter().collect();
+        let mut offset = self.position - start;
+        if start > 0 {
+            shown.insert(0, '…');
+            offset += 1;
+        }
+        if end < chars.len() {
+            shown.push('…');
+        }
+        let caret = " ".repeat(offset) + "^";
+
+        format!("```\n{shown}\n{caret}\n```{}", self)
+    }
+}
+
+impl Display for CalcError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Error at position {}: {}", self.position + 1, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(String),
+    Ident(String),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Caret,
+    LeftParen,
+    RightParen,
+    Comma,
+    End,
+}
+
+impl Display for TokenKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::Number(n) => write!(f, "`{n}`"),
+            TokenKind::Ident(i) => write!(f, "`{i}`"),
+            TokenKind::Plus => f.write_str("`+`"),
+            TokenKind::Minus => f.write_str("`-`"),
+            TokenKind::Star => f.write_str("`*`"),
+            TokenKind::Slash => f.write_str("`/`"),
+            TokenKind::Percent => f.write_str("`%`"),
+            TokenKind::Caret => f.write_str("`^`"),
+            TokenKind::LeftParen => f.write_str("`(`"),
+            TokenKind::RightParen => f.write_str("`)`"),
+            TokenKind::Comma => f.write_str("`,`"),
+            TokenKind::End => f.write_str("end of input"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, CalcError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let position = i;
+
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit() || c == '.' {
+            let mut literal = String::new();
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                literal.push(chars[i]);
+                i += 1;
+            }
+
+            //scientific notation, only when the exponent has digits so `2e` stays 2 * e
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let sign = matches!(chars.get(i + 1), Some('+') | Some('-'));
+                let digit_index = if sign { i + 2 } else { i + 1 };
+                if chars.get(digit_index).is_some_and(|d| d.is_ascii_digit()) {
+                    literal.extend(&chars[i..digit_index]);
+                    i = digit_index;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        literal.push(chars[i]);
+                        i += 1;
+                    }
+                }
+            }
+
+            if literal.matches('.').count() > 1 || literal == "." {
+                return Err(CalcError::new(position, format!("`{literal}` is not a valid number")));
+            }
+
+            tokens.push(Token { kind: TokenKind::Number(literal), position });
+            continue;
+        }
+
+        if c.is_alphabetic() {
+            let mut ident = String::new();
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                ident.push(chars[i]);
+                i += 1;
+            }
+            tokens.push(Token { kind: TokenKind::Ident(ident.to_lowercase()), position });
+            continue;
+        }
+
+        let kind = match c {
+            '+' => TokenKind::Plus,
+            '-' | '−' => TokenKind::Minus,
+            '*' | '×' if chars.get(i + 1) == Some(&'*') => {
+                i += 1;
+                TokenKind::Caret
+            }
+            '*' | '×' => TokenKind::Star,
+            '/' | '÷' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '^' => TokenKind::Caret,
+            '(' => TokenKind::LeftParen,
+            ')' => TokenKind::RightParen,
+            ',' => TokenKind::Comma,
+            _ => return Err(CalcError::new(position, format!("unexpected character `{c}`"))),
+        };
+        tokens.push(Token { kind, position });
+        i += 1;
+    }
+
+    tokens.push(Token { kind: TokenKind::End, position: chars.len() });
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Constant {
+    Pi,
+    E,
+    Tau,
+}
+
+impl Constant {
+    fn from_name(name: &str) -> Option<Constant> {
+        match name {
+            "pi" | "π" => Some(Constant::Pi),
+            "e" => Some(Constant::E),
+            "tau" | "τ" => Some(Constant::Tau),
+            _ => None,
+        }
+    }
+
+    fn value(&self) -> f64 {
+        match self {
+            Constant::Pi => std::f64::consts::PI,
+            Constant::E => std::f64::consts::E,
+            Constant::Tau => std::f64::consts::TAU,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Sqrt,
+    Cbrt,
+    Abs,
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Ln,
+    Log,
+    Log2,
+    Exp,
+    Floor,
+    Ceil,
+    Round,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "sqrt" => Some(Function::Sqrt),
+            "cbrt" => Some(Function::Cbrt),
+            "abs" => Some(Function::Abs),
+            "sin" => Some(Function::Sin),
+            "cos" => Some(Function::Cos),
+            "tan" => Some(Function::Tan),
+            "asin" => Some(Function::Asin),
+            "acos" => Some(Function::Acos),
+            "atan" => Some(Function::Atan),
+            "ln" => Some(Function::Ln),
+            "log" => Some(Function::Log),
+            "log2" => Some(Function::Log2),
+            "exp" => Some(Function::Exp),
+            "floor" => Some(Function::Floor),
+            "ceil" => Some(Function::Ceil),
+            "round" => Some(Function::Round),
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            _ => None,
+        }
+    }
+
+    /// Minimum and maximum number of arguments
+    fn arity(&self) -> (usize, usize) {
+        match self {
+            Function::Log => (1, 2),
+            Function::Min | Function::Max => (1, usize::MAX),
+            _ => (1, 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number { literal: String, position: usize },
+    Constant { constant: Constant, position: usize },
+    Negate(Box<Expr>),
+    Binary { op: BinaryOp, lhs: Box<Expr>, rhs: Box<Expr>, position: usize },
+    Call { function: Function, name: String, args: Vec<Expr>, position: usize },
+}
+
+/// Recursive descent parser, from lowest to highest precedence:
+/// `+ -`, `* / %` (and implicit multiplication), unary `-`, `^` (right associative),
+/// then numbers, names and parentheses
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    //how many `unary` calls are on the stack, every kind of nesting goes through it
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.index].clone();
+        if token.kind != TokenKind::End {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<Token, CalcError> {
+        let token = self.advance();
+        if token.kind == kind {
+            Ok(token)
+        } else {
+            Err(CalcError::new(token.position, format!("expected {kind} but found {}", token.kind)))
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr, CalcError> {
+        let mut lhs = self.term()?;
+
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinaryOp::Add,
+                TokenKind::Minus => BinaryOp::Subtract,
+                _ => return Ok(lhs),
+            };
+            let position = self.advance().position;
+            let rhs = self.term()?;
+            lhs = Expr::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs), position };
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, CalcError> {
+        let mut lhs = self.unary()?;
+
+        loop {
+            let (op, position) = match self.peek().kind {
+                TokenKind::Star => (BinaryOp::Multiply, self.advance().position),
+                TokenKind::Slash => (BinaryOp::Divide, self.advance().position),
+                TokenKind::Percent => (BinaryOp::Modulo, self.advance().position),
+                //implicit multiplication, 2pi or 3(1 + 2)
+                TokenKind::Ident(_) | TokenKind::LeftParen => (BinaryOp::Multiply, self.peek().position),
+                _ => return Ok(lhs),
+            };
+            let rhs = self.unary()?;
+            lhs = Expr::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs), position };
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, CalcError> {
+        if self.depth == MAX_DEPTH {
+            return Err(CalcError::new(self.peek().position, "expression is nested too deeply"));
+        }
+
+        self.depth += 1;
+        let result = self.signed();
+        self.depth -= 1;
+
+        result
+    }
+
+    fn signed(&mut self) -> Result<Expr, CalcError> {
+        match self.peek().kind {
+            TokenKind::Minus => {
+                self.advance();
+                Ok(Expr::Negate(Box::new(self.unary()?)))
+            }
+            TokenKind::Plus => {
+                self.advance();
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<Expr, CalcError> {
+        let base = self.primary()?;
+
+        if self.peek().kind == TokenKind::Caret {
+            let position = self.advance().position;
+            //the exponent may carry its own sign, 2^-1
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary { op: BinaryOp::Power, lhs: Box::new(base), rhs: Box::new(exponent), position });
+        }
+
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<Expr, CalcError> {
+        let token = self.advance();
+
+        match token.kind {
+            TokenKind::Number(literal) => Ok(Expr::Number { literal, position: token.position }),
+            TokenKind::LeftParen => {
+                let inner = self.expression()?;
+                self.expect(TokenKind::RightParen)?;
+                Ok(inner)
+            }
+            TokenKind::Ident(name) => {
+                if self.peek().kind == TokenKind::LeftParen {
+                    let function = Function::from_name(&name)
+                        .ok_or_else(|| CalcError::new(token.position, format!("unknown function `{name}`")))?;
+                    self.advance();
+
+                    let mut args = vec![self.expression()?];
+                    while self.peek().kind == TokenKind::Comma {
+                        self.advance();
+                        args.push(self.expression()?);
+                    }
+                    let close = self.expect(TokenKind::RightParen)?;
+
+                    let (min, max) = function.arity();
+                    if args.len() < min || args.len() > max {
+                        return Err(CalcError::new(close.position, format!("`{name}` takes {}", describe_arity(min, max))));
+                    }
+
+                    return Ok(Expr::Call { function, name, args, position: token.position });
+                }
+
+                match Constant::from_name(&name) {
+                    Some(constant) => Ok(Expr::Constant { constant, position: token.position }),
+                    None if Function::from_name(&name).is_some() => {
+                        Err(CalcError::new(token.position, format!("`{name}` is a function, use `{name}(...)`")))
+                    }
+                    None => Err(CalcError::new(token.position, format!("unknown constant `{name}`"))),
+                }
+            }
+            TokenKind::End => Err(CalcError::new(token.position, "expected a number or `(`")),
+            kind => Err(CalcError::new(token.position, format!("expected a number or `(` but found {kind}"))),
+        }
+    }
+}
+
+fn describe_arity(min: usize, max: usize) -> String {
+    match (min, max) {
+        (1, 1) => "1 argument".to_string(),
+        (min, usize::MAX) => format!("at least {min} argument(s)"),
+        (min, max) => format!("{min} to {max} arguments"),
+    }
+}
+
+fn parse(input: &str) -> Result<Expr, CalcError> {
+    if input.chars().count() > MAX_INPUT_LENGTH {
+        return Err(CalcError::new(MAX_INPUT_LENGTH, format!("expressions are limited to {MAX_INPUT_LENGTH} characters")));
+    }
+
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        index: 0,
+        depth: 0,
+    };
+
+    let expr = parser.expression()?;
+
+    let trailing = parser.advance();
+    if trailing.kind != TokenKind::End {
+        return Err(CalcError::new(trailing.position, format!("unexpected {}", trailing.kind)));
+    }
+
+    Ok(expr)
+}
+
+fn evaluate_float(input: &str) -> Result<f64, CalcError> {
+    eval_float(&parse(input)?)
+}
+
+fn eval_float(expr: &Expr) -> Result<f64, CalcError> {
+    let value = match expr {
+        Expr::Number { literal, position } => literal
+            .parse::<f64>()
+            .map_err(|_| CalcError::new(*position, format!("`{literal}` is not a valid number")))?,
+        Expr::Constant { constant, .. } => constant.value(),
+        Expr::Negate(inner) => -eval_float(inner)?,
+        Expr::Binary { op, lhs, rhs, position } => {
+            let lhs = eval_float(lhs)?;
+            let rhs = eval_float(rhs)?;
+
+            let value = match op {
+                BinaryOp::Add => lhs + rhs,
+                BinaryOp::Subtract => lhs - rhs,
+                BinaryOp::Multiply => lhs * rhs,
+                BinaryOp::Divide | BinaryOp::Modulo if rhs == 0.0 => {
+                    return Err(CalcError::new(*position, "division by zero"));
+                }
+                BinaryOp::Divide => lhs / rhs,
+                BinaryOp::Modulo => lhs % rhs,
+                BinaryOp::Power => lhs.powf(rhs),
+            };
+
+            if !value.is_finite() {
+                return Err(CalcError::new(*position, "result is not a finite number"));
+            }
+            value
+        }
+        Expr::Call { function, name, args, position } => {
+            let args = args.iter().map(eval_float).collect::<Result<Vec<f64>, CalcError>>()?;
+            let x = args[0];
+
+            let value = match function {
+                Function::Sqrt => x.sqrt(),
+                Function::Cbrt => x.cbrt(),
+                Function::Abs => x.abs(),
+                Function::Sin => x.sin(),
+                Function::Cos => x.cos(),
+                Function::Tan => x.tan(),
+                Function::Asin => x.asin(),
+                Function::Acos => x.acos(),
+                Function::Atan => x.atan(),
+                Function::Ln => x.ln(),
+                Function::Log => match args.get(1) {
+                    Some(base) => x.log(*base),
+                    None => x.log10(),
+                },
+                Function::Log2 => x.log2(),
+                Function::Exp => x.exp(),
+                Function::Floor => x.floor(),
+                Function::Ceil => x.ceil(),
+                Function::Round => x.round(),
+                Function::Min => args.iter().copied().fold(f64::INFINITY, f64::min),
+                Function::Max => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            };
+
+            if !value.is_finite() {
+                return Err(CalcError::new(*position, format!("`{name}` is undefined for this input")));
+            }
+            value
+        }
+    };
+
+    Ok(value)
+}
+
+//Upper bound on intermediate results in integer mode, keeps 9^9^9 from eating the bot
+const MAX_INTEGER_BITS: u64 = 1 << 16;
+
+fn evaluate_integer(input: &str) -> Result<BigInt, CalcError> {
+    eval_integer(&parse(input)?)
+}
+
+fn eval_integer(expr: &Expr) -> Result<BigInt, CalcError> {
+    let value = match expr {
+        Expr::Number { literal, position } => literal
+            .parse::<BigInt>()
+            .map_err(|_| CalcError::new(*position, format!("`{literal}` is not a whole number, integer mode only accepts integers")))?,
+        Expr::Constant { position, .. } => {
+            return Err(CalcError::new(*position, "constants are not available in integer mode"));
+        }
+        Expr::Negate(inner) => -eval_integer(inner)?,
+        Expr::Binary { op, lhs, rhs, position } => {
+            let lhs = eval_integer(lhs)?;
+            let rhs = eval_integer(rhs)?;
+
+            match op {
+                BinaryOp::Add => lhs + rhs,
+                BinaryOp::Subtract => lhs - rhs,
+                BinaryOp::Multiply => {
+                    if lhs.bits() + rhs.bits() > MAX_INTEGER_BITS {
+                        return Err(CalcError::new(*position, "result is too large"));
+                    }
+                    lhs * rhs
+                }
+                BinaryOp::Divide | BinaryOp::Modulo if rhs.is_zero() => {
+                    return Err(CalcError::new(*position, "division by zero"));
+                }
+                //rounds toward zero, remainder takes the sign of the dividend
+                BinaryOp::Divide => lhs / rhs,
+                BinaryOp::Modulo => lhs % rhs,
+                BinaryOp::Power => {
+                    if rhs.is_negative() {
+                        return Err(CalcError::new(*position, "negative exponents are not available in integer mode"));
+                    }
+                    let exponent = rhs
+                        .to_u32()
+                        .filter(|e| lhs.bits() <= 1 || lhs.bits().saturating_mul(*e as u64) <= MAX_INTEGER_BITS)
+                        .ok_or_else(|| CalcError::new(*position, "result is too large"))?;
+                    Pow::pow(lhs, exponent)
+                }
+            }
+        }
+        Expr::Call { function, name, args, position } => {
+            let args = args.iter().map(eval_integer).collect::<Result<Vec<BigInt>, CalcError>>()?;
+
+            match function {
+                Function::Abs => args[0].abs(),
+                Function::Min => args.into_iter().min().unwrap_or_default(),
+                Function::Max => args.into_iter().max().unwrap_or_default(),
+                _ => {
+                    return Err(CalcError::new(*position, format!("`{name}` is not available in integer mode")));
+                }
+            }
+        }
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(input: &str) -> f64 {
+        evaluate_float(input).unwrap()
+    }
+
+    fn eval_int(input: &str) -> String {
+        evaluate_integer(input).unwrap().to_string()
+    }
+
+    fn error(input: &str) -> CalcError {
+        evaluate_float(input).unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("2 + 3 * 4"), 14.0);
+        assert_eq!(eval("2 * 3 + 4"), 10.0);
+        assert_eq!(eval("10 - 4 - 3"), 3.0);
+        assert_eq!(eval("100 / 10 / 5"), 2.0);
+        assert_eq!(eval("7 % 4 * 2"), 6.0);
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(eval("(2 + 3) * 4"), 20.0);
+        assert_eq!(eval("((1))"), 1.0);
+        assert_eq!(eval("2 * (3 + (4 - 1)) / 3"), 4.0);
+    }
+
+    #[test]
+    fn exponent_is_right_associative_and_binds_tighter_than_negation() {
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(eval("-2 ^ 2"), -4.0);
+        assert_eq!(eval("(-2) ^ 2"), 4.0);
+        assert_eq!(eval("2 ^ -1"), 0.5);
+        assert_eq!(eval("2 ** 10"), 1024.0);
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(eval(".5 + 1.25"), 1.75);
+        assert_eq!(eval("1e3"), 1000.0);
+        assert_eq!(eval("2.5E-1"), 0.25);
+        assert_eq!(eval("--3"), 3.0);
+    }
+
+    #[test]
+    fn implicit_multiplication() {
+        assert_eq!(eval("2(3 + 1)"), 8.0);
+        assert_eq!(eval("(1 + 1)(2 + 2)"), 8.0);
+        assert_eq!(eval("4sqrt(4)"), 8.0);
+        assert_eq!(error("2 3").position, 2);
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(eval("pi"), std::f64::consts::PI);
+        assert_eq!(eval("2e"), 2.0 * std::f64::consts::E);
+        assert_eq!(eval("tau / 2"), std::f64::consts::PI);
+        assert_eq!(eval("PI"), std::f64::consts::PI);
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(eval("sqrt(16)"), 4.0);
+        assert!(eval("sin(pi / 2)") - 1.0 < 1e-12);
+        assert_eq!(eval("log(1000)"), 3.0);
+        assert_eq!(eval("log(8, 2)"), 3.0);
+        assert_eq!(eval("ln(e)"), 1.0);
+        assert_eq!(eval("abs(-3) + floor(2.7) + ceil(0.2)"), 6.0);
+        assert_eq!(eval("max(1, 5, 3) - min(4, 2)"), 3.0);
+        assert_eq!(eval("sqrt(9)^2"), 9.0);
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_offending_position() {
+        assert_eq!(error("2 + * 3").position, 4);
+        assert_eq!(error("(1 + 2").position, 6);
+        assert_eq!(error("1 + 2)").position, 5);
+        assert_eq!(error("3 $ 4").position, 2);
+        assert_eq!(error("").position, 0);
+        assert_eq!(error("1..2").position, 0);
+        assert_eq!(error("foo(2)").message, "unknown function `foo`");
+        assert_eq!(error("2 * bar").position, 4);
+        assert_eq!(error("sqrt").message, "`sqrt` is a function, use `sqrt(...)`");
+        assert_eq!(error("log(1, 2, 3)").position, 11);
+    }
+
+    #[test]
+    fn evaluation_errors() {
+        assert_eq!(error("1 / (2 - 2)").position, 2);
+        assert_eq!(error("5 % 0").message, "division by zero");
+        assert_eq!(error("sqrt(-1)").position, 0);
+        assert_eq!(error("10 ^ 400").message, "result is not a finite number");
+    }
+
+    #[test]
+    fn deep_nesting_is_refused() {
+        let parentheses = "(".repeat(100) + "1" + &")".repeat(100);
+        assert_eq!(error(&parentheses).message, "expression is nested too deeply");
+        assert_eq!(error(&"-".repeat(200)).message, "expression is nested too deeply");
+        assert_eq!(error(&"2^".repeat(100)).message, "expression is nested too deeply");
+        assert_eq!(error(&"(".repeat(5000)).message, "expressions are limited to 500 characters");
+        assert_eq!(eval(&("(".repeat(60) + "1" + &")".repeat(60))), 1.0);
+    }
+
+    #[test]
+    fn long_expressions_fit_in_a_message() {
+        let sum = vec!["1"; 250].join("+");
+        let reply = calculate(&sum, false);
+        assert!(reply.ends_with("…` = **250**"));
+        assert!(reply.chars().count() < 200);
+
+        //the error is at the very end, only the 50 characters before it are shown
+        let unfinished = sum + "+";
+        let rendered = error(&unfinished).render(&unfinished);
+        assert!(rendered.starts_with("```\n…1+1+"));
+        assert!(rendered.contains(&format!("\n{}^\n", " ".repeat(51))));
+        assert!(rendered.chars().count() < 200);
+    }
+
+    #[test]
+    fn render_places_caret_under_error() {
+        let rendered = error("2 + * 3").render("2 + * 3");
+        assert!(rendered.contains("2 + * 3\n    ^\n"));
+        assert!(rendered.ends_with("Error at position 5: expected a number or `(` but found `*`"));
+    }
+
+    #[test]
+    fn integer_mode() {
+        assert_eq!(eval_int("2 ^ 100"), "1267650600228229401496703205376");
+        assert_eq!(eval_int("(10 ^ 20 + 1) * 3"), "300000000000000000003");
+        assert_eq!(eval_int("7 / 2"), "3");
+        assert_eq!(eval_int("-7 % 3"), "-1");
+        assert_eq!(eval_int("abs(-5) + max(1, 9)"), "14");
+        assert_eq!(eval_int("1 ^ 4000000000"), "1");
+    }
+
+    #[test]
+    fn integer_mode_errors() {
+        assert_eq!(evaluate_integer("1.5 + 1").unwrap_err().position, 0);
+        assert_eq!(evaluate_integer("2 * pi").unwrap_err().position, 4);
+        assert_eq!(evaluate_integer("sqrt(4)").unwrap_err().message, "`sqrt` is not available in integer mode");
+        assert_eq!(evaluate_integer("2 ^ -1").unwrap_err().position, 2);
+        assert_eq!(evaluate_integer("9 ^ 9 ^ 9").unwrap_err().message, "result is too large");
+        assert_eq!(evaluate_integer("4 / 0").unwrap_err().message, "division by zero");
+    }
+
+    #[test]
+    fn calculate_formats_results() {
+        assert_eq!(calculate("1 + 1", false), "`1 + 1` = **2**");
+        assert_eq!(calculate("1 / 4", false), "`1 / 4` = **0.25**");
+        assert_eq!(calculate("2 ^ 64", true), "`2 ^ 64` = **18446744073709551616**");
+        assert!(calculate("10 ^ 3000", true).ends_with("… (3001 digits)**"));
+    }
+}
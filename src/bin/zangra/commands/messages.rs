@@ -9,8 +9,9 @@ use itertools::Itertools;
 use rand::distributions::{Distribution, Uniform};
 
 use serde_json::{json, Value};
+use sqlx::SqlitePool;
 use serenity::{
-    builder::{CreateActionRow, CreateEmbed, CreateSelectMenu, CreateSelectMenuOption},
+    builder::{CreateActionRow, CreateEmbed, CreateSelectMenuOption},
     client::Context,
     collector::EventCollectorBuilder,
     framework::standard::{macros::command, CommandResult},
@@ -27,13 +28,22 @@ use serenity::{
         channel,
         channel::{Embed, Message, MessageReference},
         event::{Event, EventType},
-        guild::Role,
-        id::{ChannelId, GuildId, RoleId},
+        guild::{Member, Role},
+        id::{ChannelId, GuildId, RoleId, UserId},
     },
     utils::Color,
 };
 
-use crate::DatabasePool;
+use crate::{
+    utils::guild_events::{publish_guild_event, GuildEventKind},
+    utils::metrics::client_metrics,
+    utils::role_selectors::{
+        button_role, edit_role_selector_message, is_role_selector, role_grantor, role_selector_components,
+        save_role_selector, saved_role_selector, validate_role_selector, RoleOption, RoleSelector,
+        RoleSelectorStyle, MENU_CUSTOM_ID,
+    },
+    DatabasePool,
+};
 
 fn random_color() -> Color {
     let mut rng = rand::thread_rng();
@@ -45,11 +55,10 @@ fn random_color() -> Color {
     )
 }
 
-struct RoleSelector {
+/// A finished wizard, `setup_message` already shows the selector
+struct RoleSelectorSetup {
     setup_message: Message,
-    content: String,
-    embeds: Vec<CreateEmbed>,
-    action_rows: Vec<CreateActionRow>,
+    selector: RoleSelector,
 }
 
 pub async fn edit_role_selector<'a, C: Into<&'a Context>>(
@@ -90,47 +99,31 @@ pub async fn edit_role_selector<'a, C: Into<&'a Context>>(
     };
 
     for (message_id, message) in messages {
-        match sqlx::query(
-            "select AutoRoleMessageId from AutoRoleMessage where AutoRoleMessageId = ?",
-        )
-        .bind(*message_id.as_u64() as i64)
-        .fetch_one(&pool)
-        .await
-        {
-            Ok(_row) => {
-                let permissions = member
-                    .permissions
-                    .expect("Unable to read a member's permissions");
-                if permissions.administrator() {
-                    let channel_id = &command.channel_id;
-
-                    let role_selector = role_selection_message_setup(
-                        ctx,
-                        guild_id,
-                        *channel_id,
-                        Some(message.clone()),
-                    )
-                    .await
-                    .expect("Unable to setup role selector message");
+        if !is_role_selector(&pool, *message_id).await? {
+            continue;
+        }
 
-                    message
-                        .clone()
-                        .edit(&ctx, |m| {
-                            m.content(role_selector.content);
-                            m.set_embeds(role_selector.embeds);
-                            m.components(|c| c.set_action_rows(role_selector.action_rows))
-                        })
-                        .await?;
-                    role_selector
-                        .setup_message
-                        .delete(&ctx)
-                        .await
-                        .expect("Unable to delete role selector set up message");
-                }
-            }
-            Err(why) => {
-                println!("{why}");
-            }
+        let permissions = member
+            .permissions
+            .expect("Unable to read a member's permissions");
+        if permissions.administrator() {
+            let channel_id = &command.channel_id;
+
+            let setup = role_selection_message_setup(
+                ctx,
+                guild_id,
+                *channel_id,
+                member.user.id,
+                Some(message.clone()),
+            )
+            .await?;
+
+            edit_role_selector_message(ctx, &pool, guild_id, message.channel_id, message.id, Some(member.user.id), &setup.selector).await?;
+            setup
+                .setup_message
+                .delete(&ctx)
+                .await
+                .expect("Unable to delete role selector set up message");
         }
     }
 
@@ -145,22 +138,25 @@ pub async fn autorole_selections(ctx: &Context, mc: &MessageComponentInteraction
     })
     .await?;
 
-    let data = ctx.data.read().await;
-    let pool = data.get::<DatabasePool>().unwrap().clone();
+    let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+    let metrics = client_metrics(ctx).await;
 
     let message_id = mc.message.id;
-    let query =
-        sqlx::query("select AutoRoleMessageId from AutoRoleMessage where AutoRoleMessageId = ?")
-            .bind(*message_id.as_u64() as i64)
-            .fetch_one(&pool)
-            .await;
 
-    if query.is_ok() {
+    if metrics.time_query("role_selector_lookup", is_role_selector(&pool, message_id)).await? {
         let msg = &mc.message;
         let mut member = mc.member.clone().ok_or(anyhow!("can't retrieve member"))?;
         let member_roles = member.roles.clone();
-
-        if mc.data.custom_id.as_str() == "selectmenu" {
+        let mut added = Vec::new();
+        let mut removed = Vec::new();
+
+        if let Some(role_id) = button_role(&mc.data.custom_id) {
+            match toggle_button_role(ctx, mc, &pool, &mut member, role_id).await? {
+                Some(true) => added.push(role_id),
+                Some(false) => removed.push(role_id),
+                None => {}
+            }
+        } else if mc.data.custom_id.as_str() == MENU_CUSTOM_ID {
             for msg_component in &msg.components {
                 for ar_component in &msg_component.components {
                     match ar_component {
@@ -200,36 +196,75 @@ pub async fn autorole_selections(ctx: &Context, mc: &MessageComponentInteraction
                             member.add_roles(&ctx, &add).await?;
 
                             println!("after");
+
+                            added.extend(add);
+                            removed.extend(remove);
                         }
                         _ => todo!(),
                     }
                 }
             }
         }
+
+        if !added.is_empty() || !removed.is_empty() {
+            metrics.role_assignments(added.len(), removed.len());
+            publish_guild_event(ctx, member.guild_id, GuildEventKind::SelectorRolesChanged {
+                channel_id: msg.channel_id,
+                message_id,
+                user_id: member.user.id,
+                added,
+                removed,
+            })
+            .await;
+        }
     }
 
     Ok(())
 }
 
+/// Gives or takes the button's role, refusing to go over the selector's maximum. `Some(true)` when
+/// the role was given, `Some(false)` when taken and `None` when it was refused.
+async fn toggle_button_role(
+    ctx: &Context,
+    mc: &MessageComponentInteraction,
+    pool: &SqlitePool,
+    member: &mut Member,
+    role_id: RoleId,
+) -> Result<Option<bool>> {
+    if member.roles.contains(&role_id) {
+        member.remove_role(&ctx, role_id).await?;
+        return Ok(Some(false));
+    }
+
+    let guild_id = mc.guild_id.ok_or(anyhow!("Role selector used outside of a guild"))?;
+    if let Some(saved) = saved_role_selector(pool, guild_id, mc.message.id).await? {
+        let picked = saved
+            .options
+            .iter()
+            .filter(|o| member.roles.contains(&o.role_id))
+            .count();
+        if picked as u64 >= saved.max_selections {
+            mc.create_followup_message(&ctx, |f| {
+                f.ephemeral(true);
+                f.content(format!(
+                    "You can pick at most {} roles here, remove one first",
+                    saved.max_selections
+                ))
+            })
+            .await?;
+            return Ok(None);
+        }
+    }
+
+    member.add_role(&ctx, role_id).await?;
+
+    Ok(Some(true))
+}
+
 #[command]
 pub async fn createroleselection(ctx: &Context, msg: &Message) -> CommandResult {
-    if let Ok(role_selector) = role_selection_message_setup(
-        ctx,
-        msg.guild_id.unwrap(),
-        msg.channel_id,
-        None,
-    )
-    .await
-    {
-        let data = ctx.data.read().await;
-        let pool = data.get::<DatabasePool>().unwrap().clone();
-
-        let role_selector_msg = role_selector.setup_message;
-        sqlx::query("insert into AutoRoleMessage (AutoRoleMessageId) values (?)")
-            .bind(*role_selector_msg.id.as_u64() as i64)
-            .execute(&pool)
-            .await
-            .unwrap();
+    if let Some(guild_id) = msg.guild_id {
+        create_role_selection(ctx, guild_id, msg.channel_id, msg.author.id).await?;
     }
 
     Ok(())
@@ -242,31 +277,27 @@ pub async fn createroleselectorslash(
     command
         .create_interaction_response(&ctx, |re| {
             re.kind(InteractionResponseType::ChannelMessageWithSource);
-            re.interaction_response_data(|data| data.content("."))
+            re.interaction_response_data(|data| {
+                data.content("Set up the role selector below");
+                data.ephemeral(true)
+            })
         })
         .await?;
 
-    command
-        .get_interaction_response(&ctx)
-        .await?
-        .delete(&ctx)
-        .await?;
+    let guild_id = command.guild_id.ok_or(anyhow!("Unable to find guild id"))?;
 
-    let guild_id = command.guild_id.unwrap();
-    let channel_id = command.channel_id;
+    create_role_selection(ctx, guild_id, command.channel_id, command.user.id).await
+}
 
-    if let Ok(role_selector) =
-        role_selection_message_setup(ctx, guild_id, channel_id, None).await
+/// Runs the role selector setup in `channel_id` for `caller` and registers the finished message
+async fn create_role_selection(ctx: &Context, guild_id: GuildId, channel_id: ChannelId, caller: UserId) -> Result<()> {
+    if let Ok(setup) =
+        role_selection_message_setup(ctx, guild_id, channel_id, caller, None).await
     {
         let data = ctx.data.read().await;
         let pool = data.get::<DatabasePool>().unwrap().clone();
 
-        let role_selector_msg = role_selector.setup_message;
-        sqlx::query("insert into AutoRoleMessage (AutoRoleMessageId) values (?)")
-            .bind(*role_selector_msg.id.as_u64() as i64)
-            .execute(&pool)
-            .await
-            .unwrap();
+        save_role_selector(&pool, guild_id, &setup.setup_message, &setup.selector).await?;
     }
 
     Ok(())
@@ -276,8 +307,9 @@ async fn role_selection_message_setup(
     ctx: &Context,
     guild_id: GuildId,
     channel_id: ChannelId,
+    caller: UserId,
     edit_message: Option<Message>,
-) -> Result<RoleSelector> {
+) -> Result<RoleSelectorSetup> {
     let guild_roles: HashMap<RoleId, Role> = guild_id.roles(&ctx).await?;
     let mut selected_roles: Vec<RoleId> = Vec::new(); //Roles available for a user to choose from
 
@@ -838,55 +870,57 @@ async fn role_selection_message_setup(
 
     }
 
-    let select_menu = |selected_roles: Vec<RoleId>| {
-        let mut sm = CreateSelectMenu::default();
-        sm.custom_id("selectmenu");
-        sm.min_values(0);
-        sm.max_values(max_selection);
-        sm.options(|ops| {
-            let options: Vec<CreateSelectMenuOption> = selected_roles
-                .iter()
-                .map(|rid| {
-                    let mut o = CreateSelectMenuOption::default();
-                    let role_name = guild_roles.get(rid).unwrap().name.as_str();
-                    // let emoji_id = roles_and_emojis.get(rid).unwrap();
-                    o.label(role_name);
-                    o.value(rid);
-                    // o.emoji(emoji_id.clone().into());
-                    if role_descriptions.len() == selected_roles.len() {
-                        o.description(role_descriptions.get(rid).unwrap());
-                    }
-                    o
-                })
-                .collect();
-            ops.set_options(options)
-        });
-        sm
+    let selector = RoleSelector {
+        content: Some(instructions_message).filter(|c| !c.is_empty()),
+        embeds,
+        options: selected_roles
+            .iter()
+            .map(|rid| RoleOption {
+                role_id: *rid,
+                label: guild_roles.get(rid).unwrap().name.clone(),
+                description: role_descriptions.get(rid).cloned(),
+            })
+            .collect(),
+        max_selections: max_selection,
+        style: RoleSelectorStyle::Menu,
     };
 
-    let mut action_rows: Vec<CreateActionRow> = Vec::new();
-    let selection_menu = CreateActionRow::default()
-        .add_select_menu(select_menu(selected_roles.clone()))
-        .clone();
+    //same checks as the API, e.g. @everyone, a bot's own role or one above the caller's can't be handed out
+    let grantor = role_grantor(ctx, guild_id, &guild_roles, Some(caller)).await?;
+    let problems = validate_role_selector(guild_id, &guild_roles, &grantor, &selector);
+    if !problems.is_empty() {
+        let description = problems
+            .iter()
+            .map(|p| format!("**{}**: {}", p.field, p.message))
+            .join("\n");
+        setup_message
+            .edit(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Unable to create the role selector");
+                    e.description(description);
+                    e.color(Color::RED)
+                });
+                m.components(|c| c.set_action_rows(vec![]))
+            })
+            .await?;
+        return Err(anyhow!("invalid role selector"));
+    }
 
-    action_rows.push(selection_menu);
+    let action_rows = role_selector_components(&selector.options, selector.max_selections, selector.style);
 
     setup_message
         .edit(&ctx, |m| {
-            m.content(&instructions_message);
-            m.set_embeds(embeds.clone());
-            m.components(|c| c.set_action_rows(action_rows.clone()))
+            m.content(selector.content.clone().unwrap_or_default());
+            m.set_embeds(selector.embeds.clone());
+            m.components(|c| c.set_action_rows(action_rows))
         })
         .await
         .unwrap();
 
-    let role_selector = RoleSelector {
+    Ok(RoleSelectorSetup {
         setup_message,
-        content: instructions_message,
-        embeds,
-        action_rows,
-    };
-    Ok(role_selector)
+        selector,
+    })
 }
 
 async fn await_message_reply(ctx: &Context, parent_message: Message) -> anyhow::Result<String> {
@@ -1,4 +1,7 @@
 use serenity::framework::standard::{macros::command, CommandResult};
+use serenity::model::application::interaction::{
+    application_command::ApplicationCommandInteraction, InteractionResponseType,
+};
 use serenity::model::prelude::*;
 use serenity::prelude::*;
 
@@ -19,4 +22,23 @@ async fn invis(ctx: &Context, _msg: &Message) -> CommandResult {
 async fn online(ctx: &Context, _msg: &Message) -> CommandResult {
     ctx.online().await;
     Ok(())
-}
\ No newline at end of file
+}
+
+pub async fn invis_slash(ctx: &Context, command: &ApplicationCommandInteraction) -> serenity::Result<()> {
+    ctx.invisible().await;
+    presence_response(ctx, command, "Status set to invisible").await
+}
+
+pub async fn online_slash(ctx: &Context, command: &ApplicationCommandInteraction) -> serenity::Result<()> {
+    ctx.online().await;
+    presence_response(ctx, command, "Status set to online").await
+}
+
+async fn presence_response(ctx: &Context, command: &ApplicationCommandInteraction, content: &str) -> serenity::Result<()> {
+    command
+        .create_interaction_response(&ctx, |response| {
+            response.kind(InteractionResponseType::ChannelMessageWithSource);
+            response.interaction_response_data(|data| data.content(content).ephemeral(true))
+        })
+        .await
+}
@@ -1,7 +1,15 @@
+pub mod announce;
+pub mod apikey;
+pub mod embed;
 pub mod math;
 pub mod meta;
 pub mod messages;
 pub mod ping;
+pub mod reminder;
 pub mod role;
-pub mod test;
+pub mod time;
+pub mod voice;
+pub mod voicehub;
+pub mod voicelog;
+pub mod webhook;
 // pub mod webblock;
\ No newline at end of file
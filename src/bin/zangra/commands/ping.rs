@@ -1,19 +1,35 @@
 use serenity::{
     framework::standard::{macros::command, CommandResult},
-    model::prelude::*,
+    model::{
+        application::interaction::{
+            application_command::ApplicationCommandInteraction, InteractionResponseType,
+        },
+        prelude::*,
+    },
     prelude::*,
 };
 
+const PONG: &str = "Pong!";
+
 //Responds to ping with "Pong!"
 #[command]
 pub async fn ping(ctx: &Context, msg: &Message) -> CommandResult {
     if let Err(why) =
     msg.channel_id.send_message(&ctx.http, |m|
         m
-            .content("Pong!")
+            .content(PONG)
             .reactions(vec![ReactionType::Unicode(String::from("✅"))])).await {
         println!("Error sending message: {why:?}");
     }
 
     Ok(())
 }
+
+pub async fn ping_slash(ctx: &Context, command: &ApplicationCommandInteraction) -> serenity::Result<()> {
+    command
+        .create_interaction_response(&ctx, |response| {
+            response.kind(InteractionResponseType::ChannelMessageWithSource);
+            response.interaction_response_data(|data| data.content(PONG).ephemeral(true))
+        })
+        .await
+}
@@ -0,0 +1,417 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Utc};
+use chrono_tz::Tz;
+use serenity::{
+    builder::{CreateActionRow, CreateEmbed},
+    client::Context,
+    http::Http,
+    model::{
+        application::{
+            component::ButtonStyle,
+            interaction::{
+                application_command::ApplicationCommandInteraction,
+                message_component::MessageComponentInteraction, InteractionResponseType,
+            },
+        },
+        channel::Message,
+        id::{ChannelId, MessageId, UserId},
+    },
+    utils::Color,
+};
+use sqlx::{query, SqlitePool};
+use tracing::error;
+
+use crate::utils::{
+    database::DatabasePool,
+    embeds::is_refused,
+    interaction::{option_i64, option_str, respond_embed},
+    time::{get_user_timezone, parse_datetime},
+};
+
+/// Buttons on a delivered reminder, (label, minutes)
+const SNOOZE_OPTIONS: [(&str, i64); 3] = [("10 minutes", 10), ("1 hour", 60), ("1 day", 60 * 24)];
+
+//delivered reminders are kept this long so their snooze buttons keep working
+const DELIVERED_RETENTION_DAYS: i64 = 7;
+//while Discord is failing a reminder is retried after 1, 2, 4... minutes, about 4 hours in all
+const MAX_DELIVERY_ATTEMPTS: i64 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReminderDelivery {
+    DirectMessage,
+    /// Reply to the confirmation in the channel `/remindme` was used in
+    Channel,
+}
+
+impl ReminderDelivery {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReminderDelivery::DirectMessage => "dm",
+            ReminderDelivery::Channel => "channel",
+        }
+    }
+}
+
+impl FromStr for ReminderDelivery {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dm" => Ok(ReminderDelivery::DirectMessage),
+            "channel" => Ok(ReminderDelivery::Channel),
+            _ => Err(anyhow!("Unknown reminder delivery: {s}")),
+        }
+    }
+}
+
+/// `/remindme set`, `/remindme list` and `/remindme cancel`
+pub async fn remindme(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let subcommand = command
+        .data
+        .options
+        .first()
+        .ok_or(anyhow!("No subcommand given for remindme"))?;
+    let user_id_i64 = *command.user.id.as_u64() as i64;
+
+    let data = ctx.data.read().await;
+    let pool = data.get::<DatabasePool>().unwrap().clone();
+
+    match subcommand.name.as_str() {
+        "set" => {
+            let options = &subcommand.options;
+            let when = option_str(options, "when").unwrap_or_default();
+            let content = option_str(options, "message").unwrap_or_default().to_string();
+            let delivery = option_str(options, "delivery")
+                .and_then(|d| d.parse().ok())
+                .unwrap_or(ReminderDelivery::DirectMessage);
+
+            let timezone = get_user_timezone(&pool, command.user.id).await?.unwrap_or(Tz::UTC);
+            let due = match parse_datetime(when, Utc::now(), timezone) {
+                Ok(due) if due <= Utc::now() => {
+                    return respond_error(ctx, command, "That time has already passed".to_string()).await;
+                }
+                Ok(due) => due,
+                Err(why) => return respond_error(ctx, command, why.to_string()).await,
+            };
+
+            let guild_id = command.guild_id.map(|g| *g.as_u64() as i64);
+            let channel_id = *command.channel_id.as_u64() as i64;
+            let delivery_text = delivery.as_str();
+            let due_at = due.timestamp();
+            let created_at = Utc::now().timestamp();
+
+            let reminder_id = query!(
+                "INSERT INTO Reminder (UserId, GuildId, ChannelId, Content, Delivery, DueAt, CreatedAt)
+                VALUES (?, ?, ?, ?, ?, ?, ?)",
+                user_id_i64,
+                guild_id,
+                channel_id,
+                content,
+                delivery_text,
+                due_at,
+                created_at
+            )
+            .execute(&pool)
+            .await?
+            .last_insert_rowid();
+
+            let mut embed = CreateEmbed::default();
+            embed.title(format!("Reminder #{reminder_id} set"));
+            embed.description(format!("<t:{due_at}:F> (<t:{due_at}:R>)\n{content}"));
+            embed.color(Color::DARK_GREEN);
+
+            match delivery {
+                ReminderDelivery::DirectMessage => {
+                    embed.footer(|f| f.text("You'll get it in your DMs"));
+                    respond_embed(ctx, command, embed, true).await?;
+                }
+                ReminderDelivery::Channel => {
+                    //public so the reminder can reply to it later
+                    respond_embed(ctx, command, embed, false).await?;
+
+                    let confirmation = command.get_interaction_response(&ctx).await?;
+                    let message_id = *confirmation.id.as_u64() as i64;
+                    query!(
+                        "UPDATE Reminder SET MessageId = ? WHERE ReminderId = ?",
+                        message_id,
+                        reminder_id
+                    )
+                    .execute(&pool)
+                    .await?;
+                }
+            }
+        }
+        "list" => {
+            let rows = query!(
+                "SELECT ReminderId, Content, DueAt FROM Reminder
+                WHERE UserId = ? AND Delivered = 0
+                ORDER BY DueAt",
+                user_id_i64
+            )
+            .fetch_all(&pool)
+            .await?;
+
+            let description = rows
+                .iter()
+                .take(20)
+                .map(|row| format!("**#{}** <t:{}:R> {}", row.ReminderId, row.DueAt, truncate(&row.Content, 80)))
+                .reduce(|a, b| a + "\n" + &b)
+                .unwrap_or("You have no pending reminders".to_string());
+
+            let mut embed = CreateEmbed::default();
+            embed.title("Your reminders");
+            embed.description(description);
+
+            respond_embed(ctx, command, embed, true).await?;
+        }
+        "cancel" => {
+            let id = option_i64(&subcommand.options, "id").ok_or(anyhow!("No id given for remindme cancel"))?;
+
+            let result = query!(
+                "DELETE FROM Reminder WHERE ReminderId = ? AND UserId = ? AND Delivered = 0",
+                id,
+                user_id_i64
+            )
+            .execute(&pool)
+            .await?;
+
+            let mut embed = CreateEmbed::default();
+            if result.rows_affected() > 0 {
+                embed.title(format!("Reminder #{id} cancelled"));
+                embed.color(Color::DARK_GREEN);
+            } else {
+                embed.title(format!("You have no pending reminder #{id}"));
+                embed.color(Color::RED);
+            }
+
+            respond_embed(ctx, command, embed, true).await?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+async fn respond_error(ctx: &Context, command: &ApplicationCommandInteraction, message: String) -> Result<()> {
+    let mut embed = CreateEmbed::default();
+    embed.title("Unable to set reminder");
+    embed.description(message);
+    embed.color(Color::RED);
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((i, _)) => format!("{}…", &text[..i]),
+        None => text.to_string(),
+    }
+}
+
+/// Snooze button on a delivered reminder, custom id `reminder_snooze <reminder id> <minutes>`
+pub async fn snooze_reminder(ctx: &Context, mc: &MessageComponentInteraction) -> Result<()> {
+    let mut parts = mc.data.custom_id.split(' ').skip(1);
+    let reminder_id: i64 = parts.next().ok_or(anyhow!("No reminder id in snooze"))?.parse()?;
+    let minutes: i64 = parts.next().ok_or(anyhow!("No minutes in snooze"))?.parse()?;
+
+    let data = ctx.data.read().await;
+    let pool = data.get::<DatabasePool>().unwrap().clone();
+
+    let user_id_i64 = *mc.user.id.as_u64() as i64;
+    let due_at = (Utc::now() + Duration::minutes(minutes)).timestamp();
+
+    let result = query!(
+        "UPDATE Reminder SET DueAt = ?, Delivered = 0, FailedAttempts = 0
+        WHERE ReminderId = ? AND UserId = ? AND Delivered = 1",
+        due_at,
+        reminder_id,
+        user_id_i64
+    )
+    .execute(&pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        mc.create_interaction_response(&ctx, |response| {
+            response.kind(InteractionResponseType::ChannelMessageWithSource);
+            response.interaction_response_data(|data| {
+                data.content("This reminder can't be snoozed anymore");
+                data.ephemeral(true)
+            })
+        })
+        .await?;
+
+        return Ok(());
+    }
+
+    let mut embeds: Vec<CreateEmbed> = mc.message.embeds.iter().cloned().map(CreateEmbed::from).collect();
+    if let Some(embed) = embeds.first_mut() {
+        embed.footer(|f| f.text("Snoozed"));
+        embed.field("Snoozed until", format!("<t:{due_at}:F> (<t:{due_at}:R>)"), false);
+    }
+
+    mc.create_interaction_response(&ctx, |response| {
+        response.kind(InteractionResponseType::UpdateMessage);
+        response.interaction_response_data(|data| {
+            data.set_embeds(embeds);
+            data.components(|c| c.set_action_rows(vec![]))
+        })
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Sends every reminder that is due, late ones included after a restart. Failures that may pass,
+/// such as an outage, put the reminder off and try again.
+pub async fn deliver_due_reminders(http: &Http, pool: &SqlitePool) -> Result<()> {
+    let now = Utc::now().timestamp();
+
+    let due = query!(
+        "SELECT ReminderId, UserId, ChannelId, MessageId, Content, Delivery, CreatedAt, FailedAttempts FROM Reminder
+        WHERE Delivered = 0 AND DueAt <= ?",
+        now
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for row in due {
+        let delivery: ReminderDelivery = row.Delivery.parse().unwrap_or(ReminderDelivery::DirectMessage);
+
+        let delivered = deliver_reminder(
+            http,
+            row.ReminderId,
+            UserId(row.UserId as u64),
+            ChannelId(row.ChannelId as u64),
+            row.MessageId.map(|m| MessageId(m as u64)),
+            &row.Content,
+            delivery,
+            row.CreatedAt,
+        )
+        .await;
+
+        if let Err(why) = delivered {
+            let attempts = row.FailedAttempts + 1;
+            error!("Unable to deliver reminder #{} (attempt {}): {}", row.ReminderId, attempts, why);
+
+            //closed DMs and a channel the bot can't post in won't get better
+            if !why.downcast_ref().is_some_and(is_refused) && attempts < MAX_DELIVERY_ATTEMPTS {
+                let retry_at = now + 60 * (1 << (attempts - 1));
+                let put_off = query!(
+                    "UPDATE Reminder SET DueAt = ?, FailedAttempts = ? WHERE ReminderId = ?",
+                    retry_at,
+                    attempts,
+                    row.ReminderId
+                )
+                .execute(pool)
+                .await;
+                if let Err(why) = put_off {
+                    error!("Unable to put off reminder #{}: {}", row.ReminderId, why);
+                }
+                continue;
+            }
+        }
+
+        let marked = query!("UPDATE Reminder SET Delivered = 1 WHERE ReminderId = ?", row.ReminderId)
+            .execute(pool)
+            .await;
+        if let Err(why) = marked {
+            error!("Unable to mark reminder #{} delivered: {}", row.ReminderId, why);
+        }
+    }
+
+    let expired = now - Duration::days(DELIVERED_RETENTION_DAYS).num_seconds();
+    query!("DELETE FROM Reminder WHERE Delivered = 1 AND DueAt < ?", expired)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn deliver_reminder(
+    http: &Http,
+    reminder_id: i64,
+    user_id: UserId,
+    channel_id: ChannelId,
+    reply_to: Option<MessageId>,
+    content: &str,
+    delivery: ReminderDelivery,
+    created_at: i64,
+) -> Result<Message> {
+    let mut embed = CreateEmbed::default();
+    embed.title("Reminder");
+    embed.description(content);
+    embed.field("Set", format!("<t:{created_at}:R>"), true);
+    embed.color(Color::GOLD);
+
+    let mut snooze_row = CreateActionRow::default();
+    for (label, minutes) in SNOOZE_OPTIONS {
+        snooze_row.create_button(|b| {
+            b.custom_id(format!("reminder_snooze {reminder_id} {minutes}"));
+            b.label(format!("Snooze {label}"));
+            b.style(ButtonStyle::Secondary)
+        });
+    }
+
+    if delivery == ReminderDelivery::DirectMessage {
+        let dm = user_id.create_dm_channel(http).await;
+        let sent = match dm {
+            Ok(dm) => {
+                dm.send_message(http, |m| {
+                    m.set_embed(embed.clone());
+                    m.components(|c| c.add_action_row(snooze_row.clone()))
+                })
+                .await
+            }
+            Err(why) => Err(why),
+        };
+        match sent {
+            Ok(message) => return Ok(message),
+            Err(why) if is_refused(&why) => error!("Unable to DM reminder #{}: {}", reminder_id, why),
+            //Discord failing rather than the member refusing, the DM is tried again later
+            Err(why) => return Err(why.into()),
+        }
+
+        //DMs closed, the reminder was private so the channel it was set in only gets a ping
+        let ping = channel_id
+            .send_message(http, |m| {
+                m.content(format!(
+                    "<@{user_id}> I couldn't DM you a reminder, allow direct messages from this server to get them"
+                ))
+            })
+            .await?;
+        return Ok(ping);
+    }
+
+    let sent = send_to_channel(http, channel_id, user_id, reply_to, &embed, &snooze_row).await;
+    match sent {
+        Ok(message) => Ok(message),
+        //the confirmation was deleted, post without replying
+        Err(_) if reply_to.is_some() => Ok(send_to_channel(http, channel_id, user_id, None, &embed, &snooze_row).await?),
+        Err(why) => Err(why.into()),
+    }
+}
+
+async fn send_to_channel(
+    http: &Http,
+    channel_id: ChannelId,
+    user_id: UserId,
+    reply_to: Option<MessageId>,
+    embed: &CreateEmbed,
+    snooze_row: &CreateActionRow,
+) -> serenity::Result<Message> {
+    channel_id
+        .send_message(http, |m| {
+            m.content(format!("<@{user_id}>"));
+            if let Some(reply_to) = reply_to {
+                m.reference_message((channel_id, reply_to));
+            }
+            m.set_embed(embed.clone());
+            m.components(|c| c.add_action_row(snooze_row.clone()))
+        })
+        .await
+}
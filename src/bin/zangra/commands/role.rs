@@ -285,8 +285,8 @@ pub async fn mutex(ctx: &Context, command: &ApplicationCommandInteraction) -> Re
                             .to_role_cached(ctx)
                             .ok_or(anyhow!("unable to get information for role2 in mutex list"));
 
-                        if role1.is_ok() && role2.is_ok() {
-                            format!("{} - {}", role1.unwrap().name, role2.unwrap().name)
+                        if let (Ok(role1), Ok(role2)) = (role1, role2) {
+                            format!("{} - {}", role1.name, role2.name)
                         } else {
                             "".to_string()
                         }
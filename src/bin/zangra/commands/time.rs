@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use chrono_tz::Tz;
+use serenity::builder::CreateEmbed;
+use serenity::client::Context;
+use serenity::framework::standard::{macros::command, Args, CommandResult};
+use serenity::model::application::interaction::application_command::ApplicationCommandInteraction;
+use serenity::model::channel::Message;
+use serenity::model::id::UserId;
+use serenity::utils::Color;
+
+use crate::utils::database::DatabasePool;
+use crate::utils::interaction::{option_str, respond_embed};
+use crate::utils::time::{
+    discord_timestamp, get_user_timezone, parse_datetime, parse_timezone, set_user_timezone,
+    TIMESTAMP_STYLES,
+};
+
+//Shows the current time, or the time given after the command, in every Discord timestamp format
+#[command]
+pub async fn timestamp(ctx: &Context, message: &Message, args: Args) -> CommandResult {
+    let embed = time_embed(ctx, message.author.id, args.rest(), None)
+        .await
+        .unwrap_or_else(|why| error_embed(why.to_string()));
+
+    if let Err(why) = message.channel_id.send_message(ctx, |m| m
+        .set_embed(embed)).await {
+        println!("Error sending timestamp: {why}");
+    }
+
+    Ok(())
+}
+
+pub async fn timestamp_slash(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let embed = time_embed(ctx, command.user.id, "", None)
+        .await
+        .unwrap_or_else(|why| error_embed(why.to_string()));
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
+
+/// `/time show [when] [timezone]` and `/time timezone [timezone]`
+pub async fn time(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let subcommand = command
+        .data
+        .options
+        .first()
+        .ok_or(anyhow!("No subcommand given for time"))?;
+
+    match subcommand.name.as_str() {
+        "show" => {
+            let when = option_str(&subcommand.options, "when").unwrap_or_default();
+            let timezone = option_str(&subcommand.options, "timezone");
+
+            match time_embed(ctx, command.user.id, when, timezone).await {
+                Ok(embed) => respond_embed(ctx, command, embed, false).await?,
+                Err(why) => respond_embed(ctx, command, error_embed(why.to_string()), true).await?,
+            }
+        }
+        "timezone" => {
+            let data = ctx.data.read().await;
+            let pool = data.get::<DatabasePool>().unwrap().clone();
+
+            let embed = match option_str(&subcommand.options, "timezone") {
+                Some(name) => match parse_timezone(name) {
+                    Some(tz) => {
+                        set_user_timezone(&pool, command.user.id, tz).await?;
+
+                        let mut embed = CreateEmbed::default();
+                        embed.title(format!("Your timezone is now {}", tz.name()));
+                        embed.color(Color::DARK_GREEN);
+                        embed
+                    }
+                    None => error_embed(unknown_timezone(name)),
+                },
+                None => {
+                    let mut embed = CreateEmbed::default();
+                    match get_user_timezone(&pool, command.user.id).await? {
+                        Some(tz) => embed.title(format!("Your timezone is {}", tz.name())),
+                        None => embed.title("You haven't set a timezone, UTC is used"),
+                    };
+                    embed
+                }
+            };
+
+            respond_embed(ctx, command, embed, true).await?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Resolves `when` in the given timezone, the member's saved one, or UTC, in that order
+async fn time_embed(ctx: &Context, user_id: UserId, when: &str, timezone: Option<&str>) -> Result<CreateEmbed> {
+    let timezone = match timezone {
+        Some(name) => parse_timezone(name).ok_or_else(|| anyhow!(unknown_timezone(name)))?,
+        None => {
+            let data = ctx.data.read().await;
+            let pool = data.get::<DatabasePool>().unwrap().clone();
+
+            get_user_timezone(&pool, user_id).await?.unwrap_or(Tz::UTC)
+        }
+    };
+
+    let time = parse_datetime(when, Utc::now(), timezone)?;
+
+    let mut embed = CreateEmbed::default();
+    embed.title(time.format("%A, %B %-d %Y %H:%M %Z").to_string());
+    embed.description(format!("{}\nShown below in your own timezone", time.timezone().name()));
+    embed.color(Color::BLUE);
+    for (style, name) in TIMESTAMP_STYLES {
+        let markup = discord_timestamp(&time, style);
+        embed.field(name, format!("{markup}\n`{markup}`"), true);
+    }
+
+    Ok(embed)
+}
+
+fn unknown_timezone(name: &str) -> String {
+    format!("`{name}` isn't a timezone, use a name like America/New_York or Europe/Berlin")
+}
+
+fn error_embed(message: String) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed.title("Unable to read that time");
+    embed.description(message);
+    embed.color(Color::RED);
+    embed
+}
+
@@ -0,0 +1,88 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue},
+    utils::Color,
+};
+
+use crate::utils::{
+    database::DatabasePool,
+    interaction::{option_resolved, option_str, respond_embed},
+    voice_sessions::{format_voice_time, member_voice_time, voice_leaderboard, VoicePeriod},
+};
+
+//how many members /voice leaderboard shows
+const LEADERBOARD_LENGTH: i64 = 10;
+//how many channels /voice stats breaks the time down into
+const TOP_CHANNELS: usize = 5;
+
+/// `/voice stats` for a member and `/voice leaderboard` for the guild
+pub async fn voice(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let subcommand = command
+        .data
+        .options
+        .first()
+        .ok_or(anyhow!("No subcommand given for voice"))?;
+    let guild_id = command.guild_id.ok_or(anyhow!("voice used outside of a guild"))?;
+
+    let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+
+    let period = VoicePeriod::from_name(option_str(&subcommand.options, "period"));
+    let now = Utc::now().timestamp();
+    let since = period.since(now);
+
+    let mut embed = CreateEmbed::default();
+    embed.color(Color::BLURPLE);
+
+    match subcommand.name.as_str() {
+        "stats" => {
+            let user = match option_resolved(&subcommand.options, "member") {
+                Some(CommandDataOptionValue::User(user, _)) => user,
+                _ => &command.user,
+            };
+
+            let channels = member_voice_time(&pool, guild_id, user.id, since, now).await?;
+            let seconds: i64 = channels.iter().map(|(_, time)| time.seconds).sum();
+            let sessions: i64 = channels.iter().map(|(_, time)| time.sessions).sum();
+
+            embed.author(|a| a.name(&user.name).icon_url(user.face()));
+            embed.title(format!("Time in voice, {}", period.description()));
+            if channels.is_empty() {
+                embed.description(format!("<@{}> hasn't been in voice", user.id));
+            } else {
+                embed.field("Total", format_voice_time(seconds), true);
+                embed.field("Sessions", sessions, true);
+                embed.field(
+                    "Channels",
+                    channels
+                        .iter()
+                        .take(TOP_CHANNELS)
+                        .map(|(channel_id, time)| format!("<#{channel_id}> {}", format_voice_time(time.seconds)))
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    false,
+                );
+            }
+        }
+        "leaderboard" => {
+            let leaderboard = voice_leaderboard(&pool, guild_id, since, now, LEADERBOARD_LENGTH).await?;
+
+            let description = leaderboard
+                .iter()
+                .enumerate()
+                .map(|(place, (user_id, time))| format!("**{}.** <@{user_id}> {}", place + 1, format_voice_time(time.seconds)))
+                .reduce(|a, b| a + "\n" + &b)
+                .unwrap_or("Nobody has been in voice".to_string());
+
+            embed.title(format!("Most time in voice, {}", period.description()));
+            embed.description(description);
+        }
+        _ => return Ok(()),
+    }
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
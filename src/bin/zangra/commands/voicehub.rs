@@ -0,0 +1,84 @@
+use anyhow::{anyhow, Result};
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue},
+    utils::Color,
+};
+
+use crate::utils::{
+    database::DatabasePool,
+    interaction::{option_i64, option_resolved, option_str, respond_embed},
+    voice_hubs::{remove_voice_hub_settings, save_voice_hub_settings, voice_hub_settings, VoiceHubSettings, DEFAULT_NAME_TEMPLATE},
+};
+
+/// `/voicehub set`, `/voicehub show` and `/voicehub disable`
+pub async fn voicehub(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let subcommand = command
+        .data
+        .options
+        .first()
+        .ok_or(anyhow!("No subcommand given for voicehub"))?;
+    let guild_id = command.guild_id.ok_or(anyhow!("voicehub used outside of a guild"))?;
+
+    let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+
+    let mut embed = CreateEmbed::default();
+
+    match subcommand.name.as_str() {
+        "set" => {
+            let channel_id = match option_resolved(&subcommand.options, "channel") {
+                Some(CommandDataOptionValue::Channel(channel)) => channel.id,
+                _ => return Err(anyhow!("No channel given for voicehub set")),
+            };
+            let name_template = option_str(&subcommand.options, "name").unwrap_or(DEFAULT_NAME_TEMPLATE).to_string();
+            let user_limit = option_i64(&subcommand.options, "limit").map(|limit| limit as u32);
+
+            let settings = VoiceHubSettings {
+                channel_id,
+                name_template,
+                user_limit,
+            };
+            save_voice_hub_settings(&pool, guild_id, &settings).await?;
+
+            embed.title("Voice hub updated");
+            embed.description("The bot needs Manage Channels, Manage Permissions and Move Members to open channels");
+            describe(&mut embed, &settings);
+            embed.color(Color::DARK_GREEN);
+        }
+        "show" => match voice_hub_settings(&pool, guild_id).await? {
+            Some(settings) => {
+                embed.title("Voice hub");
+                describe(&mut embed, &settings);
+            }
+            None => {
+                embed.title("This server has no voice hub");
+            }
+        },
+        "disable" => {
+            if remove_voice_hub_settings(&pool, guild_id).await? {
+                embed.title("Voice hub disabled");
+                embed.description("Channels it opened are still deleted once they're empty");
+                embed.color(Color::DARK_GREEN);
+            } else {
+                embed.title("This server has no voice hub");
+                embed.color(Color::RED);
+            }
+        }
+        _ => return Ok(()),
+    }
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
+
+fn describe(embed: &mut CreateEmbed, settings: &VoiceHubSettings) {
+    embed.field("Hub", format!("<#{}>", settings.channel_id), false);
+    embed.field("Channel name", format!("`{}`", settings.name_template), true);
+    embed.field(
+        "User limit",
+        settings.user_limit.map_or("None".to_string(), |limit| limit.to_string()),
+        true,
+    );
+}
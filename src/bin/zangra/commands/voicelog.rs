@@ -0,0 +1,77 @@
+use anyhow::{anyhow, Result};
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    model::application::interaction::application_command::{ApplicationCommandInteraction, CommandDataOptionValue},
+    utils::Color,
+};
+
+use crate::utils::{
+    database::DatabasePool,
+    interaction::{option_resolved, option_str, respond_embed},
+    voice_log::{event_names, parse_voice_log_events, remove_voice_log_settings, save_voice_log_settings, voice_log_settings, VoiceLogSettings},
+};
+
+/// `/voicelog set`, `/voicelog show` and `/voicelog disable`
+pub async fn voicelog(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let subcommand = command
+        .data
+        .options
+        .first()
+        .ok_or(anyhow!("No subcommand given for voicelog"))?;
+    let guild_id = command.guild_id.ok_or(anyhow!("voicelog used outside of a guild"))?;
+
+    let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+
+    let mut embed = CreateEmbed::default();
+
+    match subcommand.name.as_str() {
+        "set" => {
+            let channel_id = match option_resolved(&subcommand.options, "channel") {
+                Some(CommandDataOptionValue::Channel(channel)) => channel.id,
+                _ => return Err(anyhow!("No channel given for voicelog set")),
+            };
+            let events = match parse_voice_log_events(option_str(&subcommand.options, "events").unwrap_or_default()) {
+                Ok(events) => events,
+                Err(why) => {
+                    embed.title(why);
+                    embed.color(Color::RED);
+                    respond_embed(ctx, command, embed, true).await?;
+                    return Ok(());
+                }
+            };
+
+            let settings = VoiceLogSettings { channel_id, events };
+            save_voice_log_settings(&pool, guild_id, &settings).await?;
+
+            embed.title("Voice log updated");
+            embed.field("Channel", format!("<#{channel_id}>"), false);
+            embed.field("Events", event_names(&settings.events), false);
+            embed.color(Color::DARK_GREEN);
+        }
+        "show" => match voice_log_settings(&pool, guild_id).await? {
+            Some(settings) => {
+                embed.title("Voice log");
+                embed.field("Channel", format!("<#{}>", settings.channel_id), false);
+                embed.field("Events", event_names(&settings.events), false);
+            }
+            None => {
+                embed.title("This server has no voice log");
+            }
+        },
+        "disable" => {
+            if remove_voice_log_settings(&pool, guild_id).await? {
+                embed.title("Voice log disabled");
+                embed.color(Color::DARK_GREEN);
+            } else {
+                embed.title("This server has no voice log");
+                embed.color(Color::RED);
+            }
+        }
+        _ => return Ok(()),
+    }
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
@@ -15,6 +15,8 @@ use sqlx::{Error, Row};
 use url::Url;
 
 use crate::DatabasePool;
+use crate::utils::guild_events::{publish_guild_event, GuildEventKind};
+use crate::utils::metrics::client_metrics;
 
 pub async fn webblock<>(ctx: &Context, aci: &ApplicationCommandInteraction) -> Result<()> {
     for option in &aci.data.options {
@@ -165,7 +167,16 @@ pub async fn webblock_check_message(ctx: &Context, message: &Message) -> Result<
 
                     if delete_messages {
                         message.delete(&ctx).await?;
+                        client_metrics(ctx).await.webblock_deletion();
                     }
+
+                    publish_guild_event(ctx, guild_id, GuildEventKind::LinkBlocked {
+                        channel_id: message.channel_id,
+                        message_id: message.id,
+                        user_id: message.author.id,
+                        deleted: delete_messages,
+                    })
+                    .await;
                 }
             }
         }
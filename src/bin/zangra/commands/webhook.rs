@@ -0,0 +1,208 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    model::application::interaction::{application_command::ApplicationCommandInteraction, InteractionResponseType},
+    utils::Color,
+};
+use sqlx::query;
+
+use crate::{
+    rest_api::auth::random_token,
+    utils::{
+        database::DatabasePool,
+        interaction::{option_i64, option_str, respond_embed},
+        webhooks::{
+            deliver_test, guild_webhooks, parse_webhook_events, validate_webhook_url, webhook_client, DeliveryOutcome,
+        },
+    },
+};
+
+//how many deliveries /webhook log shows
+const LOG_LENGTH: i64 = 10;
+
+/// `/webhook add`, `list`, `remove`, `test` and `log`, webhooks only get their own guild's events
+pub async fn webhook(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    let subcommand = command
+        .data
+        .options
+        .first()
+        .ok_or(anyhow!("No subcommand given for webhook"))?;
+    let guild_id = command.guild_id.ok_or(anyhow!("webhook used outside of a guild"))?;
+    let guild_id_i64 = *guild_id.as_u64() as i64;
+
+    let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+
+    let mut embed = CreateEmbed::default();
+
+    match subcommand.name.as_str() {
+        "add" => {
+            let url = option_str(&subcommand.options, "url").ok_or(anyhow!("No url given for webhook add"))?;
+            let url = match validate_webhook_url(url) {
+                Ok(url) => url.to_string(),
+                Err(why) => return refuse(ctx, command, why).await,
+            };
+            let events = match parse_webhook_events(option_str(&subcommand.options, "events").unwrap_or_default()) {
+                Ok(events) => events.join(","),
+                Err(why) => return refuse(ctx, command, why).await,
+            };
+            let secret = match option_str(&subcommand.options, "secret") {
+                Some(secret) => secret.to_string(),
+                None => random_token(),
+            };
+            let created_by = *command.user.id.as_u64() as i64;
+            let created_at = Utc::now().timestamp();
+
+            let webhook_id = query!(
+                "INSERT INTO OutgoingWebhook (GuildId, Url, Secret, Events, CreatedBy, CreatedAt) VALUES (?, ?, ?, ?, ?, ?)",
+                guild_id_i64,
+                url,
+                secret,
+                events,
+                created_by,
+                created_at
+            )
+            .execute(&pool)
+            .await?
+            .last_insert_rowid();
+
+            embed.title(format!("Webhook #{webhook_id} added"));
+            embed.description(format!(
+                "Secret: ||`{secret}`||\nRequests are signed with `X-Zangra-Signature: sha256=<HMAC-SHA256 of \"<X-Zangra-Timestamp>.<body>\">`. The secret won't be shown again."
+            ));
+            embed.field("URL", url, false);
+            embed.field("Events", events.replace(',', ", "), false);
+            embed.color(Color::DARK_GREEN);
+        }
+        "list" => {
+            let webhooks = guild_webhooks(&pool, guild_id).await?;
+
+            let description = webhooks
+                .iter()
+                .map(|w| format!("**#{}** {} ({})", w.id, w.url, w.events.join(", ")))
+                .reduce(|a, b| a + "\n" + &b)
+                .unwrap_or("This server has no webhooks".to_string());
+
+            embed.title("Webhooks");
+            embed.description(description);
+        }
+        "remove" => {
+            let id = option_i64(&subcommand.options, "id").ok_or(anyhow!("No id given for webhook remove"))?;
+
+            let result = query!(
+                "DELETE FROM OutgoingWebhook WHERE OutgoingWebhookId = ? AND GuildId = ?",
+                id,
+                guild_id_i64
+            )
+            .execute(&pool)
+            .await?;
+
+            if result.rows_affected() > 0 {
+                embed.title(format!("Webhook #{id} removed"));
+                embed.color(Color::DARK_GREEN);
+            } else {
+                embed.title(format!("There is no webhook #{id} in this server"));
+                embed.color(Color::RED);
+            }
+        }
+        "test" => {
+            let id = option_i64(&subcommand.options, "id").ok_or(anyhow!("No id given for webhook test"))?;
+            let webhook = match guild_webhooks(&pool, guild_id).await?.into_iter().find(|w| w.id == id) {
+                Some(webhook) => webhook,
+                None => return refuse(ctx, command, format!("There is no webhook #{id} in this server")).await,
+            };
+
+            //the receiver gets up to 10 seconds, more than Discord waits for a response
+            command
+                .create_interaction_response(&ctx, |response| {
+                    response.kind(InteractionResponseType::DeferredChannelMessageWithSource);
+                    response.interaction_response_data(|data| data.ephemeral(true))
+                })
+                .await?;
+
+            match deliver_test(&webhook_client(), &pool, &webhook).await? {
+                DeliveryOutcome::Delivered(status) => {
+                    embed.title(format!("Webhook #{id} answered {status}"));
+                    embed.color(Color::DARK_GREEN);
+                }
+                DeliveryOutcome::Retry { status: None, error } => {
+                    embed.title(format!("Webhook #{id} didn't answer"));
+                    embed.description(error);
+                    embed.color(Color::RED);
+                }
+                DeliveryOutcome::Refused { status: None, error } => {
+                    embed.title(format!("Webhook #{id} wasn't sent"));
+                    embed.description(error);
+                    embed.color(Color::RED);
+                }
+                DeliveryOutcome::Retry { status: Some(status), error } | DeliveryOutcome::Refused { status: Some(status), error } => {
+                    embed.title(format!("Webhook #{id} answered {status}"));
+                    embed.description(error);
+                    embed.color(Color::RED);
+                }
+            }
+
+            command
+                .edit_original_interaction_response(&ctx, |response| response.set_embed(embed))
+                .await?;
+
+            return Ok(());
+        }
+        "log" => {
+            let id = option_i64(&subcommand.options, "id").ok_or(anyhow!("No id given for webhook log"))?;
+
+            let rows = query!(
+                "SELECT d.DeliveryKey, d.Event, d.Attempt, d.StatusCode, d.Succeeded, d.AttemptedAt
+                FROM WebhookDelivery d
+                JOIN OutgoingWebhook w ON w.OutgoingWebhookId = d.OutgoingWebhookId
+                WHERE w.OutgoingWebhookId = ? AND w.GuildId = ?
+                ORDER BY d.WebhookDeliveryId DESC
+                LIMIT ?",
+                id,
+                guild_id_i64,
+                LOG_LENGTH
+            )
+            .fetch_all(&pool)
+            .await?;
+
+            let description = rows
+                .iter()
+                .map(|row| {
+                    let status = match row.StatusCode {
+                        Some(status) => status.to_string(),
+                        None => "no answer".to_string(),
+                    };
+                    let mark = if row.Succeeded != 0 { "✅" } else { "❌" };
+                    format!(
+                        "{mark} `{}` {} attempt {}, {} <t:{}:R>",
+                        &row.DeliveryKey[..8],
+                        row.Event,
+                        row.Attempt,
+                        status,
+                        row.AttemptedAt
+                    )
+                })
+                .reduce(|a, b| a + "\n" + &b)
+                .unwrap_or(format!("Webhook #{id} has no deliveries in this server"));
+
+            embed.title(format!("Webhook #{id} deliveries"));
+            embed.description(description);
+        }
+        _ => return Ok(()),
+    }
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
+
+async fn refuse(ctx: &Context, command: &ApplicationCommandInteraction, why: String) -> Result<()> {
+    let mut embed = CreateEmbed::default();
+    embed.title(why);
+    embed.color(Color::RED);
+
+    respond_embed(ctx, command, embed, true).await?;
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use std::{fs::File, io::Read};
+
+use serde::{
+    Deserialize, // To deserialize data into structures
+    Serialize,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConfigurationData {
+    pub discord_token: String,
+    pub application_id: String,
+    /// Handle `~` prefix commands. Turning this off leaves only the slash commands.
+    #[serde(default = "default_true")]
+    pub prefix_commands: bool,
+    /// REST API keys kept in the config file rather than created with `/apikey`
+    #[serde(default)]
+    pub api_keys: Vec<ConfiguredApiKey>,
+    /// Discord login for the dashboard, the login routes are off without it
+    pub oauth: Option<OAuthConfig>,
+    #[serde(default)]
+    pub api: ApiSettings,
+}
+
+/// `[[api_keys]]` entry, only the SHA-256 hex digest of the key is stored
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConfiguredApiKey {
+    pub name: String,
+    pub key_hash: String,
+    /// Guilds the key may manage, every guild when left out
+    pub guilds: Option<Vec<u64>>,
+}
+
+/// `[oauth]` section. The endpoint URLs default to Discord's and only need setting to test
+/// against a mock server.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OAuthConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    /// Must match a redirect registered for the application, e.g. `https://example.com/api/auth/callback`
+    pub redirect_uri: String,
+    /// Where the browser is sent once logged in
+    #[serde(default = "default_dashboard_url")]
+    pub dashboard_url: String,
+    #[serde(default = "default_authorize_url")]
+    pub authorize_url: String,
+    #[serde(default = "default_token_url")]
+    pub token_url: String,
+    #[serde(default = "default_api_base_url")]
+    pub api_base_url: String,
+}
+
+/// `[api]` section, anything left out keeps its default
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ApiSettings {
+    /// Origins allowed to call the API from a browser, e.g. `https://dashboard.example.com`. Only
+    /// same origin requests work while it's empty.
+    pub cors_origins: Vec<String>,
+    /// Requests a client can make in a row before being limited
+    pub rate_limit_burst: u32,
+    /// How fast a limited client gets requests back
+    pub rate_limit_per_minute: u32,
+    /// Requests one address can make in a row, whoever it's authenticated as or whether it's
+    /// authenticated at all. Everyone behind a reverse proxy shares the proxy's address.
+    pub address_rate_limit_burst: u32,
+    pub address_rate_limit_per_minute: u32,
+    /// Largest accepted request body, the biggest embed message Discord allows fits well within the default
+    pub max_body_bytes: usize,
+    pub request_timeout_secs: u64,
+}
+
+impl Default for ApiSettings {
+    fn default() -> Self {
+        ApiSettings {
+            cors_origins: Vec::new(),
+            rate_limit_burst: 30,
+            rate_limit_per_minute: 120,
+            address_rate_limit_burst: 60,
+            address_rate_limit_per_minute: 300,
+            max_body_bytes: 64 * 1024,
+            request_timeout_secs: 30,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_dashboard_url() -> String {
+    "/".to_string()
+}
+
+fn default_authorize_url() -> String {
+    "https://discord.com/oauth2/authorize".to_string()
+}
+
+fn default_token_url() -> String {
+    "https://discord.com/api/oauth2/token".to_string()
+}
+
+fn default_api_base_url() -> String {
+    "https://discord.com/api/v10".to_string()
+}
+
+pub fn read_configuration() -> Option<ConfigurationData> {
+    match File::open("config.toml") {
+        Ok(mut file) => {
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).unwrap();
+            let configuration = toml::from_str::<ConfigurationData>(&contents).unwrap();
+
+            Some(configuration)
+        }
+
+        Err(why) => {
+            println!("Unable to open config file. Why: {why}");
+            None
+        }
+    }
+}
@@ -7,6 +7,7 @@ use serde_json::Error as JsonError;
 use sqlx::Error as SqlxError;
 
 
+#[allow(dead_code)]
 pub struct ZangraError {
     message: String,
 }
@@ -38,6 +39,7 @@ impl StdError for ZangraError {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 pub enum Error {
     Json(JsonError),
@@ -1,42 +1,52 @@
 use serenity::{
     async_trait,
+    builder::CreateApplicationCommandOption,
     client::{Client, Context, EventHandler},
     framework::{standard::macros::group, StandardFramework},
     model::{
         application::{
             interaction::{Interaction},
             command::{Command, CommandOptionType, CommandType},},
-        channel::{ChannelType, Message, Reaction},
+        channel::{ChannelType, GuildChannel, Message, Reaction},
+        event::MessageUpdateEvent,
         gateway::{GatewayIntents, Ready},
         guild::{Member},
-        id::{ChannelId},
+        id::{ChannelId, GuildId, MessageId, UserId},
         permissions::Permissions,
+        user::User,
         voice::VoiceState,
     },
     utils::Color,
 };
-use tracing::Level;
+use chrono::Utc;
+use tracing::{error, info, Level};
 use tracing_subscriber::{prelude::*, fmt::{layer, time::LocalTime}};
 
-use std::{env, fs::File, io::Read, path::Path};
+use std::{env, path::Path, time::Duration};
 
-use commands::{math::*, messages::*, meta::*, ping::*, role::{mutex, check_mutex_roles}, test::*};
+use commands::{announce::announce, apikey::apikey, embed::embed, math::*, messages::*, meta::*, ping::*, reminder::{remindme, snooze_reminder}, role::{mutex, check_mutex_roles}, time::*, voice::voice, voicehub::voicehub, voicelog::voicelog, webhook::webhook};
 
+use crate::config::read_configuration;
 use crate::limited_budgetworks_server::utils::{add_member_join_role, add_member_welcome_message, add_role_rules_verified};
 
-use serde::{
-    Deserialize, // To deserialize data into structures
-    Serialize,
-};
-
 // use crate::commands::webblock::{edit_interaction, webblock, webblock_check_message};
 
-use rest_api::entry::start_rest_api;
-use crate::utils::database::{get_sqlite_pool, DatabasePool};
+use rest_api::entry::{serve_rest_api, AppState, Discord};
+use crate::utils::database::{get_sqlite_pool, DatabasePool, MIGRATOR};
+use crate::utils::embeds::{forget_channel_embeds, forget_embed, saved_embed_channel};
+use crate::utils::guild_events::{publish_guild_event, GuildEventKind, GuildEvents};
+use crate::utils::message_store::MessageStore;
+use crate::utils::metrics::{client_metrics, EventCounter, Metrics};
+use crate::utils::role_selectors::{forget_channel_role_selectors, forget_role_selector, MENU_CUSTOM_ID, ROLE_BUTTON_PREFIX};
+use crate::utils::scheduler::run_scheduler;
+use crate::utils::shutdown::{stop_signal, Shutdown};
+use crate::utils::voice_hubs::{cleanup_hub_voice_channels, forget_channel_voice_hub, handle_voice_hub};
+use crate::utils::voice_log::log_voice_state;
+use crate::utils::voice_sessions::{reconcile_voice_sessions, record_voice_session, VoicePeriod};
+use crate::utils::webhooks::run_webhook_dispatcher;
 
 mod commands;
 mod config;
-mod edbh;
 mod error;
 mod limited_budgetworks_server;
 mod misc;
@@ -46,6 +56,9 @@ mod utils;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+//how long requests in flight get to finish once stopping
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
 const _TEST_SERVER_ID: &u64 = &373993407741427713_u64;
 
 #[group]
@@ -53,34 +66,22 @@ const _TEST_SERVER_ID: &u64 = &373993407741427713_u64;
 struct General;
 
 #[group]
-#[commands(multiply)]
+#[commands(multiply, calc)]
 struct Math;
 
 #[group]
 #[commands(createroleselection)]
 struct Moderation;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct ConfigurationData {
-    pub discord_token: String,
-    pub application_id: String,
-}
-
-fn read_configuration() -> Option<ConfigurationData> {
-    match File::open("config.toml") {
-        Ok(mut file) => {
-            let mut contents = String::new();
-            file.read_to_string(&mut contents).unwrap();
-            let configuration = toml::from_str::<ConfigurationData>(&contents).unwrap();
-
-            Some(configuration)
-        }
-
-        Err(why) => {
-            println!("Unable to open config file. Why: {why}");
-            None
-        }
+/// `period` of `/voice stats` and `/voice leaderboard`
+fn voice_period_option(o: &mut CreateApplicationCommandOption) -> &mut CreateApplicationCommandOption {
+    o.kind(CommandOptionType::String);
+    o.name("period");
+    o.description("How far back to look, the last week by default");
+    for period in VoicePeriod::ALL {
+        o.add_string_choice(period.description(), period.name());
     }
+    o
 }
 
 async fn setup_slash_commands(ctx: &Context) {
@@ -166,6 +167,502 @@ if let Err(why) = Command::create_global_application_command(&ctx, |command| {
     println!("Unable to create slash command: {why}");
 }
 
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("ping");
+    c.description("Check that the bot is responding")
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("invis");
+    c.description("Set the bot's status to invisible");
+    c.default_member_permissions(Permissions::ADMINISTRATOR)
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("online");
+    c.description("Set the bot's status to online");
+    c.default_member_permissions(Permissions::ADMINISTRATOR)
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("timestamp");
+    c.description("Show the current time")
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("time");
+    c.description("Show a time in everyone's own timezone");
+    c.create_option(|show| {
+        show.kind(CommandOptionType::SubCommand);
+        show.name("show");
+        show.description("Show the current time or a time like \"tomorrow 8pm\"");
+        show.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("when");
+            o.description("e.g. friday 18:30, in 2 hours, 2026-11-01 18:00 Europe/Berlin")
+        });
+        show.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("timezone");
+            o.description("IANA timezone such as America/New_York, defaults to yours")
+        })
+    });
+    c.create_option(|timezone| {
+        timezone.kind(CommandOptionType::SubCommand);
+        timezone.name("timezone");
+        timezone.description("Show or set your default timezone");
+        timezone.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("timezone");
+            o.description("IANA timezone such as America/New_York")
+        })
+    })
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("announce");
+    c.description("Post messages at a set time or on a schedule");
+    c.default_member_permissions(Permissions::MANAGE_GUILD);
+    c.dm_permission(false);
+    c.create_option(|schedule| {
+        schedule.kind(CommandOptionType::SubCommand);
+        schedule.name("schedule");
+        schedule.description("Schedule a one-off or repeating announcement");
+        schedule.create_sub_option(|o| {
+            o.kind(CommandOptionType::Channel);
+            o.name("channel");
+            o.description("Channel to post in");
+            o.required(true);
+            o.channel_types(&[ChannelType::Text, ChannelType::News])
+        });
+        schedule.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("when");
+            o.description("First post, e.g. friday 18:00 or 2026-11-01 09:00")
+        });
+        schedule.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("repeat");
+            o.description("Cron schedule, e.g. 0 18 * * fri for every friday at 18:00, or @daily")
+        });
+        schedule.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("message");
+            o.description("Text to post")
+        });
+        schedule.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("embed");
+            o.description("Message ID of a saved embed to post a copy of")
+        });
+        schedule.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("timezone");
+            o.description("IANA timezone for the times above, defaults to yours")
+        });
+        schedule.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("missed");
+            o.description("What to do with posts missed while the bot was offline");
+            o.add_string_choice("Post once when back (default)", "catch_up");
+            o.add_string_choice("Skip them", "skip")
+        })
+    });
+    c.create_option(|list| {
+        list.kind(CommandOptionType::SubCommand);
+        list.name("list");
+        list.description("List scheduled announcements")
+    });
+    c.create_option(|cancel| {
+        cancel.kind(CommandOptionType::SubCommand);
+        cancel.name("cancel");
+        cancel.description("Cancel a scheduled announcement");
+        cancel.create_sub_option(|o| {
+            o.kind(CommandOptionType::Integer);
+            o.name("id");
+            o.description("Announcement number from /announce list");
+            o.required(true)
+        })
+    })
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("remindme");
+    c.description("Get reminded about something later");
+    c.create_option(|set| {
+        set.kind(CommandOptionType::SubCommand);
+        set.name("set");
+        set.description("Set a reminder");
+        set.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("when");
+            o.description("e.g. in 2 hours, tomorrow 9am or 2026-11-01 18:00");
+            o.required(true)
+        });
+        set.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("message");
+            o.description("What to remind you about");
+            o.required(true);
+            o.max_length(1000)
+        });
+        set.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("delivery");
+            o.description("Where to send the reminder");
+            o.add_string_choice("Direct message (default)", "dm");
+            o.add_string_choice("Reply in this channel", "channel")
+        })
+    });
+    c.create_option(|list| {
+        list.kind(CommandOptionType::SubCommand);
+        list.name("list");
+        list.description("List your pending reminders")
+    });
+    c.create_option(|cancel| {
+        cancel.kind(CommandOptionType::SubCommand);
+        cancel.name("cancel");
+        cancel.description("Cancel a pending reminder");
+        cancel.create_sub_option(|o| {
+            o.kind(CommandOptionType::Integer);
+            o.name("id");
+            o.description("Reminder number from /remindme list");
+            o.required(true)
+        })
+    })
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("apikey");
+    c.description("Manage keys for the REST API");
+    c.default_member_permissions(Permissions::MANAGE_GUILD);
+    c.dm_permission(false);
+    c.create_option(|create| {
+        create.kind(CommandOptionType::SubCommand);
+        create.name("create");
+        create.description("Create a key that can manage this server through the API");
+        create.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("name");
+            o.description("What the key is for");
+            o.required(true);
+            o.max_length(100)
+        })
+    });
+    c.create_option(|list| {
+        list.kind(CommandOptionType::SubCommand);
+        list.name("list");
+        list.description("List this server's API keys")
+    });
+    c.create_option(|revoke| {
+        revoke.kind(CommandOptionType::SubCommand);
+        revoke.name("revoke");
+        revoke.description("Revoke an API key");
+        revoke.create_sub_option(|o| {
+            o.kind(CommandOptionType::Integer);
+            o.name("id");
+            o.description("Key number from /apikey list");
+            o.required(true)
+        })
+    })
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("voice");
+    c.description("Time spent in voice channels");
+    c.dm_permission(false);
+    c.create_option(|stats| {
+        stats.kind(CommandOptionType::SubCommand);
+        stats.name("stats");
+        stats.description("How long a member has been in voice");
+        stats.create_sub_option(|o| {
+            o.kind(CommandOptionType::User);
+            o.name("member");
+            o.description("Defaults to you")
+        });
+        stats.create_sub_option(voice_period_option)
+    });
+    c.create_option(|leaderboard| {
+        leaderboard.kind(CommandOptionType::SubCommand);
+        leaderboard.name("leaderboard");
+        leaderboard.description("Who has been in voice the longest");
+        leaderboard.create_sub_option(voice_period_option)
+    })
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("voicehub");
+    c.description("Give members their own voice channel when they join a hub channel");
+    c.default_member_permissions(Permissions::MANAGE_GUILD);
+    c.dm_permission(false);
+    c.create_option(|set| {
+        set.kind(CommandOptionType::SubCommand);
+        set.name("set");
+        set.description("Choose the hub channel and how the channels it opens are set up");
+        set.create_sub_option(|o| {
+            o.kind(CommandOptionType::Channel);
+            o.name("channel");
+            o.description("Voice channel members join to get their own, theirs go in its category");
+            o.required(true);
+            o.channel_types(&[ChannelType::Voice])
+        });
+        set.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("name");
+            o.description("Name of the channels, {name} is the member's name. {name}'s channel by default");
+            o.max_length(100)
+        });
+        set.create_sub_option(|o| {
+            o.kind(CommandOptionType::Integer);
+            o.name("limit");
+            o.description("How many members fit in the channels, no limit by default");
+            o.min_int_value(1);
+            o.max_int_value(99)
+        })
+    });
+    c.create_option(|show| {
+        show.kind(CommandOptionType::SubCommand);
+        show.name("show");
+        show.description("Show the voice hub")
+    });
+    c.create_option(|disable| {
+        disable.kind(CommandOptionType::SubCommand);
+        disable.name("disable");
+        disable.description("Stop opening channels from the hub")
+    })
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("voicelog");
+    c.description("Log voice channel activity");
+    c.default_member_permissions(Permissions::MANAGE_GUILD);
+    c.dm_permission(false);
+    c.create_option(|set| {
+        set.kind(CommandOptionType::SubCommand);
+        set.name("set");
+        set.description("Choose where voice activity is logged and what's in it");
+        set.create_sub_option(|o| {
+            o.kind(CommandOptionType::Channel);
+            o.name("channel");
+            o.description("Channel to log in");
+            o.required(true);
+            o.channel_types(&[ChannelType::Text])
+        });
+        set.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("events");
+            o.description("Comma separated, e.g. join,leave,move,self_mute,stream,video. All by default");
+            o.max_length(200)
+        })
+    });
+    c.create_option(|show| {
+        show.kind(CommandOptionType::SubCommand);
+        show.name("show");
+        show.description("Show where voice activity is logged")
+    });
+    c.create_option(|disable| {
+        disable.kind(CommandOptionType::SubCommand);
+        disable.name("disable");
+        disable.description("Stop logging voice activity")
+    })
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("webhook");
+    c.description("Send bot events to your own services");
+    c.default_member_permissions(Permissions::MANAGE_GUILD);
+    c.dm_permission(false);
+    c.create_option(|add| {
+        add.kind(CommandOptionType::SubCommand);
+        add.name("add");
+        add.description("Register a URL to POST events to");
+        add.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("url");
+            o.description("Where events are sent");
+            o.required(true);
+            o.max_length(500)
+        });
+        add.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("events");
+            o.description("Comma separated: member_join, role_selector_change, link_blocked, voice_join, voice_leave");
+            o.max_length(200)
+        });
+        add.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("secret");
+            o.description("Signs the requests, one is made up if left out");
+            o.min_length(16);
+            o.max_length(200)
+        })
+    });
+    c.create_option(|list| {
+        list.kind(CommandOptionType::SubCommand);
+        list.name("list");
+        list.description("List this server's webhooks")
+    });
+    for (name, description) in [
+        ("remove", "Stop sending events to a webhook"),
+        ("test", "Send a test event to a webhook"),
+        ("log", "Show a webhook's latest deliveries"),
+    ] {
+        c.create_option(|subcommand| {
+            subcommand.kind(CommandOptionType::SubCommand);
+            subcommand.name(name);
+            subcommand.description(description);
+            subcommand.create_sub_option(|o| {
+                o.kind(CommandOptionType::Integer);
+                o.name("id");
+                o.description("Webhook number from /webhook list");
+                o.required(true)
+            })
+        });
+    }
+    c
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("embed");
+    c.description("Manage the server's saved embeds");
+    c.default_member_permissions(Permissions::MANAGE_GUILD);
+    c.dm_permission(false);
+    c.create_option(|history| {
+        history.kind(CommandOptionType::SubCommand);
+        history.name("history");
+        history.description("List every saved version of an embed");
+        history.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("message");
+            o.description("ID of the embed's message");
+            o.required(true)
+        })
+    });
+    c.create_option(|rollback| {
+        rollback.kind(CommandOptionType::SubCommand);
+        rollback.name("rollback");
+        rollback.description("Put an earlier version of an embed back");
+        rollback.create_sub_option(|o| {
+            o.kind(CommandOptionType::String);
+            o.name("message");
+            o.description("ID of the embed's message");
+            o.required(true)
+        });
+        rollback.create_sub_option(|o| {
+            o.kind(CommandOptionType::Integer);
+            o.name("revision");
+            o.description("Revision number from /embed history");
+            o.min_int_value(1);
+            o.required(true)
+        })
+    })
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("multiply");
+    c.description("Multiply two numbers");
+    c.create_option(|o| {
+        o.kind(CommandOptionType::Number);
+        o.name("first");
+        o.description("First factor");
+        o.required(true)
+    });
+    c.create_option(|o| {
+        o.kind(CommandOptionType::Number);
+        o.name("second");
+        o.description("Second factor");
+        o.required(true)
+    })
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("calc");
+    c.description("Evaluate an arithmetic expression");
+    c.create_option(|o| {
+        o.kind(CommandOptionType::String);
+        o.name("expression");
+        o.description("e.g. (2 + 3) * sqrt(16) ^ 2 % 7");
+        o.required(true);
+        o.max_length(500)
+    });
+    c.create_option(|o| {
+        o.kind(CommandOptionType::Boolean);
+        o.name("integer");
+        o.description("Exact arithmetic on whole numbers of any size")
+    })
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
+if let Err(why) = Command::create_global_application_command(&ctx, |c| {
+    c.name("createroleselection");
+    c.description("Set up a menu for members to pick their own roles");
+    c.default_member_permissions(Permissions::ADMINISTRATOR);
+    c.dm_permission(false)
+})
+.await
+{
+    println!("Unable to create slash command: {why}");
+}
+
 if let Err(why) = Command::create_global_application_command(&ctx, |c| {
     c.name("webblock");
     c.description("Create a block list for unwanted links");
@@ -229,6 +726,12 @@ pub struct Handler;
 #[async_trait]
 impl EventHandler for Handler {
     async fn guild_member_addition(&self, ctx: Context, new_member: Member) {
+        publish_guild_event(&ctx, new_member.guild_id, GuildEventKind::MemberJoined {
+            user_id: new_member.user.id,
+            name: new_member.user.name.clone(),
+        })
+        .await;
+
         if new_member.guild_id.as_u64() == &713889872359981076 {
             add_member_join_role(&ctx, &new_member).await;
             add_member_welcome_message(&ctx, &new_member).await;
@@ -236,23 +739,147 @@ impl EventHandler for Handler {
     }
 
     async fn guild_member_update(&self, ctx: Context, old: Option<Member>, new: Member) {
+        //without the old member there's no telling whether the timeout changed
+        if let Some(old) = &old {
+            if old.communication_disabled_until != new.communication_disabled_until {
+                publish_guild_event(&ctx, new.guild_id, GuildEventKind::MemberTimedOut {
+                    user_id: new.user.id,
+                    until: new.communication_disabled_until,
+                })
+                .await;
+            }
+        }
+
         if let Err(why) = check_mutex_roles(&ctx, &old.as_ref(), &mut new.clone()).await {
             println!("Error check mutex roles: {why}");
         }
     }
 
+    async fn guild_ban_addition(&self, ctx: Context, guild_id: GuildId, banned_user: User) {
+        publish_guild_event(&ctx, guild_id, GuildEventKind::MemberBanned {
+            user_id: banned_user.id,
+            name: banned_user.name,
+        })
+        .await;
+    }
+
+    async fn guild_ban_removal(&self, ctx: Context, guild_id: GuildId, unbanned_user: User) {
+        publish_guild_event(&ctx, guild_id, GuildEventKind::MemberUnbanned {
+            user_id: unbanned_user.id,
+            name: unbanned_user.name,
+        })
+        .await;
+    }
+
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
         match interaction {
             Interaction::ApplicationCommand(ac) => {
+                let metrics = client_metrics(&ctx).await;
+                metrics.command(&ac.data.name);
+
                 match ac.data.name.as_str() {
-                    "createroleselector" => {
-                        // command.get_interaction_response(&ctx).await.unwrap().delete(&ctx).await;
-                        createroleselectorslash(&ctx, &ac).await.unwrap();
+                    "ping" => {
+                        if let Err(why) = ping_slash(&ctx, &ac).await {
+                            println!("Error with ping command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "invis" => {
+                        if let Err(why) = invis_slash(&ctx, &ac).await {
+                            println!("Error with invis command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "online" => {
+                        if let Err(why) = online_slash(&ctx, &ac).await {
+                            println!("Error with online command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "timestamp" => {
+                        if let Err(why) = timestamp_slash(&ctx, &ac).await {
+                            println!("Error with timestamp command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "time" => {
+                        if let Err(why) = time(&ctx, &ac).await {
+                            println!("Error with time command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "announce" => {
+                        if let Err(why) = announce(&ctx, &ac).await {
+                            println!("Error with announce command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "apikey" => {
+                        if let Err(why) = apikey(&ctx, &ac).await {
+                            println!("Error with apikey command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "voice" => {
+                        if let Err(why) = voice(&ctx, &ac).await {
+                            println!("Error with voice command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "voicehub" => {
+                        if let Err(why) = voicehub(&ctx, &ac).await {
+                            println!("Error with voicehub command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "voicelog" => {
+                        if let Err(why) = voicelog(&ctx, &ac).await {
+                            println!("Error with voicelog command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "webhook" => {
+                        if let Err(why) = webhook(&ctx, &ac).await {
+                            println!("Error with webhook command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "embed" => {
+                        if let Err(why) = embed(&ctx, &ac).await {
+                            println!("Error with embed command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "remindme" => {
+                        if let Err(why) = remindme(&ctx, &ac).await {
+                            println!("Error with remindme command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "multiply" => {
+                        if let Err(why) = multiply_slash(&ctx, &ac).await {
+                            println!("Error with multiply command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "calc" => {
+                        if let Err(why) = calc_slash(&ctx, &ac).await {
+                            println!("Error with calc command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
+                    }
+                    "createroleselection" => {
+                        if let Err(why) = createroleselectorslash(&ctx, &ac).await {
+                            println!("Error with createroleselection command, why: {why}");
+                            metrics.command_error(&ac.data.name);
+                        }
                     }
                     "mutex" => {
                         if let Err(why) = mutex(&ctx, &ac).await {
                             
                             println!("Error with mutex command, why: {why}");
+                            
+                            metrics.command_error(&ac.data.name);
                         };
                     }
                     "webblock" => {
@@ -261,20 +888,23 @@ impl EventHandler for Handler {
                     "Edit Role Selector" => {
                         if let Err(why) = edit_role_selector(&ctx, &ac).await {
                             println!("Unable to edit role selector: {why}");
+                            metrics.command_error(&ac.data.name);
                         };
                     }
 
                     _ => {}
                 }
             }
-            Interaction::MessageComponent(mc) => match mc.data.custom_id.as_str() {
-                "selectmenu" => {
-                    if let Err(why) = autorole_selections(&ctx, &mc).await {
-                        println!("autorole_selection err: {why}");
-                    };
-                }
-                _ => {}
-            },
+            Interaction::MessageComponent(mc) if mc.data.custom_id == MENU_CUSTOM_ID || mc.data.custom_id.starts_with(ROLE_BUTTON_PREFIX) => {
+                if let Err(why) = autorole_selections(&ctx, &mc).await {
+                    println!("autorole_selection err: {why}");
+                };
+            }
+            Interaction::MessageComponent(mc) if mc.data.custom_id.starts_with("reminder_snooze") => {
+                if let Err(why) = snooze_reminder(&ctx, &mc).await {
+                    println!("snooze_reminder err: {why}");
+                };
+            }
             // Interaction::ModalSubmit(msi) => match msi.data.custom_id.as_str().split(" ").next().unwrap_or("{}") {
             //     "webblockedit" => {
             //         edit_interaction(&ctx, &msi).await.unwrap();
@@ -293,6 +923,73 @@ impl EventHandler for Handler {
         // }
     }
 
+    async fn message_update(&self, ctx: Context, _old_if_available: Option<Message>, _new: Option<Message>, event: MessageUpdateEvent) {
+        if let Some(store) = ctx.data.read().await.get::<MessageStore>() {
+            store.update(&event);
+        }
+
+        //link previews update messages too, only edits are interesting
+        if let (Some(guild_id), Some(_)) = (event.guild_id, event.edited_timestamp) {
+            let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+            let lookup = saved_embed_channel(&pool, guild_id, event.id);
+            match client_metrics(&ctx).await.time_query("saved_embed_lookup", lookup).await {
+                Ok(Some(channel_id)) => {
+                    publish_guild_event(&ctx, guild_id, GuildEventKind::EmbedEdited {
+                        channel_id,
+                        message_id: event.id,
+                    })
+                    .await;
+                }
+                Ok(None) => {}
+                Err(why) => println!("Unable to look up edited message: {why}"),
+            }
+        }
+    }
+
+    async fn message_delete(&self, ctx: Context, _channel_id: ChannelId, deleted_message_id: MessageId, _guild_id: Option<GuildId>) {
+        let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+        if let Some(store) = ctx.data.read().await.get::<MessageStore>() {
+            store.remove(deleted_message_id);
+        }
+        if let Err(why) = forget_embed(&pool, deleted_message_id).await {
+            println!("Unable to forget deleted embed: {why}");
+        }
+        if let Err(why) = forget_role_selector(&pool, deleted_message_id).await {
+            println!("Unable to forget deleted role selector: {why}");
+        }
+    }
+
+    async fn message_delete_bulk(&self, ctx: Context, _channel_id: ChannelId, multiple_deleted_messages_ids: Vec<MessageId>, _guild_id: Option<GuildId>) {
+        let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+        for message_id in multiple_deleted_messages_ids {
+            if let Some(store) = ctx.data.read().await.get::<MessageStore>() {
+                store.remove(message_id);
+            }
+            if let Err(why) = forget_embed(&pool, message_id).await {
+                println!("Unable to forget deleted embed: {why}");
+            }
+            if let Err(why) = forget_role_selector(&pool, message_id).await {
+                println!("Unable to forget deleted role selector: {why}");
+            }
+        }
+    }
+
+    async fn channel_delete(&self, ctx: Context, channel: &GuildChannel) {
+        let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+        if let Some(store) = ctx.data.read().await.get::<MessageStore>() {
+            store.remove_channel(channel.id);
+        }
+        if let Err(why) = forget_channel_embeds(&pool, channel.id).await {
+            println!("Unable to forget embeds of deleted channel: {why}");
+        }
+        if let Err(why) = forget_channel_role_selectors(&pool, channel.id).await {
+            println!("Unable to forget role selectors of deleted channel: {why}");
+        }
+        if let Err(why) = forget_channel_voice_hub(&pool, channel.id).await {
+            println!("Unable to forget voice hub of deleted channel: {why}");
+        }
+    }
+
     async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
         add_role_rules_verified(&ctx, &reaction).await;
     }
@@ -304,7 +1001,7 @@ impl EventHandler for Handler {
         if let Err(why) = ChannelId(773036830580408330)
             .send_message(&ctx, |m| {
                 m.embed(|e| {
-                    e.author(|a| a.icon_url(&ready.user.face()).name(&ready.user.name))
+                    e.author(|a| a.icon_url(ready.user.face()).name(&ready.user.name))
                         .description(format!(
                             "\
                       {} is connected!\n\
@@ -321,48 +1018,58 @@ impl EventHandler for Handler {
         };
 
         setup_slash_commands(&ctx).await;
+    }
+
+    //voice states only come with the guilds after Ready, so sessions are reconciled once they're all in
+    async fn cache_ready(&self, ctx: Context, guilds: Vec<GuildId>) {
+        //only the guilds that arrived, sessions in unavailable ones stay as they are
+        let guilds: Vec<_> = guilds.into_iter().filter_map(|guild_id| ctx.cache.guild(guild_id)).collect();
+        let guild_ids: Vec<GuildId> = guilds.iter().map(|guild| guild.id).collect();
+        let in_voice: Vec<(GuildId, UserId, ChannelId)> = guilds
+            .iter()
+            .flat_map(|guild| {
+                guild
+                    .voice_states
+                    .values()
+                    .filter(|state| !guild.members.get(&state.user_id).is_some_and(|m| m.user.bot))
+                    .filter_map(|state| state.channel_id.map(|channel_id| (guild.id, state.user_id, channel_id)))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
 
-        start_rest_api(&ctx).await;
+        let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+        match reconcile_voice_sessions(&pool, &guild_ids, &in_voice, Utc::now().timestamp()).await {
+            Ok((closed, opened)) => info!("Reconciled voice sessions, {} closed and {} opened", closed, opened),
+            Err(why) => error!("Unable to reconcile voice sessions: {}", why),
+        }
+
+        cleanup_hub_voice_channels(&ctx).await;
     }
 
     async fn voice_state_update(&self, ctx: Context, old: Option<VoiceState>, new: VoiceState) {
-        if let Some(ref guild_id) = new.guild_id {
-            if guild_id.as_u64() == &687876072045412560_u64 {
-                edbh::utils::voice_state_changed(&ctx, guild_id, &old, &new).await;
+        if let Some(guild_id) = new.guild_id {
+            let from = old.as_ref().and_then(|o| o.channel_id);
+            if from != new.channel_id {
+                publish_guild_event(&ctx, guild_id, GuildEventKind::VoiceMoved {
+                    user_id: new.user_id,
+                    from,
+                    to: new.channel_id,
+                })
+                .await;
+
+                if !new.member.as_ref().is_some_and(|m| m.user.bot) {
+                    let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+                    let recorded = record_voice_session(&pool, guild_id, new.user_id, new.channel_id, Utc::now().timestamp());
+                    if let Err(why) = client_metrics(&ctx).await.time_query("voice_session", recorded).await {
+                        error!("Unable to record voice session: {}", why);
+                    }
+                }
+
+                handle_voice_hub(&ctx, guild_id, from, &new).await;
             }
-        }
 
-        // if let Some(ref guild_id) = guild_id{
-        //     if guild_id.as_u64() == &373993407741427713_u64 {
-        //         if let Some(state_change) = identify_state(guild_id, &old, &new) {
-        //             println!("{}", &state_change);
-        //
-        //             match state_change {
-        //                 VoiceStateChange::LeftVoiceChannel => {
-        //                     if let Err(why) = ChannelId(805612483951722496).send_message(&ctx, |m| m
-        //                         .embed(|e| e
-        //                             .title("User left Voice Channel")
-        //                             .author(|a| a
-        //                                 .name(format!("{}#{}", &new.member.as_ref().unwrap().user.name, &new.member.as_ref().unwrap().user.discriminator))
-        //                                 .icon_url(new.member.as_ref().unwrap().user.avatar_url().unwrap())
-        //                             )
-        //                             .field("Member", &new.member.as_ref().unwrap().user.name, false)
-        //                             .timestamp(Utc::now().to_rfc3339()))).await {
-        //                         println!("Error sending test voice log. Why: {}", why);
-        //                     };
-        //                 }
-        //                 VoiceStateChange::JoinedVoiceChannel => {}
-        //                 VoiceStateChange::ServerDeafened => {}
-        //                 VoiceStateChange::ServerMuted => {}
-        //                 VoiceStateChange::SelfDeafened => {}
-        //                 VoiceStateChange::SelfMuted => {}
-        //                 VoiceStateChange::SelfStream => {}
-        //                 VoiceStateChange::SelfVideo => {}
-        //                 VoiceStateChange::Suppress => {}
-        //             }
-        //         }
-        //     }
-        // }
+            log_voice_state(&ctx, guild_id, &old, &new).await;
+        }
     }
 }
 
@@ -385,29 +1092,91 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     let configuration = read_configuration().unwrap();
 
-    let framework = StandardFramework::new()
-        .configure(|c| c.prefix("~").ignore_bots(true).with_whitespace(true).case_insensitivity(true))
-        .group(&GENERAL_GROUP)
-        .group(&MATH_GROUP)
-        .group(&MODERATION_GROUP);
+    let metrics = Metrics::new();
 
-    let mut client = Client::builder(configuration.discord_token, GatewayIntents::all())
+    let mut client_builder = Client::builder(&configuration.discord_token, GatewayIntents::all())
         .event_handler(Handler)
-        .framework(framework)
-        .application_id(configuration.application_id.parse().unwrap())
+        .raw_event_handler(EventCounter { metrics: metrics.clone() })
+        .application_id(configuration.application_id.parse().unwrap());
+
+    //Prefix commands are being replaced by slash commands, the framework can be switched off in config.toml
+    if configuration.prefix_commands {
+        let framework = StandardFramework::new()
+            .configure(|c| c.prefix("~").ignore_bots(true).with_whitespace(true).case_insensitivity(true))
+            .group(&GENERAL_GROUP)
+            .group(&MATH_GROUP)
+            .group(&MODERATION_GROUP);
+
+        client_builder = client_builder.framework(framework);
+    }
+
+    let mut client = client_builder
         .await
         .expect("Error creating client");
 
+    let pool = get_sqlite_pool("sqlite://zangra.db").await?;
+    MIGRATOR.run(&pool).await?;
+    let message_store = MessageStore::new();
+    let events = GuildEvents::new();
+    let shutdown = Shutdown::new();
+
     {
         let mut data = client.data.write().await;
-        let pool = get_sqlite_pool("sqlite://zangra.db").await?;
-        sqlx::migrate!("./migrations").run(&pool).await?;
-        data.insert::<DatabasePool>(pool);
+        data.insert::<DatabasePool>(pool.clone());
+        data.insert::<MessageStore>(message_store.clone());
+        data.insert::<GuildEvents>(events.clone());
+        data.insert::<Metrics>(metrics.clone());
     }
 
+    //started here rather than in ready, which fires again on every reconnect
+    let scheduler = tokio::spawn(run_scheduler(client.cache_and_http.http.clone(), pool.clone(), shutdown.clone()));
+    let dispatcher = tokio::spawn(run_webhook_dispatcher(events.clone(), pool.clone(), shutdown.clone()));
+
+    let discord = Discord {
+        cache: client.cache_and_http.cache.clone(),
+        http: client.cache_and_http.http.clone(),
+    };
+    let app_state = AppState::new(
+        discord,
+        pool.clone(),
+        message_store,
+        events,
+        shutdown.clone(),
+        metrics,
+        client.shard_manager.clone(),
+        &configuration,
+    );
+    let rest_api = tokio::spawn(serve_rest_api(app_state));
+
+    let shard_manager = client.shard_manager.clone();
+    let stopping = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            tokio::select! {
+                _ = stop_signal() => info!("Stop signal received, shutting down"),
+                _ = shutdown.clone().wait() => {}
+            }
+            shutdown.trigger();
+
+            if tokio::time::timeout(DRAIN_TIMEOUT, rest_api).await.is_err() {
+                info!("Rest API didn't drain in time");
+            }
+            //no more gateway events write to the database once the shards are down, then the
+            //background tasks finish what they started and the pool goes last
+            shard_manager.lock().await.shutdown_all().await;
+            let _ = dispatcher.await;
+            let _ = scheduler.await;
+            pool.close().await;
+        }
+    });
+
     if let Err(why) = client.start().await {
         println!("Client error: {why:?}");
     }
 
+    //the client can also stop on its own, the rest still has to be wound down
+    shutdown.trigger();
+    let _ = stopping.await;
+
     Ok(())
 }
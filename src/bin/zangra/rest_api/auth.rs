@@ -0,0 +1,251 @@
+use std::collections::{HashMap, HashSet};
+
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, Request},
+    middleware::Next,
+    response::Response,
+    Extension,
+};
+use chrono::Utc;
+use rand::{rngs::OsRng, RngCore};
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use sha2::{Digest, Sha256};
+use sqlx::query;
+use tracing::{error, warn};
+
+use crate::{
+    rest_api::{
+        entry::AppState,
+        error::ApiError,
+        oauth::{find_session, SESSION_COOKIE},
+    },
+    utils::embeds::RevisionAuthor,
+};
+
+//makes leaked keys easy to spot in logs and secret scanners
+const API_KEY_PREFIX: &str = "zangra_";
+
+/// Which guilds a caller may act on
+#[derive(Debug, Clone)]
+pub enum ApiScope {
+    AllGuilds,
+    Guilds(HashSet<GuildId>),
+    /// Logged in through Discord, any guild where the user has Manage Server
+    Member(UserId),
+}
+
+/// The authenticated caller, added to the request extensions by [`authenticate`]
+#[derive(Debug, Clone)]
+pub struct ApiPrincipal {
+    pub name: String,
+    /// Stable id of the key or user, unlike `name` it's never shared by two callers
+    pub client: String,
+    pub scope: ApiScope,
+}
+
+impl From<&ApiPrincipal> for RevisionAuthor {
+    fn from(principal: &ApiPrincipal) -> Self {
+        let user_id = match principal.scope {
+            ApiScope::Member(user_id) => Some(user_id),
+            _ => None,
+        };
+
+        RevisionAuthor {
+            user_id,
+            name: principal.name.clone(),
+        }
+    }
+}
+
+impl ApiPrincipal {
+    /// The Discord user behind a dashboard session, API keys aren't anyone in particular
+    pub fn member(&self) -> Option<UserId> {
+        match self.scope {
+            ApiScope::Member(user_id) => Some(user_id),
+            _ => None,
+        }
+    }
+
+    pub async fn can_access(&self, state: &AppState, guild_id: GuildId) -> bool {
+        match &self.scope {
+            ApiScope::AllGuilds => true,
+            ApiScope::Guilds(guilds) => guilds.contains(&guild_id),
+            ApiScope::Member(user_id) => can_manage_guild(state, guild_id, *user_id).await,
+        }
+    }
+}
+
+/// Checked against the bot's own view of the guild rather than anything the client sends
+async fn can_manage_guild(state: &AppState, guild_id: GuildId, user_id: UserId) -> bool {
+    let guild = match state.discord.cache.guild(guild_id) {
+        Some(guild) => guild,
+        None => return false,
+    };
+
+    match guild.member_permissions(&state.discord, user_id).await {
+        Ok(permissions) => permissions.manage_guild() || permissions.administrator(),
+        //not a member
+        Err(_) => false,
+    }
+}
+
+/// 32 random bytes as hex
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+
+    hex::encode(bytes)
+}
+
+/// A new random key, shown once and never stored in plain text
+pub fn generate_api_key() -> String {
+    format!("{API_KEY_PREFIX}{}", random_token())
+}
+
+/// SHA-256 hex digest for API keys and session cookies, both are random enough that a salt adds nothing
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Looks the bearer token up among the configured keys, then the keys created with `/apikey`
+async fn find_principal(state: &AppState, key: &str) -> Option<ApiPrincipal> {
+    let key_hash = hash_token(key);
+
+    if let Some(configured) = state.configured_keys.iter().find(|k| k.key_hash.eq_ignore_ascii_case(&key_hash)) {
+        let scope = match &configured.guilds {
+            Some(guilds) => ApiScope::Guilds(guilds.iter().map(|g| GuildId(*g)).collect()),
+            None => ApiScope::AllGuilds,
+        };
+
+        return Some(ApiPrincipal {
+            name: configured.name.clone(),
+            client: format!("config:{}", configured.key_hash.to_ascii_lowercase()),
+            scope,
+        });
+    }
+
+    let lookup = query!("SELECT ApiKeyId, GuildId, Name FROM ApiKey WHERE KeyHash = ?", key_hash).fetch_optional(&state.db_pool);
+    let row = match state.metrics.time_query("api_key_lookup", lookup).await {
+        Ok(row) => row?,
+        Err(why) => {
+            error!("Unable to read API keys: {}", why);
+            return None;
+        }
+    };
+
+    let now = Utc::now().timestamp();
+    if let Err(why) = query!("UPDATE ApiKey SET LastUsedAt = ? WHERE ApiKeyId = ?", now, row.ApiKeyId)
+        .execute(&state.db_pool)
+        .await
+    {
+        error!("Unable to update API key usage: {}", why);
+    }
+
+    Some(ApiPrincipal {
+        name: row.Name,
+        client: format!("key:{}", row.ApiKeyId),
+        scope: ApiScope::Guilds(HashSet::from([GuildId(row.GuildId as u64)])),
+    })
+}
+
+/// Accepts an `Authorization: Bearer <key>` header or a dashboard session cookie, anything else
+/// gets 401
+pub async fn authenticate<B>(
+    State(state): State<AppState>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApiError> {
+    let key = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+
+    let principal = match key {
+        Some(key) => find_principal(&state, key).await,
+        None => match cookie(request.headers(), SESSION_COOKIE) {
+            Some(session) => find_session(&state, session).await,
+            None => None,
+        },
+    };
+
+    match principal {
+        Some(principal) => {
+            request.extensions_mut().insert(principal);
+            Ok(next.run(request).await)
+        }
+        None => Err(ApiError::unauthorized()),
+    }
+}
+
+/// A cookie's value from the request's `Cookie` headers
+pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+/// The guild a channel belongs to, from the cache when possible
+pub async fn channel_guild(state: &AppState, channel_id: ChannelId) -> Option<GuildId> {
+    if let Some(channel) = state.discord.cache.guild_channel(channel_id) {
+        return Some(channel.guild_id);
+    }
+
+    match state.discord.http.get_channel(*channel_id.as_u64()).await {
+        Ok(channel) => channel.guild().map(|c| c.guild_id),
+        Err(why) => {
+            error!("Unable to look up channel {}: {}", channel_id, why);
+            None
+        }
+    }
+}
+
+/// Answers 403 when the route's `guild_id`, or the guild owning its `channel_id`, is outside
+/// the caller's scope. A channel from a different guild than the `guild_id` given is refused too.
+pub async fn authorize_guild<B>(
+    State(state): State<AppState>,
+    Extension(principal): Extension<ApiPrincipal>,
+    params: Option<Path<HashMap<String, String>>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApiError> {
+    let params = params.map(|Path(params)| params).unwrap_or_default();
+
+    let guild_id = match params.get("guild_id").map(|g| g.parse::<u64>()) {
+        Some(Ok(guild_id)) => Some(GuildId(guild_id)),
+        Some(Err(_)) => return Err(ApiError::forbidden()),
+        None => None,
+    };
+
+    let channel_guild_id = match params.get("channel_id").map(|c| c.parse::<u64>()) {
+        Some(Ok(channel_id)) => match channel_guild(&state, ChannelId(channel_id)).await {
+            Some(channel_guild_id) => Some(channel_guild_id),
+            None => return Err(ApiError::forbidden()),
+        },
+        Some(Err(_)) => return Err(ApiError::forbidden()),
+        None => None,
+    };
+
+    let allowed = match (guild_id, channel_guild_id) {
+        (Some(guild_id), Some(channel_guild_id)) => {
+            guild_id == channel_guild_id && principal.can_access(&state, guild_id).await
+        }
+        (Some(guild_id), None) | (None, Some(guild_id)) => principal.can_access(&state, guild_id).await,
+        //routes that aren't about one guild filter their results by scope themselves
+        (None, None) => true,
+    };
+
+    if allowed {
+        Ok(next.run(request).await)
+    } else {
+        warn!("{} was refused {}", principal.name, request.uri());
+        Err(ApiError::forbidden())
+    }
+}
@@ -0,0 +1,734 @@
+//! Request and response bodies of the REST API. These are the contract with the dashboard and
+//! what `/api/openapi.json` describes, serenity's models stay behind them so a serenity upgrade
+//! can't change the wire format. Snowflakes are strings, JavaScript can't hold them as numbers.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serenity::{
+    builder::CreateEmbed,
+    model::{
+        channel::{ChannelType, Embed, GuildChannel, Message},
+        guild::{Emoji, Role},
+        id::RoleId,
+        Timestamp,
+    },
+};
+use utoipa::ToSchema;
+
+use crate::{
+    rest_api::{
+        error::{ApiError, ApiErrorCode, FieldError},
+        routes::channels::MessageDetails,
+    },
+    utils::{
+        embeds::{EmbedChange, EmbedRevision},
+        guild_events::{GuildEvent, GuildEventKind},
+        role_selectors::{RoleOption, RoleSelector, RoleSelectorStyle},
+    },
+};
+
+/// A message as the dashboard sees it
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MessageDto {
+    pub id: String,
+    pub channel_id: String,
+    pub guild_id: Option<String>,
+    pub content: String,
+    pub embeds: Vec<EmbedDto>,
+    /// RFC 3339
+    pub timestamp: String,
+    pub edited_timestamp: Option<String>,
+}
+
+impl From<Message> for MessageDto {
+    fn from(message: Message) -> Self {
+        MessageDto {
+            id: message.id.to_string(),
+            channel_id: message.channel_id.to_string(),
+            guild_id: message.guild_id.map(|g| g.to_string()),
+            content: message.content,
+            embeds: message.embeds.into_iter().map(EmbedDto::from).collect(),
+            timestamp: message.timestamp.to_string(),
+            edited_timestamp: message.edited_timestamp.map(|t| t.to_string()),
+        }
+    }
+}
+
+/// An embed in Discord's own layout, used by the `/api/embed` routes
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct EmbedDto {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    /// `0xRRGGBB` as a number
+    pub color: Option<u32>,
+    /// RFC 3339
+    pub timestamp: Option<String>,
+    pub footer: Option<EmbedFooterDto>,
+    pub image: Option<EmbedMediaDto>,
+    pub thumbnail: Option<EmbedMediaDto>,
+    pub author: Option<EmbedAuthorDto>,
+    #[serde(default)]
+    pub fields: Vec<EmbedFieldDto>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EmbedFooterDto {
+    pub text: String,
+    pub icon_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EmbedMediaDto {
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EmbedAuthorDto {
+    pub name: String,
+    pub url: Option<String>,
+    pub icon_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EmbedFieldDto {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub inline: bool,
+}
+
+impl From<Embed> for EmbedDto {
+    fn from(embed: Embed) -> Self {
+        EmbedDto {
+            title: embed.title,
+            description: embed.description,
+            url: embed.url,
+            color: embed.colour.map(|c| c.0),
+            timestamp: embed.timestamp,
+            footer: embed.footer.map(|f| EmbedFooterDto {
+                text: f.text,
+                icon_url: f.icon_url,
+            }),
+            image: embed.image.map(|i| EmbedMediaDto { url: i.url }),
+            thumbnail: embed.thumbnail.map(|t| EmbedMediaDto { url: t.url }),
+            author: embed.author.map(|a| EmbedAuthorDto {
+                name: a.name,
+                url: a.url,
+                icon_url: a.icon_url,
+            }),
+            fields: embed
+                .fields
+                .into_iter()
+                .map(|f| EmbedFieldDto {
+                    name: f.name,
+                    value: f.value,
+                    inline: f.inline,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<EmbedDto> for CreateEmbed {
+    type Error = ApiError;
+
+    fn try_from(dto: EmbedDto) -> Result<Self, ApiError> {
+        let mut embed = CreateEmbed::default();
+
+        if let Some(title) = dto.title {
+            embed.title(title);
+        }
+        if let Some(description) = dto.description {
+            embed.description(description);
+        }
+        if let Some(url) = dto.url {
+            embed.url(url);
+        }
+        if let Some(color) = dto.color {
+            embed.color(color);
+        }
+        if let Some(timestamp) = dto.timestamp {
+            let timestamp = Timestamp::parse(&timestamp)
+                .map_err(|_| ApiError::validation(format!("`{timestamp}` isn't an RFC 3339 timestamp")))?;
+            embed.timestamp(timestamp);
+        }
+        if let Some(footer) = dto.footer {
+            embed.footer(|f| {
+                f.text(footer.text);
+                if let Some(icon_url) = footer.icon_url {
+                    f.icon_url(icon_url);
+                }
+                f
+            });
+        }
+        if let Some(image) = dto.image {
+            embed.image(image.url);
+        }
+        if let Some(thumbnail) = dto.thumbnail {
+            embed.thumbnail(thumbnail.url);
+        }
+        if let Some(author) = dto.author {
+            embed.author(|a| {
+                a.name(author.name);
+                if let Some(url) = author.url {
+                    a.url(url);
+                }
+                if let Some(icon_url) = author.icon_url {
+                    a.icon_url(icon_url);
+                }
+                a
+            });
+        }
+        for field in dto.fields {
+            embed.field(field.name, field.value, field.inline);
+        }
+
+        Ok(embed)
+    }
+}
+
+/// A page of saved embeds grouped by channel id
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EmbedListResponse {
+    /// Always `success`, messages that couldn't be fetched are in `errors`
+    pub status: String,
+    pub messages: HashMap<String, ChannelMessages>,
+    pub errors: Vec<EmbedFetchErrorDto>,
+    /// Pass as `after` for the next page, unset on the last one
+    pub next: Option<String>,
+}
+
+/// A saved embed that couldn't be fetched from Discord this time
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EmbedFetchErrorDto {
+    pub channel_id: String,
+    pub message_id: String,
+    pub error: ApiErrorCode,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmbedPageQuery {
+    pub limit: Option<u32>,
+    /// Saved embeds are paged in id order, this is the last id of the previous page
+    pub after: Option<u64>,
+}
+
+/// The saved embeds of one channel
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+#[serde(transparent)]
+pub struct ChannelMessages(pub Vec<MessageDto>);
+
+/// One stored version of a saved embed message
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EmbedRevisionDto {
+    /// Starts at 1 and goes up with every edit
+    pub revision: i64,
+    /// API key name or Discord username
+    pub author_name: String,
+    /// Unset for API keys
+    pub author_id: Option<String>,
+    /// RFC 3339
+    pub created_at: String,
+    pub embeds: Vec<EmbedDto>,
+}
+
+impl From<EmbedRevision> for EmbedRevisionDto {
+    fn from(revision: EmbedRevision) -> Self {
+        EmbedRevisionDto {
+            revision: revision.revision,
+            author_name: revision.author.name,
+            author_id: revision.author.user_id.map(|u| u.to_string()),
+            created_at: Timestamp::from_unix_timestamp(revision.created_at)
+                .map(|t| t.to_string())
+                .unwrap_or_default(),
+            embeds: revision.embeds.into_iter().map(EmbedDto::from).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmbedDiffQuery {
+    pub from: i64,
+    /// The latest revision when left out
+    pub to: Option<i64>,
+}
+
+/// What changed between two revisions
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EmbedDiffDto {
+    pub from: i64,
+    pub to: i64,
+    pub changes: Vec<EmbedChangeDto>,
+}
+
+/// A value that differs between the revisions, in Discord's embed layout
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EmbedChangeDto {
+    /// e.g. `embeds[0].fields[2].value`
+    pub path: String,
+    /// Unset when the value was added
+    pub before: Option<Value>,
+    /// Unset when the value was removed
+    pub after: Option<Value>,
+}
+
+impl From<EmbedChange> for EmbedChangeDto {
+    fn from(change: EmbedChange) -> Self {
+        EmbedChangeDto {
+            path: change.path,
+            before: change.before,
+            after: change.after,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ChannelSummary {
+    pub id: String,
+    pub name: String,
+}
+
+impl From<&GuildChannel> for ChannelSummary {
+    fn from(channel: &GuildChannel) -> Self {
+        ChannelSummary {
+            id: channel.id.to_string(),
+            name: channel.name.clone(),
+        }
+    }
+}
+
+/// A guild's name and member counts
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GuildDto {
+    pub id: String,
+    pub name: String,
+    pub icon: Option<String>,
+    pub owner_id: String,
+    pub member_count: u64,
+    /// Unset when Discord didn't say
+    pub online_count: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelKindDto {
+    Text,
+    Announcement,
+    Forum,
+    Voice,
+    Stage,
+    Category,
+    PublicThread,
+    PrivateThread,
+    AnnouncementThread,
+    Other,
+}
+
+impl From<ChannelType> for ChannelKindDto {
+    fn from(kind: ChannelType) -> Self {
+        match kind {
+            ChannelType::Text => ChannelKindDto::Text,
+            ChannelType::News => ChannelKindDto::Announcement,
+            ChannelType::Forum => ChannelKindDto::Forum,
+            ChannelType::Voice => ChannelKindDto::Voice,
+            ChannelType::Stage => ChannelKindDto::Stage,
+            ChannelType::Category => ChannelKindDto::Category,
+            ChannelType::PublicThread => ChannelKindDto::PublicThread,
+            ChannelType::PrivateThread => ChannelKindDto::PrivateThread,
+            ChannelType::NewsThread => ChannelKindDto::AnnouncementThread,
+            _ => ChannelKindDto::Other,
+        }
+    }
+}
+
+/// A channel or thread, with what the bot may do in it
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GuildChannelDto {
+    pub id: String,
+    pub name: String,
+    pub kind: ChannelKindDto,
+    pub position: i64,
+    /// The category, or for threads the channel they're in
+    pub parent_id: Option<String>,
+    pub nsfw: bool,
+    /// Discord's names for the bot's effective permissions, e.g. `Send Messages`
+    pub bot_permissions: Vec<String>,
+    /// The same permissions as a bitfield
+    pub bot_permission_bits: String,
+    /// Active threads, empty for threads themselves
+    pub threads: Vec<GuildChannelDto>,
+}
+
+/// A category and its channels in the order Discord shows them
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CategoryDto {
+    /// Unset for the channels outside any category, which come first
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub position: i64,
+    pub channels: Vec<GuildChannelDto>,
+}
+
+/// A role, highest first in lists
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoleDto {
+    pub id: String,
+    pub name: String,
+    /// `0xRRGGBB` as a number, 0 when the role has no colour
+    pub color: u32,
+    pub position: i64,
+    pub hoist: bool,
+    /// Owned by an integration, it can't be given out
+    pub managed: bool,
+    pub mentionable: bool,
+    /// Bitfield
+    pub permissions: String,
+}
+
+impl From<&Role> for RoleDto {
+    fn from(role: &Role) -> Self {
+        RoleDto {
+            id: role.id.to_string(),
+            name: role.name.clone(),
+            color: role.colour.0,
+            position: role.position,
+            hoist: role.hoist,
+            managed: role.managed,
+            mentionable: role.mentionable,
+            permissions: role.permissions.bits().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EmojiDto {
+    pub id: String,
+    pub name: String,
+    pub animated: bool,
+    /// False when lost with a boost level
+    pub available: bool,
+    /// Roles allowed to use it, everyone when empty
+    pub role_ids: Vec<String>,
+    pub url: String,
+}
+
+impl From<&Emoji> for EmojiDto {
+    fn from(emoji: &Emoji) -> Self {
+        EmojiDto {
+            id: emoji.id.to_string(),
+            name: emoji.name.clone(),
+            animated: emoji.animated,
+            available: emoji.available,
+            role_ids: emoji.roles.iter().map(|r| r.to_string()).collect(),
+            url: emoji.url(),
+        }
+    }
+}
+
+/// A guild the bot is in, as the guild picker shows it
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GuildSummary {
+    pub id: String,
+    pub name: String,
+    pub icon: Option<String>,
+}
+
+/// Who the request is authenticated as
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PrincipalDto {
+    /// API key name or Discord username
+    pub name: String,
+    /// Set for dashboard logins
+    pub user_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RoleSelectorStyleDto {
+    /// A select menu, what the `/createroleselection` wizard makes
+    #[default]
+    Menu,
+    /// A button per role, descriptions aren't shown
+    Buttons,
+}
+
+impl From<RoleSelectorStyleDto> for RoleSelectorStyle {
+    fn from(style: RoleSelectorStyleDto) -> Self {
+        match style {
+            RoleSelectorStyleDto::Menu => RoleSelectorStyle::Menu,
+            RoleSelectorStyleDto::Buttons => RoleSelectorStyle::Buttons,
+        }
+    }
+}
+
+impl From<RoleSelectorStyle> for RoleSelectorStyleDto {
+    fn from(style: RoleSelectorStyle) -> Self {
+        match style {
+            RoleSelectorStyle::Menu => RoleSelectorStyleDto::Menu,
+            RoleSelectorStyle::Buttons => RoleSelectorStyleDto::Buttons,
+        }
+    }
+}
+
+/// A role members can pick, in the order shown
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoleOptionDto {
+    pub role_id: String,
+    /// At most 100 characters
+    pub label: String,
+    /// At most 100 characters, only shown by menus
+    pub description: Option<String>,
+}
+
+impl From<RoleOption> for RoleOptionDto {
+    fn from(option: RoleOption) -> Self {
+        RoleOptionDto {
+            role_id: option.role_id.to_string(),
+            label: option.label,
+            description: option.description,
+        }
+    }
+}
+
+/// Creates a role selector, or replaces everything about one
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoleSelectorRequest {
+    pub content: Option<String>,
+    #[serde(default)]
+    pub embeds: Vec<EmbedDto>,
+    /// 1 to 25 roles
+    pub options: Vec<RoleOptionDto>,
+    /// How many roles a member can hold from this selector, every role when left out
+    pub max_selections: Option<u64>,
+    #[serde(default)]
+    pub style: RoleSelectorStyleDto,
+}
+
+impl TryFrom<RoleSelectorRequest> for RoleSelector {
+    type Error = ApiError;
+
+    fn try_from(request: RoleSelectorRequest) -> Result<Self, ApiError> {
+        let mut errors = Vec::new();
+        let mut options = Vec::new();
+        for (i, option) in request.options.into_iter().enumerate() {
+            match option.role_id.parse::<u64>() {
+                Ok(role_id) => options.push(RoleOption {
+                    role_id: RoleId(role_id),
+                    label: option.label,
+                    description: option.description.filter(|d| !d.trim().is_empty()),
+                }),
+                Err(_) => errors.push(FieldError::new(format!("options[{i}].role_id"), "Not a snowflake")),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ApiError::invalid_fields(errors));
+        }
+
+        let mut embeds = Vec::new();
+        for embed in request.embeds {
+            embeds.push(CreateEmbed::try_from(embed)?);
+        }
+
+        Ok(RoleSelector {
+            content: request.content.filter(|c| !c.trim().is_empty()),
+            embeds,
+            max_selections: request.max_selections.unwrap_or(options.len() as u64),
+            options,
+            style: request.style.into(),
+        })
+    }
+}
+
+/// A role selector message and the roles it offers
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RoleSelectorDto {
+    pub message: MessageDto,
+    pub options: Vec<RoleOptionDto>,
+    pub max_selections: u64,
+    pub style: RoleSelectorStyleDto,
+}
+
+impl RoleSelectorDto {
+    pub fn new(message: Message, options: Vec<RoleOption>, max_selections: u64, style: RoleSelectorStyle) -> RoleSelectorDto {
+        RoleSelectorDto {
+            message: message.into(),
+            options: options.into_iter().map(RoleOptionDto::from).collect(),
+            max_selections,
+            style: style.into(),
+        }
+    }
+}
+
+/// What happened, `type` is also the name of the server-sent event carrying it
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GuildEventKindDto {
+    MemberJoined {
+        user_id: String,
+        name: String,
+    },
+    SelectorRolesChanged {
+        channel_id: String,
+        message_id: String,
+        user_id: String,
+        added: Vec<String>,
+        removed: Vec<String>,
+    },
+    /// `from` is null when joining voice, `to` when leaving it
+    VoiceMoved {
+        user_id: String,
+        from: Option<String>,
+        to: Option<String>,
+    },
+    MemberBanned {
+        user_id: String,
+        name: String,
+    },
+    MemberUnbanned {
+        user_id: String,
+        name: String,
+    },
+    /// `until` (RFC 3339) is null when the timeout was lifted
+    MemberTimedOut {
+        user_id: String,
+        until: Option<String>,
+    },
+    /// `deleted` when the message was removed rather than only logged
+    LinkBlocked {
+        channel_id: String,
+        message_id: String,
+        user_id: String,
+        deleted: bool,
+    },
+    EmbedEdited {
+        channel_id: String,
+        message_id: String,
+    },
+}
+
+/// One event of a guild's event stream
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GuildEventDto {
+    pub guild_id: String,
+    /// RFC 3339, when the bot saw it
+    pub at: String,
+    pub event: GuildEventKindDto,
+}
+
+fn ids<T: ToString>(ids: &[T]) -> Vec<String> {
+    ids.iter().map(|id| id.to_string()).collect()
+}
+
+impl From<&GuildEvent> for GuildEventDto {
+    fn from(event: &GuildEvent) -> Self {
+        let kind = match &event.kind {
+            GuildEventKind::MemberJoined { user_id, name } => GuildEventKindDto::MemberJoined {
+                user_id: user_id.to_string(),
+                name: name.clone(),
+            },
+            GuildEventKind::SelectorRolesChanged {
+                channel_id,
+                message_id,
+                user_id,
+                added,
+                removed,
+            } => GuildEventKindDto::SelectorRolesChanged {
+                channel_id: channel_id.to_string(),
+                message_id: message_id.to_string(),
+                user_id: user_id.to_string(),
+                added: ids(added),
+                removed: ids(removed),
+            },
+            GuildEventKind::VoiceMoved { user_id, from, to } => GuildEventKindDto::VoiceMoved {
+                user_id: user_id.to_string(),
+                from: from.map(|c| c.to_string()),
+                to: to.map(|c| c.to_string()),
+            },
+            GuildEventKind::MemberBanned { user_id, name } => GuildEventKindDto::MemberBanned {
+                user_id: user_id.to_string(),
+                name: name.clone(),
+            },
+            GuildEventKind::MemberUnbanned { user_id, name } => GuildEventKindDto::MemberUnbanned {
+                user_id: user_id.to_string(),
+                name: name.clone(),
+            },
+            GuildEventKind::MemberTimedOut { user_id, until } => GuildEventKindDto::MemberTimedOut {
+                user_id: user_id.to_string(),
+                until: until.map(|t| t.to_string()),
+            },
+            GuildEventKind::LinkBlocked {
+                channel_id,
+                message_id,
+                user_id,
+                deleted,
+            } => GuildEventKindDto::LinkBlocked {
+                channel_id: channel_id.to_string(),
+                message_id: message_id.to_string(),
+                user_id: user_id.to_string(),
+                deleted: *deleted,
+            },
+            GuildEventKind::EmbedEdited { channel_id, message_id } => GuildEventKindDto::EmbedEdited {
+                channel_id: channel_id.to_string(),
+                message_id: message_id.to_string(),
+            },
+        };
+
+        GuildEventDto {
+            guild_id: event.guild_id.to_string(),
+            at: event.at.to_string(),
+            event: kind,
+        }
+    }
+}
+
+/// Sent instead of the events a client was too slow to receive
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EventsSkippedDto {
+    pub skipped: u64,
+}
+
+/// Creates an incoming integration, or changes where and what one posts. Any text in `template`
+/// can hold `{{variable}}`s, filled in from the variables each call sends.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct IntegrationRequest {
+    /// What's calling, e.g. `CI`
+    pub name: String,
+    /// Where messages go, a channel of the guild
+    pub channel_id: String,
+    pub template: MessageDetails,
+}
+
+/// An incoming integration, its token is only ever shown when it's created
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct IntegrationDto {
+    pub id: i64,
+    pub name: String,
+    pub channel_id: String,
+    pub template: MessageDetails,
+    /// API key name or Discord username
+    pub created_by: String,
+    /// RFC 3339
+    pub created_at: String,
+    /// RFC 3339, null until the first message
+    pub last_used_at: Option<String>,
+}
+
+/// A new integration and the token it posts with
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreatedIntegrationDto {
+    /// Sent as `Authorization: Bearer <token>` to `/api/hooks/{integration_id}`
+    pub token: String,
+    pub integration: IntegrationDto,
+}
+
+/// A message from an integration, rendered from its template
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct IncomingMessageRequest {
+    /// Values for the template's `{{variable}}`s, e.g. `{"repo": "zangra", "status": "passed"}`
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
@@ -1,89 +1,229 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, net::SocketAddr, sync::Arc};
 
 use axum::{
-    extract::{Path, State},
+    extract::{DefaultBodyLimit, State},
+    Extension,
     http::StatusCode,
-    routing::{get, post},
+    middleware,
+    routing::{get, post, put},
     Json, Router,
 };
-use serde_json::{json, Value};
 use serenity::{
     builder::CreateEmbed,
-    client::Context,
-    model::{
-        channel::{Embed, Message},
-        id::ChannelId,
-        ModelError,
-    },
-    Error,
+    futures::{stream, StreamExt},
+    cache::Cache,
+    client::bridge::gateway::ShardManager,
+    prelude::Mutex,
+    http::{CacheHttp, Http},
+    model::id::{ChannelId, GuildId, MessageId},
 };
 use sqlx::{query, Pool, Sqlite};
 use tracing::{error, info};
 
-use crate::{utils::database::DatabasePool, rest_api::routes::{guilds::{get_guild_channels, post_filter_guilds}, channels::post_channel_embed, embed_edit::post_edit_embed}};
+use crate::{config::{ApiSettings, ConfigurationData, ConfiguredApiKey, OAuthConfig}, utils::{guild_events::GuildEvents, message_store::MessageStore, metrics::Metrics, shutdown::Shutdown, embeds::{delete_embed_message, edit_embed_message, forget_embed, is_unknown, post_embed_message, RevisionAuthor}}, rest_api::{auth::{authenticate, authorize_guild, ApiPrincipal}, layers::{access_log, cors_layer, rate_limit, rate_limit_address, request_timeout, RateLimiter}, dto::{ChannelMessages, EmbedDto, EmbedFetchErrorDto, EmbedListResponse, EmbedPageQuery, MessageDto}, error::ApiError, extract::{ApiJson, ApiPath, ApiQuery}, oauth::{callback, login, logout, me}, openapi::get_openapi, routes::{embed::{get_embed_diff, get_embed_revisions, post_embed_rollback}, events::get_guild_events, guilds::{get_guild, get_guild_categories, get_guild_channels, get_guild_emojis, get_guild_roles, post_filter_guilds}, health::{get_healthz, get_metrics, get_readyz}, integrations::{delete_integration, get_integrations, post_incoming_message, post_integration, put_integration}, role_selector::{delete_role_selector, get_role_selector, post_role_selector, put_role_selector}, channels::post_channel_embed, embed_edit::post_edit_embed}}};
 
+/// The gateway's cache and HTTP client. Handlers get this rather than a `Context` so the router
+/// can be built without a gateway connection.
 #[derive(Clone)]
-pub struct AppState {
-    pub ctx: Arc<Context>,
-    pub db_pool: Pool<Sqlite>,
+pub struct Discord {
+    pub cache: Arc<Cache>,
+    pub http: Arc<Http>,
 }
 
-pub async fn start_rest_api(ctx: &Context) -> Result<(), String> {
-    let data = ctx.data.read().await;
-    let db_pool = data.get::<DatabasePool>().unwrap().clone();
+impl AsRef<Http> for Discord {
+    fn as_ref(&self) -> &Http {
+        &self.http
+    }
+}
 
-    let ctx = ctx.clone();
+impl CacheHttp for Discord {
+    fn http(&self) -> &Http {
+        &self.http
+    }
 
-    tokio::spawn(async move {
-        let ctx = Arc::new(ctx);
-        let app_state: AppState = AppState { ctx, db_pool };
+    fn cache(&self) -> Option<&Arc<Cache>> {
+        Some(&self.cache)
+    }
+}
 
-        let app = Router::new()
-            .route("/", get(root))
-            .route("/api/channels/:channel_id/embed", post(post_channel_embed))
+#[derive(Clone)]
+pub struct AppState {
+    pub discord: Discord,
+    pub db_pool: Pool<Sqlite>,
+    pub configured_keys: Arc<Vec<ConfiguredApiKey>>,
+    pub oauth: Option<Arc<OAuthConfig>>,
+    /// For talking to Discord as the logged in user, separate from the bot's own client
+    pub http_client: reqwest::Client,
+    pub message_store: MessageStore,
+    pub events: GuildEvents,
+    pub settings: Arc<ApiSettings>,
+    pub rate_limiter: RateLimiter,
+    /// Per client address, in front of authentication
+    pub address_rate_limiter: RateLimiter,
+    /// Event streams end when it's triggered, otherwise the server could never finish draining
+    pub shutdown: Shutdown,
+    pub metrics: Metrics,
+    /// For the shards' status, `None` without a gateway connection
+    pub shard_manager: Option<Arc<Mutex<ShardManager>>>,
+}
 
-            .route("/api/guilds/filter", post(post_filter_guilds))
-            .route("/api/guilds/:guild_id/channels", get(get_guild_channels))
+const LISTEN_ADDRESS: &str = "0.0.0.0:4000";
+
+impl AppState {
+    /// The state shared with the bot's gateway side, keys and limits come from config.toml
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        discord: Discord,
+        db_pool: Pool<Sqlite>,
+        message_store: MessageStore,
+        events: GuildEvents,
+        shutdown: Shutdown,
+        metrics: Metrics,
+        shard_manager: Arc<Mutex<ShardManager>>,
+        configuration: &ConfigurationData,
+    ) -> AppState {
+        let settings = configuration.api.clone();
+
+        AppState {
+            discord,
+            db_pool,
+            configured_keys: Arc::new(configuration.api_keys.clone()),
+            oauth: configuration.oauth.clone().map(Arc::new),
+            http_client: reqwest::Client::new(),
+            message_store,
+            events,
+            rate_limiter: RateLimiter::new(settings.rate_limit_burst, settings.rate_limit_per_minute),
+            address_rate_limiter: RateLimiter::new(settings.address_rate_limit_burst, settings.address_rate_limit_per_minute),
+            settings: Arc::new(settings),
+            shutdown,
+            metrics,
+            shard_manager: Some(shard_manager),
+        }
+    }
+}
 
-            .route("/api/embededit/:channel_id/:message_id", post(post_edit_embed))
+/// Serves the API until the shutdown is triggered, then lets the requests in flight finish.
+/// Started once from main, `ready` fires again on every reconnect.
+pub async fn serve_rest_api(app_state: AppState) {
+    let shutdown = app_state.shutdown.clone();
 
-            .route("/api/embed/:guild_id/:channel_id", post(post_embed))
-            .route(
-                "/api/embed/:guild_id/:channel_id/:message_id",
-                get(get_embed).put(put_embed).delete(delete_embed),
-            )
-            .route("/api/embed/all/:guild_id", get(get_embed_all_guild))
-            .route(
-                "/api/embed/all/:guild_id/:channel_id",
-                get(get_embed_all_channel),
-            )
-            
-            .with_state(app_state);
+    let server = match axum::Server::try_bind(&LISTEN_ADDRESS.parse().unwrap()) {
+        Ok(server) => server,
+        Err(why) => {
+            error!("Unable to start rest API on {}: {}", LISTEN_ADDRESS, why);
+            return;
+        }
+    };
 
-        info!("Starting rest API");
+    info!("Starting rest API");
 
-        axum::Server::bind(&"0.0.0.0:4000".parse().unwrap())
-            .serve(app.into_make_service())
-            .await
-            .unwrap();
-    });
+    if let Err(why) = server
+        .serve(router(app_state).into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown.wait())
+        .await
+    {
+        error!("Rest API failed: {}", why);
+    }
 
-    Ok(())
+    info!("Rest API stopped");
 }
 
+/// Every route, with authentication in front of everything but login and the OpenAPI document
+pub fn router(app_state: AppState) -> Router {
+    let api = Router::new()
+        .route("/api/auth/me", get(me))
+        .route("/api/channels/:channel_id/embed", post(post_channel_embed))
+
+        .route("/api/guilds/filter", post(post_filter_guilds))
+        .route("/api/guilds/:guild_id", get(get_guild))
+        .route("/api/guilds/:guild_id/channels", get(get_guild_channels))
+        .route("/api/guilds/:guild_id/categories", get(get_guild_categories))
+        .route("/api/guilds/:guild_id/roles", get(get_guild_roles))
+        .route("/api/guilds/:guild_id/emojis", get(get_guild_emojis))
+        .route("/api/guilds/:guild_id/events", get(get_guild_events))
+        .route("/api/guilds/:guild_id/integrations", get(get_integrations).post(post_integration))
+        .route(
+            "/api/guilds/:guild_id/integrations/:integration_id",
+            put(put_integration).delete(delete_integration),
+        )
+
+        .route("/api/embededit/:channel_id/:message_id", post(post_edit_embed))
+
+        .route("/api/embed/:guild_id/:channel_id", post(post_embed))
+        .route(
+            "/api/embed/:guild_id/:channel_id/:message_id",
+            get(get_embed).put(put_embed).delete(delete_embed),
+        )
+        .route("/api/embed/:guild_id/:channel_id/:message_id/revisions", get(get_embed_revisions))
+        .route("/api/embed/:guild_id/:channel_id/:message_id/revisions/diff", get(get_embed_diff))
+        .route(
+            "/api/embed/:guild_id/:channel_id/:message_id/revisions/:revision/rollback",
+            post(post_embed_rollback),
+        )
+        .route("/api/embed/all/:guild_id", get(get_embed_all_guild))
+        .route(
+            "/api/embed/all/:guild_id/:channel_id",
+            get(get_embed_all_channel),
+        )
+
+        .route("/api/roleselector/:guild_id/:channel_id", post(post_role_selector))
+        .route(
+            "/api/roleselector/:guild_id/:channel_id/:message_id",
+            get(get_role_selector).put(put_role_selector).delete(delete_role_selector),
+        )
+        //route layers run bottom up, the key has to be checked before its rate and scope
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), authorize_guild))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), rate_limit))
+        .route_layer(middleware::from_fn_with_state(app_state.clone(), authenticate));
+
+    let router = Router::new()
+        .route("/", get(root))
+        .route("/api/openapi.json", get(get_openapi))
+        .route("/api/auth/login", get(login))
+        .route("/api/auth/callback", get(callback))
+        .route("/api/auth/logout", post(logout))
+        //integrations bring their own token, checked by the handler
+        .route("/api/hooks/:integration_id", post(post_incoming_message))
+        .merge(api)
+        //bottom up again, the access log sees every response including timeouts. Addresses are
+        //limited before anything is authenticated so failed attempts and logins count.
+        .layer(middleware::from_fn_with_state(app_state.clone(), rate_limit_address))
+        //added after the address limit, probes and scrapes come from the same few addresses
+        //all day and must not be turned away
+        .route("/healthz", get(get_healthz))
+        .route("/readyz", get(get_readyz))
+        .route("/metrics", get(get_metrics))
+        .layer(DefaultBodyLimit::max(app_state.settings.max_body_bytes))
+        .layer(middleware::from_fn_with_state(app_state.clone(), request_timeout))
+        .layer(middleware::from_fn(access_log));
+
+    //outermost so preflight requests are answered without credentials
+    let router = match cors_layer(&app_state.settings) {
+        Some(cors) => router.layer(cors),
+        None => router,
+    };
+
+    router.with_state(app_state)
+}
+
+//Discord's ratelimiter queues whatever goes over, fewer in flight leaves room for other requests
+const FETCH_CONCURRENCY: usize = 5;
+const DEFAULT_PAGE: u32 = 50;
+const MAX_PAGE: u32 = 100;
+
 async fn root() {}
 
-/// POST
-///
-/// Body contains name of action to perform and data need to perform that action within JSON
-///
-/// {
-///     action:
-///     data:{
-///     channel:
-///     embeddata:    
-/// }
-/// }
+// POST
+//
+// Body contains name of action to perform and data need to perform that action within JSON
+//
+// {
+//     action:
+//     data:{
+//     channel:
+//     embeddata:    
+// }
+// }
 // async fn post_api(State(state): State<AppState>, body: String) -> impl IntoResponse {
 //     let v: Value = match serde_json::from_str(&body) {
 //         Ok(val) => val,
@@ -117,310 +257,308 @@ async fn root() {}
 // }
 
 /// Get a single embed
-async fn get_embed(
+#[utoipa::path(
+    get,
+    path = "/api/embed/{guild_id}/{channel_id}/{message_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+        ("message_id" = String, Path, description = "Message snowflake"),
+    ),
+    responses(
+        (status = 200, description = "The message", body = MessageDto),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+        (status = 404, description = "No such message", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_embed(
     State(state): State<AppState>,
-    Path((guild_id, channel_id, message_id)): Path<(u64, u64, u64)>,
-) -> Result<Json<Value>, StatusCode> {
+    ApiPath((guild_id, channel_id, message_id)): ApiPath<(u64, u64, u64)>,
+) -> Result<Json<MessageDto>, ApiError> {
     let _guild_id = guild_id;
 
-    match ChannelId(channel_id)
-        .message(state.ctx.clone(), message_id)
-        .await
-    {
-        Ok(msg) => {
-            Ok(Json(json!(msg)))
-        }
-        Err(why) => {
-            error!("{}", why);
-            Ok(Json(json!({
-                "status": "error",
-                "error": "NoMessage",
-            })))
-        }
-    }
+    let message = ChannelId(channel_id)
+        .message(&state.discord, message_id)
+        .await?;
+
+    Ok(Json(message.into()))
 }
 
 /// create an embed
-async fn post_embed(
+#[utoipa::path(
+    post,
+    path = "/api/embed/{guild_id}/{channel_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+    ),
+    request_body = EmbedDto,
+    responses(
+        (status = 201, description = "Embed posted and saved", body = MessageDto),
+        (status = 400, description = "Malformed body", body = ApiErrorBody),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild, or the bot can't post there", body = ApiErrorBody),
+        (status = 422, description = "Discord rejected the embed", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn post_embed(
     State(state): State<AppState>,
-    Path((guild_id, channel_id)): Path<(u64, u64)>,
-    Json(embed): Json<Embed>,
-) -> Result<Json<Value>, StatusCode> {
-    let message = match ChannelId(channel_id)
-        .send_message(state.ctx.clone(), |m| {
-            m.set_embed(CreateEmbed::from(embed));
-            m
-        })
-        .await
-    {
-        Ok(msg) => msg,
-        Err(Error::Model(ModelError::MessageTooLong(mtl))) => {
-            return Ok(Json(json!(
-                {
-                    "status": "error",
-                    "error": "MessageTooLong",
-                    "mtl": mtl
-                }
-            )))
-        }
-        Err(Error::Http(http_err)) => {
-            error!("{}", http_err);
-            //missing permissions
-            return Ok(Json(json!(
-                {
-                    "status": "error",
-                    "error": "MissingPermissions"
-                }
-            )));
-        }
-        Err(why) => {
-            //anything else
-            //maybe channel doesn't exist or guild doesn't exist
-            error!("{}", why);
-            return Ok(Json(json!(
-                {
-                    "status": "error",
-                    "error": "Unknown"
-                }
-            )));
-        }
-    };
-
-    //add to db
-    let message_id_i64 = message.id.0 as i64;
-    let guild_id_i64 = guild_id as i64;
-    let channel_id_i64 = channel_id as i64;
-    match query!(
-        "INSERT INTO Embed (EmbedId, GuildId, ChannelId) VALUES (?, ?, ?)",
-        message_id_i64,
-        guild_id_i64,
-        channel_id_i64
+    Extension(principal): Extension<ApiPrincipal>,
+    ApiPath((guild_id, channel_id)): ApiPath<(u64, u64)>,
+    ApiJson(embed): ApiJson<EmbedDto>,
+) -> Result<(StatusCode, Json<MessageDto>), ApiError> {
+    let embed = CreateEmbed::try_from(embed)?;
+    let message = post_embed_message(
+        &state.discord,
+        &state.db_pool,
+        GuildId(guild_id),
+        ChannelId(channel_id),
+        None,
+        vec![embed],
+        &RevisionAuthor::from(&principal),
     )
-    .execute(&state.db_pool.clone())
-    .await
-    {
-        Ok(_result) => {
-            return Ok(Json(json!(message)));
-        }
-        Err(why) => {
-            error!("{}", why);
-            match message.delete(state.ctx.clone()).await {
-                Ok(()) => {}
-                Err(why) => {
-                    error!("{}", why);
-                }
-            };
-        }
-    };
+    .await?;
 
-    Err(StatusCode::BAD_GATEWAY)
+    Ok((StatusCode::CREATED, Json(message.into())))
 }
 
 /// delete an embed
-async fn delete_embed(
+#[utoipa::path(
+    delete,
+    path = "/api/embed/{guild_id}/{channel_id}/{message_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+        ("message_id" = String, Path, description = "Message snowflake"),
+    ),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+        (status = 404, description = "No such message", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn delete_embed(
     State(state): State<AppState>,
-    Path((guild_id, channel_id, message_id)): Path<(u64, u64, u64)>,
-) -> Result<Json<Value>, StatusCode> {
+    ApiPath((guild_id, channel_id, message_id)): ApiPath<(u64, u64, u64)>,
+) -> Result<StatusCode, ApiError> {
     let _guild_id = guild_id;
 
-    match ChannelId(channel_id)
-        .message(state.ctx.clone(), message_id)
-        .await
-    {
-        Ok(msg) => match msg.delete(state.ctx.clone()).await {
-            Ok(()) => {}
-            Err(why) => {
-                error!("{}", why);
-            }
-        },
-        Err(why) => {
-            //can't get message
-            //maybe message doesn't exist or channel doesn't exist
-            error!("{}", why);
-        }
-    }
+    delete_embed_message(&state.discord, &state.db_pool, ChannelId(channel_id), MessageId(message_id)).await?;
 
-    Err(StatusCode::BAD_GATEWAY)
+    Ok(StatusCode::NO_CONTENT)
 }
 
 /// edit an embed
-/// returns the message that was created if successful
-async fn put_embed(
+/// returns the edited message if successful
+#[utoipa::path(
+    put,
+    path = "/api/embed/{guild_id}/{channel_id}/{message_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+        ("message_id" = String, Path, description = "Message snowflake"),
+    ),
+    request_body = EmbedDto,
+    responses(
+        (status = 200, description = "The edited message", body = MessageDto),
+        (status = 400, description = "Malformed body", body = ApiErrorBody),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild, or not the bot's message", body = ApiErrorBody),
+        (status = 404, description = "No such message", body = ApiErrorBody),
+        (status = 422, description = "Discord rejected the embed", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn put_embed(
     State(state): State<AppState>,
-    Path((guild_id, channel_id, message_id)): Path<(u64, u64, u64)>,
-    Json(embed): Json<Embed>,
-) -> Result<Json<Value>, StatusCode> {
-    let _guild_id = guild_id;
+    Extension(principal): Extension<ApiPrincipal>,
+    ApiPath((guild_id, channel_id, message_id)): ApiPath<(u64, u64, u64)>,
+    ApiJson(embed): ApiJson<EmbedDto>,
+) -> Result<Json<MessageDto>, ApiError> {
+    let embed = CreateEmbed::try_from(embed)?;
+
+    let message = edit_embed_message(
+        &state.discord,
+        &state.db_pool,
+        GuildId(guild_id),
+        ChannelId(channel_id),
+        MessageId(message_id),
+        None,
+        vec![embed],
+        &RevisionAuthor::from(&principal),
+    )
+    .await?;
 
-    let mut message = match ChannelId(channel_id)
-        .message(state.ctx.clone(), message_id)
-        .await
-    {
-        Ok(msg) => msg,
-        Err(why) => {
-            //can't get message
-            //maybe message doesn't exist or channel doesn't exist
-            error!("{}", why);
-            return Err(StatusCode::BAD_GATEWAY);
-        }
-    };
+    Ok(Json(message.into()))
+}
 
-    match message
-        .edit(state.ctx.clone(), |m| {
-            m.set_embed(CreateEmbed::from(embed));
-            m
+/// Fetches a page of saved embed messages grouped by channel, a few at a time. Messages deleted in
+/// Discord lose their row, any other failure is reported for that message alone.
+async fn fetch_embed_messages(
+    state: &AppState,
+    embed_ids: Vec<(ChannelId, MessageId)>,
+) -> Result<(HashMap<String, ChannelMessages>, Vec<EmbedFetchErrorDto>), ApiError> {
+    let results: Vec<_> = stream::iter(embed_ids)
+        .map(|(channel_id, message_id)| async move {
+            (channel_id, message_id, state.message_store.message(&state.discord, channel_id, message_id).await)
         })
-        .await
-    {
-        Ok(()) => {}
-        Err(Error::Model(ModelError::MessageTooLong(mtl))) => {
-            error!("MessageTooLong: {}", mtl);
-            return Ok(Json(json!(
-                {
-                    "status": "error",
-                    "error": "MessageTooLong",
-                    "mtl": mtl
-                }
-            )));
-        }
-        Err(Error::Model(ModelError::InvalidUser)) => {
-            return Ok(Json(json!(
-                {
-                    "status": "error",
-                    "error": "InvalidUser",
-                }
-            )))
-        }
-        Err(why) => {
-            error!("{}", why);
-        }
+        .buffered(FETCH_CONCURRENCY)
+        .collect()
+        .await;
+
+    let mut message_map: HashMap<String, ChannelMessages> = HashMap::new();
+    let mut errors = Vec::new();
+
+    for (channel_id, message_id, result) in results {
+        match result {
+            Ok(msg) => message_map.entry(channel_id.to_string()).or_default().0.push(msg.into()),
+            Err(why) if is_unknown(&why) => {
+                state.message_store.remove(message_id);
+                forget_embed(&state.db_pool, message_id).await?;
+            }
+            Err(why) => {
+                let error = ApiError::from(why);
+                errors.push(EmbedFetchErrorDto {
+                    channel_id: channel_id.to_string(),
+                    message_id: message_id.to_string(),
+                    error: error.code,
+                    message: error.message,
+                });
+            }
+        };
     }
 
-    Err(StatusCode::BAD_GATEWAY)
+    Ok((message_map, errors))
+}
+
+/// Clamps the page size, and the row to continue after
+fn page_bounds(page: &EmbedPageQuery) -> (i64, i64) {
+    let limit = page.limit.unwrap_or(DEFAULT_PAGE).clamp(1, MAX_PAGE);
+    (limit as i64, page.after.unwrap_or(0) as i64)
+}
+
+/// Drops the extra row fetched to see if there's another page, and gives the cursor to it
+fn next_page(rows: &mut Vec<(ChannelId, MessageId)>, limit: i64) -> Option<String> {
+    if rows.len() as i64 <= limit {
+        return None;
+    }
+
+    rows.truncate(limit as usize);
+    rows.last().map(|(_, message_id)| message_id.to_string())
 }
 
 /// get all embeds in the entire server
-async fn get_embed_all_guild(
+#[utoipa::path(
+    get,
+    path = "/api/embed/all/{guild_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("limit" = Option<u32>, Query, description = "Saved embeds per page, 50 by default and at most 100"),
+        ("after" = Option<String>, Query, description = "The `next` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "A page of saved embeds by channel, with the ones that couldn't be fetched in `errors`", body = EmbedListResponse),
+        (status = 400, description = "Malformed page parameters", body = ApiErrorBody),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_embed_all_guild(
     State(state): State<AppState>,
-    Path(guild_id): Path<u64>,
-) -> Result<Json<Value>, StatusCode> {
+    ApiPath(guild_id): ApiPath<u64>,
+    ApiQuery(page): ApiQuery<EmbedPageQuery>,
+) -> Result<Json<EmbedListResponse>, ApiError> {
     let guild_id_i64 = guild_id as i64;
-    let db_results = match query!(
-        "SELECT EmbedId, GuildId, ChannelId FROM Embed 
-        WHERE GuildId = ? 
-        ORDER BY ChannelId",
-        guild_id_i64
+    let (limit, after) = page_bounds(&page);
+    let fetch_limit = limit + 1;
+    let page_query = query!(
+        "SELECT EmbedId, ChannelId FROM Embed
+        WHERE GuildId = ? AND EmbedId > ?
+        ORDER BY EmbedId
+        LIMIT ?",
+        guild_id_i64,
+        after,
+        fetch_limit
     )
-    .fetch_all(&state.db_pool.clone())
-    .await
-    {
-        Ok(rows) => rows,
-        Err(why) => {
-            error!("Unable to read from DB: {}", why);
-            return Err(StatusCode::BAD_GATEWAY);
-        }
-    };
-
-    let mut message_map: HashMap<ChannelId, Vec<Message>> = HashMap::new();
-
-    for row in db_results {
-        //group by channel
-        let channel_id = ChannelId(row.ChannelId as u64);
-        match channel_id
-            .message(state.ctx.clone(), row.EmbedId as u64)
-            .await
-        {
-            Ok(msg) => match message_map.get_mut(&channel_id) {
-                Some(vec) => vec.push(msg),
-                None => {
-                    message_map.insert(channel_id, Vec::new());
-                    message_map.get_mut(&channel_id).unwrap().push(msg);
-                }
-            },
-            Err(Error::Http(http_err)) => {
-                //missing read history permissions
-                error!("{}", http_err);
-                return Ok(Json(json!(
-                    {
-                        "status": "error",
-                        "error": "MissingPermissions"
-                    }
-                )));
-            }
-            Err(why) => {
-                //unable to retrieve message for some reason
-                error!("{}", why);
-            }
-        };
-    }
-
-    Ok(Json(json!(
-        {
-            "status": "success",
-            "messages": message_map
-        }
-    )))
+    .fetch_all(&state.db_pool);
+    let mut rows: Vec<(ChannelId, MessageId)> = state
+        .metrics
+        .time_query("embed_page", page_query)
+        .await?
+        .into_iter()
+        .map(|row| (ChannelId(row.ChannelId as u64), MessageId(row.EmbedId as u64)))
+        .collect();
+
+    let next = next_page(&mut rows, limit);
+    let (messages, errors) = fetch_embed_messages(&state, rows).await?;
+
+    Ok(Json(EmbedListResponse {
+        status: "success".to_string(),
+        messages,
+        errors,
+        next,
+    }))
 }
 
 /// get all embds in a single channel
-async fn get_embed_all_channel(
+#[utoipa::path(
+    get,
+    path = "/api/embed/all/{guild_id}/{channel_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+        ("limit" = Option<u32>, Query, description = "Saved embeds per page, 50 by default and at most 100"),
+        ("after" = Option<String>, Query, description = "The `next` of the previous page"),
+    ),
+    responses(
+        (status = 200, description = "A page of saved embeds in the channel, with the ones that couldn't be fetched in `errors`", body = EmbedListResponse),
+        (status = 400, description = "Malformed page parameters", body = ApiErrorBody),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_embed_all_channel(
     State(state): State<AppState>,
-    Path((guild_id, channel_id)): Path<(u64, u64)>,
-) -> Result<Json<Value>, StatusCode> {
+    ApiPath((guild_id, channel_id)): ApiPath<(u64, u64)>,
+    ApiQuery(page): ApiQuery<EmbedPageQuery>,
+) -> Result<Json<EmbedListResponse>, ApiError> {
     let _guild_id = guild_id;
 
     let channel_id_i64 = channel_id as i64;
-    let db_results = match query!(
-        "SELECT EmbedId, GuildId, ChannelId FROM Embed 
-        WHERE ChannelId = ?",
-        channel_id_i64
+    let (limit, after) = page_bounds(&page);
+    let fetch_limit = limit + 1;
+    let page_query = query!(
+        "SELECT EmbedId, ChannelId FROM Embed
+        WHERE ChannelId = ? AND EmbedId > ?
+        ORDER BY EmbedId
+        LIMIT ?",
+        channel_id_i64,
+        after,
+        fetch_limit
     )
-    .fetch_all(&state.db_pool.clone())
-    .await
-    {
-        Ok(rows) => rows,
-        Err(why) => {
-            error!("Unable to read from DB: {}", why);
-            return Err(StatusCode::BAD_GATEWAY);
-        }
-    };
-
-    let mut message_map: HashMap<ChannelId, Vec<Message>> = HashMap::new();
-
-    for row in db_results {
-        let channel_id = ChannelId(row.ChannelId as u64);
-        match channel_id
-            .message(state.ctx.clone(), row.EmbedId as u64)
-            .await
-        {
-            Ok(msg) => match message_map.get_mut(&channel_id) {
-                Some(vec) => vec.push(msg),
-                None => {
-                    message_map.insert(channel_id, Vec::new());
-                    message_map.get_mut(&channel_id).unwrap().push(msg);
-                }
-            },
-            Err(Error::Http(http_err)) => {
-                //missing read history permissions
-                error!("{}", http_err);
-                return Ok(Json(json!(
-                    {
-                        "status": "error",
-                        "error": "MissingPermissions"
-                    }
-                )));
-            }
-            Err(why) => {
-                //unable to retrieve message for some reason
-                error!("{}", why);
-            }
-        };
-    }
-
-    Ok(Json(json!(
-        {
-            "status": "success",
-            "messages": message_map
-        }
-    )))
+    .fetch_all(&state.db_pool);
+    let mut rows: Vec<(ChannelId, MessageId)> = state
+        .metrics
+        .time_query("embed_page", page_query)
+        .await?
+        .into_iter()
+        .map(|row| (ChannelId(row.ChannelId as u64), MessageId(row.EmbedId as u64)))
+        .collect();
+
+    let next = next_page(&mut rows, limit);
+    let (messages, errors) = fetch_embed_messages(&state, rows).await?;
+
+    Ok(Json(EmbedListResponse {
+        status: "success".to_string(),
+        messages,
+        errors,
+        next,
+    }))
 }
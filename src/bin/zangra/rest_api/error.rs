@@ -0,0 +1,298 @@
+use std::fmt::{self, Display, Formatter};
+
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use serenity::{http::HttpError, model::ModelError, Error as SerenityError};
+use sqlx::Error as SqlxError;
+use tracing::{error, warn};
+use utoipa::ToSchema;
+
+use crate::utils::{embeds::EmbedError, role_selectors::RoleSelectorError};
+
+/// Machine readable reason for a failed request, serialized as the `error` field. Clients match
+/// on these so existing names must never change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum ApiErrorCode {
+    /// Malformed path, query or body
+    InvalidRequest,
+    /// Well formed but the values aren't acceptable, `details` says which
+    ValidationFailed,
+    MessageTooLong,
+    EmbedTooLarge,
+    Unauthorized,
+    Forbidden,
+    /// The bot lacks a permission it needs in Discord
+    MissingPermissions,
+    /// Only messages the bot sent can be edited
+    NotBotMessage,
+    NotFound,
+    Conflict,
+    RateLimited,
+    OAuthDisabled,
+    /// Discord failed or couldn't be reached
+    DiscordUnavailable,
+    DatabaseError,
+    Internal,
+    /// The body is over the configured size limit
+    PayloadTooLarge,
+    /// Gave up waiting, usually on Discord
+    Timeout,
+}
+
+impl ApiErrorCode {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiErrorCode::InvalidRequest => StatusCode::BAD_REQUEST,
+            ApiErrorCode::ValidationFailed | ApiErrorCode::MessageTooLong | ApiErrorCode::EmbedTooLarge => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            ApiErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiErrorCode::Forbidden | ApiErrorCode::MissingPermissions | ApiErrorCode::NotBotMessage => {
+                StatusCode::FORBIDDEN
+            }
+            ApiErrorCode::NotFound | ApiErrorCode::OAuthDisabled => StatusCode::NOT_FOUND,
+            ApiErrorCode::Conflict => StatusCode::CONFLICT,
+            ApiErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            ApiErrorCode::DiscordUnavailable => StatusCode::BAD_GATEWAY,
+            ApiErrorCode::DatabaseError | ApiErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiErrorCode::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+}
+
+/// Body of every error response
+#[derive(Serialize, ToSchema)]
+pub struct ApiErrorBody {
+    /// Always `error`
+    pub status: String,
+    pub error: ApiErrorCode,
+    pub message: String,
+    /// Extra data for some codes, e.g. Discord's own error for `DiscordUnavailable`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<Value>,
+}
+
+/// One invalid value of a request body, `ValidationFailed` errors list them in `details.errors`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    /// e.g. `embeds[1].embed_fields[3].value`
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new<F: Into<String>, M: Into<String>>(field: F, message: M) -> FieldError {
+        FieldError {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Every REST failure, rendered as an [`ApiErrorBody`]
+#[derive(Debug)]
+pub struct ApiError {
+    pub code: ApiErrorCode,
+    pub message: String,
+    pub details: Option<Value>,
+}
+
+impl ApiError {
+    pub fn new<S: Into<String>>(code: ApiErrorCode, message: S) -> ApiError {
+        ApiError {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> ApiError {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn unauthorized() -> ApiError {
+        ApiError::new(ApiErrorCode::Unauthorized, "A valid API key or dashboard session is required")
+    }
+
+    pub fn forbidden() -> ApiError {
+        ApiError::new(ApiErrorCode::Forbidden, "Not allowed to manage this guild")
+    }
+
+    /// `Retry-After` is set from `retry_after`, in seconds
+    pub fn rate_limited(retry_after: u64) -> ApiError {
+        ApiError::new(ApiErrorCode::RateLimited, "Too many requests, slow down").with_details(json!({ "retry_after": retry_after }))
+    }
+
+    pub fn not_found<S: Into<String>>(message: S) -> ApiError {
+        ApiError::new(ApiErrorCode::NotFound, message)
+    }
+
+    pub fn validation<S: Into<String>>(message: S) -> ApiError {
+        ApiError::new(ApiErrorCode::ValidationFailed, message)
+    }
+
+    pub fn invalid_fields(errors: Vec<FieldError>) -> ApiError {
+        let message = match errors.as_slice() {
+            [only] => format!("{}: {}", only.field, only.message),
+            _ => format!("{} fields are invalid", errors.len()),
+        };
+
+        ApiError::validation(message).with_details(json!({ "errors": errors }))
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.code, self.message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.code.status();
+        if status.is_server_error() {
+            error!("{}", self);
+        }
+
+        let retry_after = self.details.as_ref().and_then(|d| d["retry_after"].as_u64());
+
+        let body = ApiErrorBody {
+            status: "error".to_string(),
+            error: self.code,
+            message: self.message,
+            details: self.details,
+        };
+
+        let mut response = (status, Json(body)).into_response();
+        if self.code == ApiErrorCode::Unauthorized {
+            response
+                .headers_mut()
+                .insert(axum::http::header::WWW_AUTHENTICATE, "Bearer".parse().unwrap());
+        }
+        if let (ApiErrorCode::RateLimited, Some(retry_after)) = (self.code, retry_after) {
+            response.headers_mut().insert(axum::http::header::RETRY_AFTER, retry_after.into());
+        }
+
+        response
+    }
+}
+
+impl From<SerenityError> for ApiError {
+    fn from(e: SerenityError) -> Self {
+        match e {
+            SerenityError::Model(ModelError::MessageTooLong(overflow)) => {
+                ApiError::new(ApiErrorCode::MessageTooLong, "Message content is too long")
+                    .with_details(json!({ "overflow": overflow }))
+            }
+            SerenityError::Model(ModelError::EmbedTooLarge(overflow)) => {
+                ApiError::new(ApiErrorCode::EmbedTooLarge, "Embed text is too long")
+                    .with_details(json!({ "overflow": overflow }))
+            }
+            SerenityError::Model(ModelError::InvalidPermissions(missing)) => {
+                ApiError::new(ApiErrorCode::MissingPermissions, "The bot is missing permissions")
+                    .with_details(json!({ "missing": missing }))
+            }
+            SerenityError::Model(ModelError::InvalidUser) => {
+                ApiError::new(ApiErrorCode::NotBotMessage, "The message wasn't sent by the bot")
+            }
+            SerenityError::Http(http_error) => match *http_error {
+                HttpError::UnsuccessfulRequest(response) => {
+                    let details = json!({
+                        "discord_code": response.error.code,
+                        "discord_message": response.error.message,
+                    });
+                    let code = match response.status_code.as_u16() {
+                        400 => ApiErrorCode::ValidationFailed,
+                        //the bot's own token was refused, nothing the caller did or can fix
+                        401 => {
+                            error!("Discord rejected the bot's token: {}", response.error.message);
+                            return ApiError::new(ApiErrorCode::Internal, "The bot couldn't authenticate with Discord");
+                        }
+                        403 => ApiErrorCode::MissingPermissions,
+                        404 => ApiErrorCode::NotFound,
+                        429 => ApiErrorCode::RateLimited,
+                        _ => ApiErrorCode::DiscordUnavailable,
+                    };
+                    if code == ApiErrorCode::DiscordUnavailable {
+                        warn!("Discord answered {}: {}", response.status_code, response.error.message);
+                    }
+
+                    ApiError::new(code, "Discord rejected the request").with_details(details)
+                }
+                other => ApiError::new(ApiErrorCode::DiscordUnavailable, other.to_string()),
+            },
+            other => ApiError::new(ApiErrorCode::Internal, other.to_string()),
+        }
+    }
+}
+
+impl From<SqlxError> for ApiError {
+    fn from(e: SqlxError) -> Self {
+        match e {
+            SqlxError::RowNotFound => ApiError::not_found("No such record"),
+            SqlxError::Database(db_error) if db_error.message().contains("UNIQUE constraint failed") => {
+                ApiError::new(ApiErrorCode::Conflict, "That record already exists")
+            }
+            //the text can hold SQL and schema names, it's only logged
+            other => {
+                error!("Database error: {}", other);
+                ApiError::new(ApiErrorCode::DatabaseError, "Database error")
+            }
+        }
+    }
+}
+
+impl From<EmbedError> for ApiError {
+    fn from(e: EmbedError) -> Self {
+        match e {
+            EmbedError::Discord(why) => why.into(),
+            EmbedError::Database(why) => why.into(),
+            EmbedError::UnknownRevision => ApiError::not_found("No such revision of a saved embed"),
+            EmbedError::InvalidJson(why) => ApiError::new(ApiErrorCode::Internal, why.to_string()),
+        }
+    }
+}
+
+impl From<RoleSelectorError> for ApiError {
+    fn from(e: RoleSelectorError) -> Self {
+        match e {
+            RoleSelectorError::Discord(why) => why.into(),
+            RoleSelectorError::Database(why) => why.into(),
+            RoleSelectorError::Invalid(problems) => {
+                ApiError::invalid_fields(problems.into_iter().map(|p| FieldError::new(p.field, p.message)).collect())
+            }
+            RoleSelectorError::UnknownSelector => ApiError::not_found("Not a role selector in this channel"),
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return ApiError::new(ApiErrorCode::PayloadTooLarge, rejection.body_text());
+        }
+
+        ApiError::new(ApiErrorCode::InvalidRequest, rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::new(ApiErrorCode::InvalidRequest, rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::new(ApiErrorCode::InvalidRequest, rejection.body_text())
+    }
+}
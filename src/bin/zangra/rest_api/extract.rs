@@ -0,0 +1,18 @@
+use axum_macros::{FromRequest, FromRequestParts};
+
+use crate::rest_api::error::ApiError;
+
+/// `axum::Json` that rejects bad bodies with an [`ApiError`] instead of plain text
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct ApiJson<T>(pub T);
+
+/// `axum::extract::Path` that rejects bad ids with an [`ApiError`] instead of plain text
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct ApiPath<T>(pub T);
+
+/// `axum::extract::Query` that rejects bad parameters with an [`ApiError`] instead of plain text
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct ApiQuery<T>(pub T);
@@ -0,0 +1,188 @@
+//! What every request passes through besides authentication: CORS, body and time limits, per
+//! address and per client rate limiting and the access log.
+
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Path, State},
+    http::{header, HeaderValue, Method, Request},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tracing::{field, info, info_span, warn, Instrument};
+
+use crate::{
+    config::ApiSettings,
+    rest_api::{
+        auth::ApiPrincipal,
+        entry::AppState,
+        error::{ApiError, ApiErrorCode},
+    },
+};
+
+//buckets of clients that have been quiet long enough to be full again are dropped past this
+const MAX_CLIENTS: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// A token bucket per client, `burst` requests in a row and then `per_minute`
+#[derive(Clone)]
+pub struct RateLimiter {
+    burst: f64,
+    per_second: f64,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+impl RateLimiter {
+    pub fn new(burst: u32, per_minute: u32) -> RateLimiter {
+        RateLimiter {
+            burst: f64::from(burst.max(1)),
+            per_second: f64::from(per_minute.max(1)) / 60.0,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Takes one of the client's tokens, or says how long until there is one
+    pub fn take(&self, client: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_CLIENTS && !buckets.contains_key(client) {
+            let (burst, per_second) = (self.burst, self.per_second);
+            buckets.retain(|_, b| b.tokens + now.duration_since(b.updated).as_secs_f64() * per_second < burst);
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * self.per_second).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.per_second))
+        }
+    }
+}
+
+/// 429 once the caller has spent its tokens, runs after [`authenticate`](crate::rest_api::auth::authenticate)
+/// so each API key and dashboard user has their own bucket
+pub async fn rate_limit<B>(
+    State(state): State<AppState>,
+    Extension(principal): Extension<ApiPrincipal>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApiError> {
+    match state.rate_limiter.take(&principal.client) {
+        Ok(()) => Ok(next.run(request).await),
+        Err(wait) => {
+            let retry_after = wait.as_secs() + 1;
+            warn!("{} is rate limited for {}s", principal.name, retry_after);
+            Err(ApiError::rate_limited(retry_after))
+        }
+    }
+}
+
+/// 429 once the address has spent its tokens, before [`authenticate`](crate::rest_api::auth::authenticate)
+/// so guessed keys, logins and incoming hooks are limited too
+pub async fn rate_limit_address<B>(
+    State(state): State<AppState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Result<Response, ApiError> {
+    match state.address_rate_limiter.take(&address.ip().to_string()) {
+        Ok(()) => Ok(next.run(request).await),
+        Err(wait) => {
+            let retry_after = wait.as_secs() + 1;
+            warn!("{} is rate limited for {}s", address.ip(), retry_after);
+            Err(ApiError::rate_limited(retry_after))
+        }
+    }
+}
+
+/// Gives up on requests that take longer than `request_timeout_secs`, usually Discord being slow.
+/// Streamed bodies only need their headers in time.
+pub async fn request_timeout<B>(State(state): State<AppState>, request: Request<B>, next: Next<B>) -> Response {
+    let limit = Duration::from_secs(state.settings.request_timeout_secs);
+
+    match tokio::time::timeout(limit, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => ApiError::new(ApiErrorCode::Timeout, "The request took too long").into_response(),
+    }
+}
+
+/// A span per request with its route and guild, closed by a line with the status and latency
+pub async fn access_log<B>(
+    matched_path: Option<MatchedPath>,
+    params: Option<Path<HashMap<String, String>>>,
+    request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let route = matched_path.as_ref().map_or("unmatched", |p| p.as_str()).to_string();
+    let guild = params.and_then(|Path(params)| params.get("guild_id").cloned());
+
+    let span = info_span!(
+        "request",
+        method = %request.method(),
+        route = %route,
+        guild = field::Empty,
+    );
+    if let Some(guild) = &guild {
+        span.record("guild", guild.as_str());
+    }
+
+    let started = Instant::now();
+    let response = next.run(request).instrument(span.clone()).await;
+
+    info!(
+        parent: &span,
+        status = response.status().as_u16(),
+        latency_ms = started.elapsed().as_millis() as u64,
+        "served"
+    );
+
+    response
+}
+
+/// Lets the configured dashboard origins call the API with their session cookie, `None` when
+/// there are none
+pub fn cors_layer(settings: &ApiSettings) -> Option<CorsLayer> {
+    let origins: Vec<HeaderValue> = settings
+        .cors_origins
+        .iter()
+        .filter_map(|origin| match origin.trim_end_matches('/').parse() {
+            Ok(origin) => Some(origin),
+            Err(_) => {
+                warn!("Ignoring CORS origin {origin:?}, it isn't a valid header value");
+                None
+            }
+        })
+        .collect();
+
+    if origins.is_empty() {
+        return None;
+    }
+
+    Some(
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::list(origins))
+            .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+            .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+            .allow_credentials(true)
+            .max_age(Duration::from_secs(60 * 60)),
+    )
+}
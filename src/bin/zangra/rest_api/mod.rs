@@ -1,2 +1,11 @@
+pub mod auth;
+pub mod dto;
 pub mod entry;
-mod routes;
\ No newline at end of file
+pub mod error;
+pub mod extract;
+pub mod layers;
+pub mod oauth;
+pub mod openapi;
+mod routes;
+#[cfg(test)]
+mod tests;
\ No newline at end of file
@@ -0,0 +1,362 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{rejection::QueryRejection, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Extension, Json,
+};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::UserId;
+use sqlx::query;
+use tracing::{error, info};
+use url::Url;
+
+use crate::{
+    config::OAuthConfig,
+    rest_api::{
+        auth::{cookie, hash_token, random_token, ApiPrincipal, ApiScope},
+        dto::PrincipalDto,
+        entry::AppState,
+        error::{ApiError, ApiErrorCode},
+    },
+};
+
+pub const SESSION_COOKIE: &str = "zangra_session";
+const STATE_COOKIE: &str = "zangra_oauth_state";
+
+const SESSION_DAYS: i64 = 7;
+//long enough to click through Discord's consent screen
+const STATE_MINUTES: i64 = 10;
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// The part of `GET /users/@me` the dashboard needs
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DiscordUser {
+    pub id: String,
+    pub username: String,
+}
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+fn set_cookie(oauth: &OAuthConfig, name: &str, value: &str, max_age: Duration) -> String {
+    //browsers drop Secure cookies on plain http, which is what local testing uses
+    let secure = if oauth.redirect_uri.starts_with("https://") {
+        "; Secure"
+    } else {
+        ""
+    };
+
+    format!(
+        "{name}={value}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{secure}",
+        max_age.num_seconds()
+    )
+}
+
+fn oauth_disabled() -> ApiError {
+    ApiError::new(ApiErrorCode::OAuthDisabled, "Dashboard login isn't configured")
+}
+
+/// Trades the authorization code for a token and asks Discord who it belongs to
+pub async fn exchange_code(client: &reqwest::Client, oauth: &OAuthConfig, code: &str) -> Result<DiscordUser> {
+    let token: TokenResponse = client
+        .post(&oauth.token_url)
+        .form(&[
+            ("client_id", oauth.client_id.as_str()),
+            ("client_secret", oauth.client_secret.as_str()),
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", oauth.redirect_uri.as_str()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let user: DiscordUser = client
+        .get(format!("{}/users/@me", oauth.api_base_url.trim_end_matches('/')))
+        .bearer_auth(&token.access_token)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    //only the identity is needed, guild permissions come from the bot's cache
+    if user.id.parse::<u64>().is_err() {
+        return Err(anyhow!("Discord returned an invalid user id {}", user.id));
+    }
+
+    Ok(user)
+}
+
+/// /api/auth/login
+///
+/// Sends the browser to Discord's consent screen
+pub async fn login(State(state): State<AppState>) -> Result<Response, ApiError> {
+    let oauth = match &state.oauth {
+        Some(oauth) => oauth,
+        None => return Err(oauth_disabled()),
+    };
+
+    let csrf_state = random_token();
+    let authorize_url = Url::parse_with_params(
+        &oauth.authorize_url,
+        &[
+            ("client_id", oauth.client_id.as_str()),
+            ("redirect_uri", oauth.redirect_uri.as_str()),
+            ("response_type", "code"),
+            ("scope", "identify"),
+            ("state", csrf_state.as_str()),
+        ],
+    )
+    .map_err(|why| ApiError::new(ApiErrorCode::Internal, format!("Invalid OAuth authorize url: {why}")))?;
+
+    Ok((
+        [(
+            header::SET_COOKIE,
+            set_cookie(oauth, STATE_COOKIE, &csrf_state, Duration::minutes(STATE_MINUTES)),
+        )],
+        Redirect::to(authorize_url.as_str()),
+    )
+        .into_response())
+}
+
+/// /api/auth/callback
+///
+/// Discord redirects here after consent, the session cookie is set and the browser moves on to
+/// the dashboard
+pub async fn callback(State(state): State<AppState>, headers: HeaderMap, query: Result<Query<CallbackQuery>, QueryRejection>) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+    let oauth = match &state.oauth {
+        Some(oauth) => oauth,
+        None => return Err(oauth_disabled()),
+    };
+
+    let code = match (&query.code, &query.error) {
+        (Some(code), None) => code,
+        _ => return Err(ApiError::unauthorized()),
+    };
+
+    //the state has to come back to the browser that started the login
+    let expected_state = cookie(&headers, STATE_COOKIE);
+    if expected_state.is_none() || expected_state != query.state.as_deref() {
+        return Err(ApiError::unauthorized());
+    }
+
+    let user = match exchange_code(&state.http_client, oauth, code).await {
+        Ok(user) => user,
+        Err(why) => {
+            error!("OAuth code exchange failed: {}", why);
+            return Err(ApiError::unauthorized());
+        }
+    };
+
+    let session = random_token();
+    let session_hash = hash_token(&session);
+    let user_id = user.id.parse::<u64>().unwrap_or_default() as i64;
+    let now = Utc::now();
+    let created_at = now.timestamp();
+    let expires_at = (now + Duration::days(SESSION_DAYS)).timestamp();
+
+    if let Err(why) = query!("DELETE FROM DashboardSession WHERE ExpiresAt <= ?", created_at)
+        .execute(&state.db_pool)
+        .await
+    {
+        error!("Unable to clear expired sessions: {}", why);
+    }
+
+    query!(
+        "INSERT INTO DashboardSession (SessionHash, UserId, Username, CreatedAt, ExpiresAt) VALUES (?, ?, ?, ?, ?)",
+        session_hash,
+        user_id,
+        user.username,
+        created_at,
+        expires_at
+    )
+    .execute(&state.db_pool)
+    .await?;
+
+    info!("{} ({}) logged in to the dashboard", user.username, user.id);
+
+    let mut response = Redirect::to(&oauth.dashboard_url).into_response();
+    let cookies = [
+        set_cookie(oauth, SESSION_COOKIE, &session, Duration::days(SESSION_DAYS)),
+        set_cookie(oauth, STATE_COOKIE, "", Duration::zero()),
+    ];
+    for cookie in cookies {
+        if let Ok(value) = cookie.parse() {
+            response.headers_mut().append(header::SET_COOKIE, value);
+        }
+    }
+
+    Ok(response)
+}
+
+/// /api/auth/logout
+pub async fn logout(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Some(session) = cookie(&headers, SESSION_COOKIE) {
+        let session_hash = hash_token(session);
+        if let Err(why) = query!("DELETE FROM DashboardSession WHERE SessionHash = ?", session_hash)
+            .execute(&state.db_pool)
+            .await
+        {
+            error!("Unable to delete session: {}", why);
+        }
+    }
+
+    match &state.oauth {
+        Some(oauth) => (
+            [(header::SET_COOKIE, set_cookie(oauth, SESSION_COOKIE, "", Duration::zero()))],
+            StatusCode::NO_CONTENT,
+        )
+            .into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+/// /api/auth/me
+#[utoipa::path(
+    get,
+    path = "/api/auth/me",
+    responses(
+        (status = 200, description = "The caller", body = PrincipalDto),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn me(Extension(principal): Extension<ApiPrincipal>) -> Json<PrincipalDto> {
+    let user_id = match principal.scope {
+        ApiScope::Member(user_id) => Some(user_id.to_string()),
+        _ => None,
+    };
+
+    Json(PrincipalDto {
+        name: principal.name,
+        user_id,
+    })
+}
+
+/// The logged in user behind a session cookie, expired sessions don't count
+pub async fn find_session(state: &AppState, session: &str) -> Option<ApiPrincipal> {
+    let session_hash = hash_token(session);
+    let now = Utc::now().timestamp();
+
+    let lookup = query!(
+        "SELECT UserId, Username FROM DashboardSession WHERE SessionHash = ? AND ExpiresAt > ?",
+        session_hash,
+        now
+    )
+    .fetch_optional(&state.db_pool);
+
+    match state.metrics.time_query("session_lookup", lookup).await {
+        Ok(row) => row.map(|row| ApiPrincipal {
+            name: row.Username,
+            client: format!("user:{}", row.UserId),
+            scope: ApiScope::Member(UserId(row.UserId as u64)),
+        }),
+        Err(why) => {
+            error!("Unable to read sessions: {}", why);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, net::SocketAddr};
+
+    use axum::{
+        extract::Form,
+        routing::{get, post},
+        Router,
+    };
+    use serde_json::json;
+
+    use super::*;
+
+    fn mock_config(addr: SocketAddr) -> OAuthConfig {
+        OAuthConfig {
+            client_id: "client".to_string(),
+            client_secret: "secret".to_string(),
+            redirect_uri: "http://localhost/api/auth/callback".to_string(),
+            dashboard_url: "/".to_string(),
+            authorize_url: format!("http://{addr}/oauth2/authorize"),
+            token_url: format!("http://{addr}/oauth2/token"),
+            api_base_url: format!("http://{addr}/api"),
+        }
+    }
+
+    /// Stands in for Discord, only `good-code` is a valid authorization code
+    async fn start_mock_discord() -> SocketAddr {
+        let app = Router::new()
+            .route(
+                "/oauth2/token",
+                post(|Form(form): Form<HashMap<String, String>>| async move {
+                    let valid = form.get("code").map(String::as_str) == Some("good-code")
+                        && form.get("client_secret").map(String::as_str) == Some("secret")
+                        && form.get("grant_type").map(String::as_str) == Some("authorization_code");
+                    if valid {
+                        Json(json!({"access_token": "user-token", "token_type": "Bearer"})).into_response()
+                    } else {
+                        StatusCode::BAD_REQUEST.into_response()
+                    }
+                }),
+            )
+            .route(
+                "/api/users/@me",
+                get(|headers: HeaderMap| async move {
+                    match headers.get(header::AUTHORIZATION).and_then(|v| v.to_str().ok()) {
+                        Some("Bearer user-token") => {
+                            Json(json!({"id": "80351110224678912", "username": "nelly"})).into_response()
+                        }
+                        _ => StatusCode::UNAUTHORIZED.into_response(),
+                    }
+                }),
+            );
+
+        let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        addr
+    }
+
+    #[tokio::test]
+    async fn exchanges_code_against_configured_endpoints() {
+        let addr = start_mock_discord().await;
+        let user = exchange_code(&reqwest::Client::new(), &mock_config(addr), "good-code")
+            .await
+            .unwrap();
+
+        assert_eq!(user.id, "80351110224678912");
+        assert_eq!(user.username, "nelly");
+    }
+
+    #[tokio::test]
+    async fn rejected_code_is_an_error() {
+        let addr = start_mock_discord().await;
+        let result = exchange_code(&reqwest::Client::new(), &mock_config(addr), "bad-code").await;
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn reads_cookie_among_others() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, "theme=dark; zangra_session=abc123; other=1".parse().unwrap());
+
+        assert_eq!(cookie(&headers, SESSION_COOKIE), Some("abc123"));
+        assert_eq!(cookie(&headers, STATE_COOKIE), None);
+    }
+}
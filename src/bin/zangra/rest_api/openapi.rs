@@ -0,0 +1,120 @@
+use axum::Json;
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme},
+    Modify, OpenApi,
+};
+
+use crate::rest_api::{
+    dto::{
+        CategoryDto, ChannelKindDto, CreatedIntegrationDto, ChannelMessages, ChannelSummary, EmbedAuthorDto, EmbedChangeDto, EmbedDiffDto, EmbedDto, EmbedFetchErrorDto, EmbedFieldDto,
+        EmbedFooterDto, EmbedListResponse, EmbedMediaDto, EmbedRevisionDto, EmojiDto, EventsSkippedDto, GuildChannelDto, GuildDto, GuildEventDto, GuildEventKindDto, GuildSummary,
+        IncomingMessageRequest, IntegrationDto, IntegrationRequest, MessageDto, PrincipalDto, RoleDto,
+        RoleOptionDto, RoleSelectorDto, RoleSelectorRequest, RoleSelectorStyleDto,
+    },
+    entry,
+    error::{ApiErrorBody, ApiErrorCode, FieldError},
+    oauth::{self, SESSION_COOKIE},
+    routes::{channels, embed, embed_edit, events, guilds, integrations, role_selector},
+};
+
+/// Everything under `/api`, served as `/api/openapi.json`
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Zangra",
+        description = "Dashboard API of the Zangra Discord bot. Besides the responses listed per route, any \
+            authenticated route can answer 429 `RateLimited` with a `Retry-After` header, 413 `PayloadTooLarge` \
+            and 504 `Timeout`."
+    ),
+    paths(
+        oauth::me,
+        channels::post_channel_embed,
+        guilds::post_filter_guilds,
+        guilds::get_guild,
+        guilds::get_guild_channels,
+        guilds::get_guild_categories,
+        guilds::get_guild_roles,
+        guilds::get_guild_emojis,
+        events::get_guild_events,
+        integrations::get_integrations,
+        integrations::post_integration,
+        integrations::put_integration,
+        integrations::delete_integration,
+        integrations::post_incoming_message,
+        embed_edit::post_edit_embed,
+        entry::post_embed,
+        entry::get_embed,
+        entry::put_embed,
+        entry::delete_embed,
+        entry::get_embed_all_guild,
+        entry::get_embed_all_channel,
+        embed::get_embed_revisions,
+        embed::get_embed_diff,
+        embed::post_embed_rollback,
+        role_selector::post_role_selector,
+        role_selector::get_role_selector,
+        role_selector::put_role_selector,
+        role_selector::delete_role_selector,
+    ),
+    components(schemas(
+        ApiErrorBody,
+        ApiErrorCode,
+        CategoryDto,
+        ChannelKindDto,
+        channels::EmbedDetails,
+        channels::EmbedFieldDetails,
+        channels::MessageDetails,
+        ChannelMessages,
+        ChannelSummary,
+        CreatedIntegrationDto,
+        EmbedAuthorDto,
+        EmbedChangeDto,
+        EmbedDiffDto,
+        EmbedDto,
+        EmbedFetchErrorDto,
+        EmbedFieldDto,
+        EmbedFooterDto,
+        EmbedListResponse,
+        EmbedMediaDto,
+        EmbedRevisionDto,
+        EmojiDto,
+        EventsSkippedDto,
+        FieldError,
+        GuildChannelDto,
+        GuildDto,
+        GuildEventDto,
+        GuildEventKindDto,
+        GuildSummary,
+        IncomingMessageRequest,
+        IntegrationDto,
+        IntegrationRequest,
+        MessageDto,
+        PrincipalDto,
+        RoleDto,
+        RoleOptionDto,
+        RoleSelectorDto,
+        RoleSelectorRequest,
+        RoleSelectorStyleDto,
+    )),
+    modifiers(&SecuritySchemes)
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("api_key", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+        components.add_security_scheme("integration_token", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(SESSION_COOKIE))),
+        );
+    }
+}
+
+/// /api/openapi.json
+pub async fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
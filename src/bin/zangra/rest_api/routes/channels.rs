@@ -1,72 +1,328 @@
-use axum::{extract::{State, Path}, http::StatusCode, Json};
+use axum::{extract::State, http::StatusCode, Extension, Json};
 use axum_macros::debug_handler;
 use serde::{Deserialize, Serialize};
-use serenity::{builder::CreateEmbed, utils::Color};
+use serenity::{builder::CreateEmbed, model::{id::ChannelId, Timestamp}, utils::Color};
+use url::Url;
+use utoipa::ToSchema;
 
-use crate::rest_api::entry::AppState;
-#[derive(Clone, Deserialize, Serialize)]
+use crate::{rest_api::{auth::{channel_guild, ApiPrincipal}, dto::MessageDto, entry::AppState, error::{ApiError, FieldError}, extract::{ApiJson, ApiPath}}, utils::embeds::{post_embed_message, RevisionAuthor}};
+
+//Discord's limits, counted in characters
+const MAX_CONTENT: usize = 2000;
+const MAX_EMBEDS: usize = 10;
+const MAX_TITLE: usize = 256;
+const MAX_DESCRIPTION: usize = 4096;
+const MAX_FIELDS: usize = 25;
+const MAX_FIELD_NAME: usize = 256;
+const MAX_FIELD_VALUE: usize = 1024;
+const MAX_FOOTER_TEXT: usize = 2048;
+const MAX_AUTHOR_NAME: usize = 256;
+//across every embed of the message
+const MAX_EMBED_TOTAL: usize = 6000;
+
+/// The dashboard's embed form
+#[derive(Debug, Clone, Default, Deserialize, Serialize, ToSchema)]
 pub struct EmbedDetails {
     embed_title: Option<String>,
     embed_title_url: Option<String>,
     embed_description: Option<String>,
     embed_image_url: Option<String>,
     embed_thumbnail_url: Option<String>,
-    embed_color: Option<String>
+    /// `#rrggbb`
+    embed_color: Option<String>,
+    embed_footer_text: Option<String>,
+    embed_footer_icon_url: Option<String>,
+    embed_author_name: Option<String>,
+    embed_author_url: Option<String>,
+    embed_author_icon_url: Option<String>,
+    /// RFC 3339
+    embed_timestamp: Option<String>,
+    #[serde(default)]
+    embed_fields: Vec<EmbedFieldDetails>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct EmbedFieldDetails {
+    name: String,
+    value: String,
+    #[serde(default)]
+    inline: bool,
+}
+
+/// A whole message from the dashboard. A single embed can still be given at the top level the
+/// way it was before `embeds` existed, it's used when `embeds` is empty.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, ToSchema)]
+pub struct MessageDetails {
+    content: Option<String>,
+    #[serde(default)]
+    embeds: Vec<EmbedDetails>,
+    #[serde(flatten)]
+    embed: EmbedDetails,
+}
+
+/// `#rrggbb`
+fn parse_hex_color(color: &str) -> Option<Color> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+
+    u32::from_str_radix(hex, 16).ok().map(Color::new)
+}
+
+fn chars(text: Option<&str>) -> usize {
+    text.map_or(0, |t| t.chars().count())
+}
+
+fn check_length(errors: &mut Vec<FieldError>, field: String, text: Option<&str>, max: usize) {
+    let length = chars(text);
+    if length > max {
+        errors.push(FieldError::new(field, format!("Must be at most {max} characters, got {length}")));
+    }
+}
+
+//Discord only shows links and images from these, `attachment://` points at a file sent with the message
+fn check_url(errors: &mut Vec<FieldError>, field: String, url: Option<&str>) {
+    let url = match url {
+        Some(url) => url,
+        None => return,
+    };
+    match Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https" | "attachment") => {}
+        Ok(_) => errors.push(FieldError::new(field, format!("`{url}` must be an http, https or attachment URL"))),
+        Err(_) => errors.push(FieldError::new(field, format!("`{url}` isn't a URL"))),
+    }
 }
 
-impl From<EmbedDetails> for CreateEmbed {
-    fn from(ed: EmbedDetails) -> Self {
+impl EmbedDetails {
+    /// Discord refuses embeds with nothing to show, a colour or link alone isn't enough
+    fn is_empty(&self) -> bool {
+        self.embed_title.is_none()
+            && self.embed_description.is_none()
+            && self.embed_image_url.is_none()
+            && self.embed_thumbnail_url.is_none()
+            && self.embed_footer_text.is_none()
+            && self.embed_author_name.is_none()
+            && self.embed_fields.is_empty()
+    }
+
+    /// The characters Discord counts toward the message's embed total
+    fn text_length(&self) -> usize {
+        chars(self.embed_title.as_deref())
+            + chars(self.embed_description.as_deref())
+            + chars(self.embed_footer_text.as_deref())
+            + chars(self.embed_author_name.as_deref())
+            + self
+                .embed_fields
+                .iter()
+                .map(|f| f.name.chars().count() + f.value.chars().count())
+                .sum::<usize>()
+    }
+
+    /// Adds a [`FieldError`] for every value Discord would refuse, `prefix` locates the embed
+    fn validate(&self, prefix: &str, errors: &mut Vec<FieldError>) {
+        if self.is_empty() {
+            errors.push(FieldError::new(
+                format!("{prefix}embed_title"),
+                "An embed needs a title, description, field, image, thumbnail, author or footer",
+            ));
+        }
+
+        check_length(errors, format!("{prefix}embed_title"), self.embed_title.as_deref(), MAX_TITLE);
+        check_length(errors, format!("{prefix}embed_description"), self.embed_description.as_deref(), MAX_DESCRIPTION);
+        check_length(errors, format!("{prefix}embed_footer_text"), self.embed_footer_text.as_deref(), MAX_FOOTER_TEXT);
+        check_length(errors, format!("{prefix}embed_author_name"), self.embed_author_name.as_deref(), MAX_AUTHOR_NAME);
+
+        check_url(errors, format!("{prefix}embed_title_url"), self.embed_title_url.as_deref());
+        check_url(errors, format!("{prefix}embed_image_url"), self.embed_image_url.as_deref());
+        check_url(errors, format!("{prefix}embed_thumbnail_url"), self.embed_thumbnail_url.as_deref());
+        check_url(errors, format!("{prefix}embed_footer_icon_url"), self.embed_footer_icon_url.as_deref());
+        check_url(errors, format!("{prefix}embed_author_url"), self.embed_author_url.as_deref());
+        check_url(errors, format!("{prefix}embed_author_icon_url"), self.embed_author_icon_url.as_deref());
+
+        if let Some(color) = &self.embed_color {
+            if parse_hex_color(color).is_none() {
+                errors.push(FieldError::new(format!("{prefix}embed_color"), format!("`{color}` isn't a #rrggbb colour")));
+            }
+        }
+
+        if let Some(timestamp) = &self.embed_timestamp {
+            if Timestamp::parse(timestamp).is_err() {
+                errors.push(FieldError::new(format!("{prefix}embed_timestamp"), format!("`{timestamp}` isn't an RFC 3339 timestamp")));
+            }
+        }
+
+        if self.embed_footer_icon_url.is_some() && self.embed_footer_text.is_none() {
+            errors.push(FieldError::new(format!("{prefix}embed_footer_text"), "A footer icon needs footer text"));
+        }
+
+        if (self.embed_author_url.is_some() || self.embed_author_icon_url.is_some()) && self.embed_author_name.is_none() {
+            errors.push(FieldError::new(format!("{prefix}embed_author_name"), "An author link or icon needs an author name"));
+        }
+
+        if self.embed_fields.len() > MAX_FIELDS {
+            errors.push(FieldError::new(
+                format!("{prefix}embed_fields"),
+                format!("At most {MAX_FIELDS} fields are allowed, got {}", self.embed_fields.len()),
+            ));
+        }
+
+        for (i, field) in self.embed_fields.iter().enumerate() {
+            if field.name.trim().is_empty() {
+                errors.push(FieldError::new(format!("{prefix}embed_fields[{i}].name"), "Must not be empty"));
+            }
+            if field.value.trim().is_empty() {
+                errors.push(FieldError::new(format!("{prefix}embed_fields[{i}].value"), "Must not be empty"));
+            }
+            check_length(errors, format!("{prefix}embed_fields[{i}].name"), Some(&field.name), MAX_FIELD_NAME);
+            check_length(errors, format!("{prefix}embed_fields[{i}].value"), Some(&field.value), MAX_FIELD_VALUE);
+        }
+    }
+
+    /// Only call once [`EmbedDetails::validate`] found nothing
+    fn build(self) -> CreateEmbed {
         let mut embed = CreateEmbed::default();
 
-        if let Some(title) = ed.embed_title {
+        if let Some(title) = self.embed_title {
             embed.title(title);
         }
 
-        if let Some(title_url) = ed.embed_title_url {
+        if let Some(title_url) = self.embed_title_url {
             embed.url(title_url);
         }
 
-        if let Some(description) = ed.embed_description {
+        if let Some(description) = self.embed_description {
             embed.description(description);
         }
 
-        if let Some(image_url) = ed.embed_image_url {
+        if let Some(image_url) = self.embed_image_url {
             embed.image(image_url);
         }
 
-        if let Some(thumbnail_url) = ed.embed_thumbnail_url {
+        if let Some(thumbnail_url) = self.embed_thumbnail_url {
             embed.thumbnail(thumbnail_url);
         }
 
-        if let Some(color) = ed.embed_color { 
-            //hex to rgb conversion
-            let r = u8::from_str_radix(&color[1..3], 16).unwrap();
-            let g = u8::from_str_radix(&color[3..5], 16).unwrap();
-            let b = u8::from_str_radix(&color[5..7], 16).unwrap();
+        if let Some(color) = self.embed_color.as_deref().and_then(parse_hex_color) {
+            embed.color(color);
+        }
+
+        if let Some(text) = self.embed_footer_text {
+            embed.footer(|f| {
+                f.text(text);
+                if let Some(icon_url) = self.embed_footer_icon_url {
+                    f.icon_url(icon_url);
+                }
+                f
+            });
+        }
 
-            embed.color(Color::from_rgb(r, g, b));
+        if let Some(name) = self.embed_author_name {
+            embed.author(|a| {
+                a.name(name);
+                if let Some(url) = self.embed_author_url {
+                    a.url(url);
+                }
+                if let Some(icon_url) = self.embed_author_icon_url {
+                    a.icon_url(icon_url);
+                }
+                a
+            });
+        }
+
+        if let Some(timestamp) = self.embed_timestamp.as_deref().and_then(|t| Timestamp::parse(t).ok()) {
+            embed.timestamp(timestamp);
+        }
+
+        for field in self.embed_fields {
+            embed.field(field.name, field.value, field.inline);
         }
 
         embed
     }
 }
 
-#[debug_handler]
-pub async fn post_channel_embed(State(state): State<AppState>, Path(channel_id): Path<u64>, Json(embed_payload): Json<EmbedDetails>) -> StatusCode {
-    let ctx = state.ctx.clone();
+impl MessageDetails {
+    fn content(&self) -> Option<&str> {
+        self.content.as_deref().filter(|c| !c.trim().is_empty())
+    }
+
+    /// The embeds to send with where each is in the body, `embeds` or else the top level one
+    fn embeds(&self) -> Vec<(String, &EmbedDetails)> {
+        if !self.embeds.is_empty() {
+            self.embeds.iter().enumerate().map(|(i, e)| (format!("embeds[{i}]."), e)).collect()
+        } else if !self.embed.is_empty() {
+            vec![(String::new(), &self.embed)]
+        } else {
+            Vec::new()
+        }
+    }
 
+    /// Adds a [`FieldError`] for every value Discord would refuse, `prefix` locates the message
+    pub fn validate(&self, prefix: &str, errors: &mut Vec<FieldError>) {
+        let content = self.content();
+        check_length(errors, format!("{prefix}content"), content, MAX_CONTENT);
 
-    match ctx.http.get_channel(channel_id).await.unwrap().id().send_message(&ctx, |m| {
-        m.set_embed(embed_payload.into())
-    }).await {
-        Ok(_msg) => {
-            StatusCode::OK
-        },
-        Err(why) => {
-            println!("{why}");
-            StatusCode::BAD_GATEWAY
+        let embeds = self.embeds();
+
+        if content.is_none() && embeds.is_empty() {
+            errors.push(FieldError::new(format!("{prefix}content"), "A message needs content or at least one embed"));
+        }
+
+        if embeds.len() > MAX_EMBEDS {
+            errors.push(FieldError::new(format!("{prefix}embeds"), format!("At most {MAX_EMBEDS} embeds are allowed, got {}", embeds.len())));
+        }
+
+        for (embed_prefix, embed) in &embeds {
+            embed.validate(&format!("{prefix}{embed_prefix}"), errors);
         }
 
+        let total: usize = embeds.iter().map(|(_, e)| e.text_length()).sum();
+        if total > MAX_EMBED_TOTAL {
+            errors.push(FieldError::new(
+                format!("{prefix}embeds"),
+                format!("Embeds may hold at most {MAX_EMBED_TOTAL} characters in total, got {total}"),
+            ));
+        }
     }
-    // StatusCode::OK
-}
\ No newline at end of file
+
+    /// The content and embeds to send, or every problem with them at once
+    pub fn build(self) -> Result<(Option<String>, Vec<CreateEmbed>), ApiError> {
+        let mut errors = Vec::new();
+        self.validate("", &mut errors);
+        if !errors.is_empty() {
+            return Err(ApiError::invalid_fields(errors));
+        }
+
+        let content = self.content().map(str::to_string);
+        let embeds = self.embeds().into_iter().map(|(_, e)| e.clone().build()).collect();
+
+        Ok((content, embeds))
+    }
+}
+
+/// /api/channels/:channel_id/embed
+#[utoipa::path(
+    post,
+    path = "/api/channels/{channel_id}/embed",
+    params(("channel_id" = String, Path, description = "Channel snowflake")),
+    request_body = MessageDetails,
+    responses(
+        (status = 201, description = "Message posted and saved", body = MessageDto),
+        (status = 400, description = "Malformed body", body = ApiErrorBody),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild, or the bot can't post there", body = ApiErrorBody),
+        (status = 422, description = "Invalid fields, listed in `details.errors`", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+#[debug_handler]
+pub async fn post_channel_embed(State(state): State<AppState>, Extension(principal): Extension<ApiPrincipal>, ApiPath(channel_id): ApiPath<u64>, ApiJson(message_payload): ApiJson<MessageDetails>) -> Result<(StatusCode, Json<MessageDto>), ApiError> {
+    let (content, embeds) = message_payload.build()?;
+    let channel_id = ChannelId(channel_id);
+    let guild_id = channel_guild(&state, channel_id).await.ok_or_else(ApiError::forbidden)?;
+
+    let message = post_embed_message(&state.discord, &state.db_pool, guild_id, channel_id, content, embeds, &RevisionAuthor::from(&principal)).await?;
+
+    Ok((StatusCode::CREATED, Json(message.into())))
+}
@@ -0,0 +1,122 @@
+use axum::{extract::State, Extension, Json};
+use serenity::model::id::{ChannelId, GuildId, MessageId};
+
+use crate::{
+    rest_api::{
+        auth::ApiPrincipal,
+        dto::{EmbedDiffDto, EmbedDiffQuery, EmbedRevisionDto, MessageDto},
+        entry::AppState,
+        error::ApiError,
+        extract::{ApiPath, ApiQuery},
+    },
+    utils::embeds::{diff_embeds, embed_revisions, rollback_embed_message, EmbedError, RevisionAuthor},
+};
+
+/// /api/embed/:guild_id/:channel_id/:message_id/revisions
+#[utoipa::path(
+    get,
+    path = "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+        ("message_id" = String, Path, description = "Message snowflake"),
+    ),
+    responses(
+        (status = 200, description = "Every version of the embed, oldest first, also once its message is deleted", body = [EmbedRevisionDto]),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+        (status = 404, description = "Never a saved embed", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_embed_revisions(
+    State(state): State<AppState>,
+    ApiPath((guild_id, channel_id, message_id)): ApiPath<(u64, u64, u64)>,
+) -> Result<Json<Vec<EmbedRevisionDto>>, ApiError> {
+    let revisions = embed_revisions(&state.db_pool, GuildId(guild_id), ChannelId(channel_id), MessageId(message_id)).await?;
+    if revisions.is_empty() {
+        return Err(EmbedError::UnknownRevision.into());
+    }
+
+    Ok(Json(revisions.into_iter().map(EmbedRevisionDto::from).collect()))
+}
+
+/// /api/embed/:guild_id/:channel_id/:message_id/revisions/diff?from=&to=
+#[utoipa::path(
+    get,
+    path = "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions/diff",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+        ("message_id" = String, Path, description = "Message snowflake"),
+        ("from" = i64, Query, description = "Older revision"),
+        ("to" = Option<i64>, Query, description = "Newer revision, the latest when left out"),
+    ),
+    responses(
+        (status = 200, description = "The values that differ", body = EmbedDiffDto),
+        (status = 400, description = "Missing or malformed revision numbers", body = ApiErrorBody),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+        (status = 404, description = "Not a saved embed, or no such revision", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_embed_diff(
+    State(state): State<AppState>,
+    ApiPath((guild_id, channel_id, message_id)): ApiPath<(u64, u64, u64)>,
+    ApiQuery(diff): ApiQuery<EmbedDiffQuery>,
+) -> Result<Json<EmbedDiffDto>, ApiError> {
+    let revisions = embed_revisions(&state.db_pool, GuildId(guild_id), ChannelId(channel_id), MessageId(message_id)).await?;
+
+    let from = revisions.iter().find(|r| r.revision == diff.from);
+    let to = match diff.to {
+        Some(to) => revisions.iter().find(|r| r.revision == to),
+        None => revisions.last(),
+    };
+
+    match (from, to) {
+        (Some(from), Some(to)) => Ok(Json(EmbedDiffDto {
+            from: from.revision,
+            to: to.revision,
+            changes: diff_embeds(&from.embeds, &to.embeds).into_iter().map(Into::into).collect(),
+        })),
+        _ => Err(EmbedError::UnknownRevision.into()),
+    }
+}
+
+/// /api/embed/:guild_id/:channel_id/:message_id/revisions/:revision/rollback
+#[utoipa::path(
+    post,
+    path = "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions/{revision}/rollback",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+        ("message_id" = String, Path, description = "Message snowflake"),
+        ("revision" = i64, Path, description = "Revision to restore"),
+    ),
+    responses(
+        (status = 200, description = "The message with the old embeds, saved as a new revision", body = MessageDto),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild, or not the bot's message", body = ApiErrorBody),
+        (status = 404, description = "Not a saved embed, or no such revision", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn post_embed_rollback(
+    State(state): State<AppState>,
+    Extension(principal): Extension<ApiPrincipal>,
+    ApiPath((guild_id, channel_id, message_id, revision)): ApiPath<(u64, u64, u64, i64)>,
+) -> Result<Json<MessageDto>, ApiError> {
+    let message = rollback_embed_message(
+        &state.discord,
+        &state.db_pool,
+        GuildId(guild_id),
+        ChannelId(channel_id),
+        MessageId(message_id),
+        revision,
+        &RevisionAuthor::from(&principal),
+    )
+    .await?;
+
+    Ok(Json(message.into()))
+}
@@ -1,42 +1,36 @@
-use axum::{extract::{State, Path}, http::StatusCode, Json};
-use tracing::error;
+use axum::{extract::State, Extension, Json};
+use serenity::model::id::{ChannelId, MessageId};
 
-use crate::rest_api::entry::AppState;
+use crate::{rest_api::{auth::{channel_guild, ApiPrincipal}, dto::MessageDto, entry::AppState, error::ApiError, extract::{ApiJson, ApiPath}}, utils::embeds::{edit_embed_message, RevisionAuthor}};
 
-use super::channels::EmbedDetails;
+use super::channels::MessageDetails;
 
 
-pub async fn post_edit_embed(State(state): State<AppState>, Path((channel_id, message_id)): Path<(u64, u64)>, Json(embed_payload): Json<EmbedDetails>) -> StatusCode {
-    let ctx = state.ctx.clone();
-    let mut message = match ctx.http.get_message(channel_id, message_id).await {
-        Ok(msg) => msg,
-        Err(why) => {
-            error!("{why}");
-            return StatusCode::BAD_GATEWAY;
-        }
-    };
+/// /api/embededit/:channel_id/:message_id
+#[utoipa::path(
+    post,
+    path = "/api/embededit/{channel_id}/{message_id}",
+    params(
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+        ("message_id" = String, Path, description = "Message snowflake"),
+    ),
+    request_body = MessageDetails,
+    responses(
+        (status = 200, description = "The edited message", body = MessageDto),
+        (status = 400, description = "Malformed body", body = ApiErrorBody),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild, or not the bot's message", body = ApiErrorBody),
+        (status = 404, description = "No such message", body = ApiErrorBody),
+        (status = 422, description = "Invalid fields, listed in `details.errors`", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn post_edit_embed(State(state): State<AppState>, Extension(principal): Extension<ApiPrincipal>, ApiPath((channel_id, message_id)): ApiPath<(u64, u64)>, ApiJson(message_payload): ApiJson<MessageDetails>) -> Result<Json<MessageDto>, ApiError> {
+    let (content, embeds) = message_payload.build()?;
+    let channel_id = ChannelId(channel_id);
+    let guild_id = channel_guild(&state, channel_id).await.ok_or_else(ApiError::forbidden)?;
 
-    match message.edit(&ctx, |e| {
-        e.set_embed(embed_payload.into())
-    }).await {
-        Ok(()) => StatusCode::OK,
-        Err(why) => {
-            error!("{why}");
-            StatusCode::BAD_GATEWAY
-        }
-    }
+    let message = edit_embed_message(&state.discord, &state.db_pool, guild_id, channel_id, MessageId(message_id), content, embeds, &RevisionAuthor::from(&principal)).await?;
 
-    // match ctx.http.get_message(channel_id, message_id).await.unwrap().id().send_message(&ctx, |m| {
-    //     m.set_embed(embed_payload.into())
-    // }).await {
-    //     Ok(_msg) => {
-    //         StatusCode::OK
-    //     },
-    //     Err(why) => {
-    //         println!("{why}");
-    //         StatusCode::BAD_GATEWAY
-    //     }
-
-    // }
-    // StatusCode::OK
-}
\ No newline at end of file
+    Ok(Json(message.into()))
+}
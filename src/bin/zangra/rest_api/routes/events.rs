@@ -0,0 +1,61 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use serenity::{
+    futures::{stream, Stream, StreamExt},
+    model::id::GuildId,
+};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::rest_api::{
+    dto::{EventsSkippedDto, GuildEventDto},
+    entry::AppState,
+    extract::ApiPath,
+};
+
+/// /api/guilds/:guild_id/events
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild_id}/events",
+    params(("guild_id" = String, Path, description = "Guild snowflake")),
+    responses(
+        (status = 200, description = "Server-sent events named after the event's `type` for as long as the connection stays open. \
+            A client too slow to keep up gets a `skipped` event with the number of this guild's events it missed instead of holding the bot up.",
+            body = GuildEventDto, content_type = "text/event-stream"),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_guild_events(
+    State(state): State<AppState>,
+    ApiPath(guild_id): ApiPath<u64>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let guild_id = GuildId(guild_id);
+
+    let events = stream::unfold(state.events.subscribe_guild(guild_id), move |mut receiver| async move {
+        loop {
+            let event = match receiver.recv().await {
+                Ok(event) => Event::default()
+                    .event(event.kind.name())
+                    .json_data(GuildEventDto::from(event.as_ref())),
+                Err(RecvError::Lagged(skipped)) => Event::default()
+                    .event("skipped")
+                    .json_data(EventsSkippedDto { skipped }),
+                Err(RecvError::Closed) => return None,
+            };
+
+            //only fails for values that don't serialise, the DTOs always do
+            if let Ok(event) = event {
+                return Some((Ok(event), receiver));
+            }
+        }
+    })
+    //the server waits for open responses before it stops
+    .take_until(state.shutdown.wait());
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
@@ -1,66 +1,305 @@
 
-use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    Json,
-};
+use axum::{extract::State, Extension, Json};
 use serenity::model::{
-    id::{ChannelId, GuildId},
-    prelude::{ChannelType, GuildInfo},
+    channel::GuildChannel,
+    guild::{Emoji, Guild, Member, PartialGuild, Role},
+    id::GuildId,
+    prelude::{ChannelType, OnlineStatus},
+    Permissions,
 };
-use tracing::error;
 
-use crate::rest_api::entry::AppState;
+use crate::rest_api::{auth::ApiPrincipal, dto::{CategoryDto, ChannelSummary, EmojiDto, GuildChannelDto, GuildDto, GuildSummary, RoleDto}, entry::AppState, error::ApiError, extract::ApiPath};
 
-/// /api/guilds/:guild_id/channels
-pub async fn get_guild_channels(
-    State(state): State<AppState>,
-    Path(guild_id): Path<u64>,
-) -> Result<Json<Vec<(ChannelId, String)>>, StatusCode> {
-    let guild_id = GuildId(guild_id);
-    let ctx = state.ctx;
-    let mut channels: Vec<(ChannelId, String)> = Vec::new();
-
-    match guild_id.channels(&ctx).await {
-        Ok(c) => {
-            c.iter()
-                .filter(|(_k, v)| matches!(v.kind, ChannelType::Text))
-                .for_each(|(k, v)| channels.push((k.to_owned(), v.name.to_owned())));
+enum GuildSource {
+    Cached(Box<Guild>),
+    Fetched(Box<PartialGuild>),
+}
+
+/// What the metadata routes read about a guild, from the gateway's cache when it has the guild
+/// and otherwise from Discord
+struct GuildSnapshot {
+    source: GuildSource,
+    channels: Vec<GuildChannel>,
+    threads: Vec<GuildChannel>,
+    /// The bot's own member, without it every permission reads as none
+    bot: Option<Member>,
+}
+
+impl GuildSnapshot {
+    async fn load(state: &AppState, guild_id: GuildId) -> Result<GuildSnapshot, ApiError> {
+        let bot_id = state.discord.cache.current_user_id();
+
+        if let Some(guild) = state.discord.cache.guild(guild_id) {
+            return Ok(GuildSnapshot {
+                channels: guild.channels.values().filter_map(|c| c.clone().guild()).collect(),
+                threads: guild.threads.clone(),
+                bot: guild.members.get(&bot_id).cloned(),
+                source: GuildSource::Cached(Box::new(guild)),
+            });
         }
-        Err(why) => {
-            error!("{}", why);
-            return Err(StatusCode::BAD_GATEWAY);
+
+        let guild = guild_id.to_partial_guild_with_counts(&state.discord).await?;
+        let channels = guild_id.channels(&state.discord).await?.into_values().collect();
+        let threads = guild_id.get_active_threads(&state.discord).await?.threads;
+        let bot = guild_id.member(&state.discord, bot_id).await.ok();
+
+        Ok(GuildSnapshot {
+            source: GuildSource::Fetched(Box::new(guild)),
+            channels,
+            threads,
+            bot,
+        })
+    }
+
+    fn bot_permissions(&self, channel: &GuildChannel) -> Permissions {
+        let bot = match &self.bot {
+            Some(bot) => bot,
+            None => return Permissions::empty(),
+        };
+
+        let permissions = match &self.source {
+            GuildSource::Cached(guild) => guild.user_permissions_in(channel, bot),
+            GuildSource::Fetched(guild) => guild.user_permissions_in(channel, bot),
+        };
+
+        permissions.unwrap_or_else(|_| Permissions::empty())
+    }
+
+    /// Threads take their permissions from the channel they're in
+    fn channel_dto(&self, channel: &GuildChannel, permissions_from: &GuildChannel) -> GuildChannelDto {
+        let permissions = self.bot_permissions(permissions_from);
+
+        let mut threads: Vec<&GuildChannel> = self
+            .threads
+            .iter()
+            .filter(|t| t.parent_id == Some(channel.id))
+            .collect();
+        threads.sort_by_key(|t| t.id);
+
+        GuildChannelDto {
+            id: channel.id.to_string(),
+            name: channel.name.clone(),
+            kind: channel.kind.into(),
+            position: channel.position,
+            parent_id: channel.parent_id.map(|p| p.to_string()),
+            nsfw: channel.nsfw,
+            bot_permissions: permissions.get_permission_names().into_iter().map(String::from).collect(),
+            bot_permission_bits: permissions.bits().to_string(),
+            threads: threads.into_iter().map(|t| self.channel_dto(t, channel)).collect(),
         }
-    };
+    }
+}
 
-    Ok(Json(channels))
+//Discord lists text-like channels above voice ones within a category
+fn display_order(channel: &GuildChannel) -> (bool, i64, u64) {
+    let voice_like = matches!(channel.kind, ChannelType::Voice | ChannelType::Stage);
+    (voice_like, channel.position, channel.id.0)
 }
 
-pub async fn post_filter_guilds(State(state): State<AppState>, Json(guilds): Json<Vec<GuildInfo>>) -> Result<Json<Vec<GuildInfo>>, StatusCode> {
-    let ctx = state.ctx;
-    let mut in_guilds: Vec<GuildInfo> = Vec::new();
-    let bot_user = match ctx.http.get_current_user().await {
-        Ok(user) => user,
-        Err(why) => {
-            error!("{why}");
-            return Err(StatusCode::BAD_GATEWAY);
+/// /api/guilds/:guild_id
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild_id}",
+    params(("guild_id" = String, Path, description = "Guild snowflake")),
+    responses(
+        (status = 200, description = "The guild and its member counts", body = GuildDto),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_guild(
+    State(state): State<AppState>,
+    ApiPath(guild_id): ApiPath<u64>,
+) -> Result<Json<GuildDto>, ApiError> {
+    let guild = match state.discord.cache.guild(GuildId(guild_id)) {
+        Some(guild) => GuildDto {
+            id: guild.id.to_string(),
+            name: guild.name,
+            icon: guild.icon,
+            owner_id: guild.owner_id.to_string(),
+            member_count: guild.member_count,
+            online_count: Some(guild.presences.values().filter(|p| p.status != OnlineStatus::Offline).count() as u64),
+        },
+        None => {
+            let guild = GuildId(guild_id).to_partial_guild_with_counts(&state.discord).await?;
+            GuildDto {
+                id: guild.id.to_string(),
+                name: guild.name,
+                icon: guild.icon,
+                owner_id: guild.owner_id.to_string(),
+                member_count: guild.approximate_member_count.unwrap_or_default(),
+                online_count: guild.approximate_presence_count,
+            }
         }
     };
 
-    let bot_guild_ids: Vec<u64> = match bot_user.guilds(&ctx).await {
-        Ok(guilds) => guilds.iter().map(|g| g.id.0).collect(),
-        Err(why) => {
-            error!("{why}");
-            return Err(StatusCode::BAD_GATEWAY);
-        }
+    Ok(Json(guild))
+}
+
+/// /api/guilds/:guild_id/categories
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild_id}/categories",
+    params(("guild_id" = String, Path, description = "Guild snowflake")),
+    responses(
+        (status = 200, description = "Every channel and active thread by category, in Discord's order", body = [CategoryDto]),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_guild_categories(
+    State(state): State<AppState>,
+    ApiPath(guild_id): ApiPath<u64>,
+) -> Result<Json<Vec<CategoryDto>>, ApiError> {
+    let snapshot = GuildSnapshot::load(&state, GuildId(guild_id)).await?;
+
+    let mut categories: Vec<&GuildChannel> = snapshot.channels.iter().filter(|c| c.kind == ChannelType::Category).collect();
+    categories.sort_by_key(|c| (c.position, c.id.0));
+
+    let children = |parent: Option<&GuildChannel>| {
+        let mut channels: Vec<&GuildChannel> = snapshot
+            .channels
+            .iter()
+            .filter(|c| c.kind != ChannelType::Category && c.parent_id == parent.map(|p| p.id))
+            .collect();
+        channels.sort_by_key(|c| display_order(c));
+        channels.into_iter().map(|c| snapshot.channel_dto(c, c)).collect::<Vec<_>>()
+    };
+
+    let mut listed = vec![CategoryDto {
+        id: None,
+        name: None,
+        position: -1,
+        channels: children(None),
+    }];
+    for category in categories {
+        listed.push(CategoryDto {
+            id: Some(category.id.to_string()),
+            name: Some(category.name.clone()),
+            position: category.position,
+            channels: children(Some(category)),
+        });
+    }
+
+    Ok(Json(listed))
+}
+
+/// /api/guilds/:guild_id/roles
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild_id}/roles",
+    params(("guild_id" = String, Path, description = "Guild snowflake")),
+    responses(
+        (status = 200, description = "The guild's roles, highest first", body = [RoleDto]),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_guild_roles(
+    State(state): State<AppState>,
+    ApiPath(guild_id): ApiPath<u64>,
+) -> Result<Json<Vec<RoleDto>>, ApiError> {
+    let guild_id = GuildId(guild_id);
+    let roles = match state.discord.cache.guild_roles(guild_id) {
+        Some(roles) => roles,
+        None => guild_id.roles(&state.discord).await?,
+    };
+
+    let mut roles: Vec<&Role> = roles.values().collect();
+    roles.sort_by_key(|r| (std::cmp::Reverse(r.position), r.id.0));
+
+    Ok(Json(roles.into_iter().map(RoleDto::from).collect()))
+}
+
+/// /api/guilds/:guild_id/emojis
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild_id}/emojis",
+    params(("guild_id" = String, Path, description = "Guild snowflake")),
+    responses(
+        (status = 200, description = "The guild's custom emojis by name", body = [EmojiDto]),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_guild_emojis(
+    State(state): State<AppState>,
+    ApiPath(guild_id): ApiPath<u64>,
+) -> Result<Json<Vec<EmojiDto>>, ApiError> {
+    let guild_id = GuildId(guild_id);
+    let emojis: Vec<Emoji> = match state.discord.cache.guild(guild_id) {
+        Some(guild) => guild.emojis.into_values().collect(),
+        None => guild_id.emojis(&state.discord).await?,
     };
 
-    
-    for guild in guilds {
-        if bot_guild_ids.contains(guild.id.as_u64()) {
-            in_guilds.push(guild);
+    let mut emojis: Vec<EmojiDto> = emojis.iter().map(EmojiDto::from).collect();
+    emojis.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(Json(emojis))
+}
+
+/// /api/guilds/:guild_id/channels
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild_id}/channels",
+    params(("guild_id" = String, Path, description = "Guild snowflake")),
+    responses(
+        (status = 200, description = "The guild's text channels", body = [ChannelSummary]),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_guild_channels(
+    State(state): State<AppState>,
+    ApiPath(guild_id): ApiPath<u64>,
+) -> Result<Json<Vec<ChannelSummary>>, ApiError> {
+    let guild_id = GuildId(guild_id);
+    let ctx = state.discord;
+
+    let channels = guild_id
+        .channels(&ctx)
+        .await?
+        .values()
+        .filter(|v| matches!(v.kind, ChannelType::Text))
+        .map(ChannelSummary::from)
+        .collect();
+
+    Ok(Json(channels))
+}
+
+/// The guilds the bot is in and the caller may manage, read from the gateway's cache so nothing
+/// the client sends is trusted and guilds past Discord's first page aren't lost, any body is ignored
+#[utoipa::path(
+    post,
+    path = "/api/guilds/filter",
+    responses(
+        (status = 200, description = "The guilds the caller can manage through the bot, by name", body = [GuildSummary]),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn post_filter_guilds(State(state): State<AppState>, Extension(principal): Extension<ApiPrincipal>) -> Result<Json<Vec<GuildSummary>>, ApiError> {
+    let mut in_guilds: Vec<GuildSummary> = Vec::new();
+
+    for guild_id in state.discord.cache.guilds() {
+        if !principal.can_access(&state, guild_id).await {
+            continue;
+        }
+        //unavailable guilds have nothing cached to show
+        if let Some(guild) = state.discord.cache.guild(guild_id) {
+            in_guilds.push(GuildSummary {
+                id: guild.id.to_string(),
+                name: guild.name,
+                icon: guild.icon,
+            });
         }
     }
+    in_guilds.sort_by(|a, b| a.name.cmp(&b.name));
 
     Ok(Json(in_guilds))
 }
\ No newline at end of file
@@ -0,0 +1,86 @@
+//! Probes and metrics for whatever runs the bot. Outside `/api`, they need no credentials and
+//! aren't part of the OpenAPI document.
+
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use serenity::gateway::ConnectionStage;
+
+use crate::{
+    rest_api::entry::AppState,
+    utils::{database::migrations_applied, metrics::ShardStatus},
+};
+
+/// What `/readyz` checked, `ready` only when all of them passed
+#[derive(Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub gateway: bool,
+    pub database: bool,
+    pub migrations: bool,
+}
+
+async fn shard_statuses(state: &AppState) -> Vec<ShardStatus> {
+    let shard_manager = match &state.shard_manager {
+        Some(shard_manager) => shard_manager,
+        None => return Vec::new(),
+    };
+
+    let runners = shard_manager.lock().await.runners.clone();
+    let runners = runners.lock().await;
+
+    let mut shards: Vec<ShardStatus> = runners
+        .iter()
+        .map(|(id, runner)| ShardStatus {
+            id: id.0,
+            connected: runner.stage == ConnectionStage::Connected,
+            latency: runner.latency,
+        })
+        .collect();
+    shards.sort_by_key(|s| s.id);
+
+    shards
+}
+
+/// /healthz, answers as long as the process does
+pub async fn get_healthz() -> &'static str {
+    "ok"
+}
+
+/// /readyz, 503 until the gateway is connected and the database is reachable and migrated
+pub async fn get_readyz(State(state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let shards = shard_statuses(&state).await;
+    let gateway = !shards.is_empty() && shards.iter().all(|s| s.connected);
+
+    let (database, migrations) = match state.metrics.time_query("readiness", migrations_applied(&state.db_pool)).await {
+        Ok(applied) => (true, applied),
+        Err(_) => (false, false),
+    };
+
+    let ready = gateway && database && migrations;
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+
+    (
+        status,
+        Json(Readiness {
+            ready,
+            gateway,
+            database,
+            migrations,
+        }),
+    )
+}
+
+/// /metrics in Prometheus' text format
+pub async fn get_metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let shards = shard_statuses(&state).await;
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(&shards),
+    )
+}
@@ -0,0 +1,443 @@
+//! Incoming integrations: CI and other services post into a channel through `/api/hooks/:id`
+//! with a token of their own, the message is the integration's template with their variables
+//! filled in.
+
+use std::collections::{BTreeSet, HashMap};
+
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    Extension, Json,
+};
+use chrono::Utc;
+use serde_json::Value;
+use serenity::model::{
+    id::{ChannelId, GuildId},
+    Timestamp,
+};
+use sqlx::{query, Pool, Sqlite};
+
+use crate::rest_api::{
+    auth::{channel_guild, hash_token, random_token, ApiPrincipal},
+    dto::{CreatedIntegrationDto, IncomingMessageRequest, IntegrationDto, IntegrationRequest, MessageDto},
+    entry::AppState,
+    error::{ApiError, ApiErrorCode, FieldError},
+    extract::{ApiJson, ApiPath},
+    routes::channels::MessageDetails,
+};
+
+//makes leaked tokens easy to spot in logs and secret scanners, like API keys
+const TOKEN_PREFIX: &str = "zangra_hook_";
+const MAX_NAME: usize = 100;
+
+/// Fills in every `{{name}}`, collecting the names it has no value for. Text that only looks
+/// like a variable, e.g. an unclosed `{{`, is left as it is.
+fn render_text(text: &str, variables: &HashMap<String, String>, missing: &mut BTreeSet<String>) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+
+        let name = after.find("}}").map(|end| (after[..end].trim(), end));
+        match name {
+            Some((name, end)) if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-') => {
+                match variables.get(name) {
+                    Some(value) => rendered.push_str(value),
+                    None => {
+                        missing.insert(name.to_string());
+                    }
+                }
+                rest = &after[end + 2..];
+            }
+            _ => {
+                rendered.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+//templates are only stored after they deserialized, so this is a bug rather than bad input
+fn template_error(why: serde_json::Error) -> ApiError {
+    ApiError::new(ApiErrorCode::Internal, format!("Unreadable template: {why}"))
+}
+
+fn render_value(value: &mut Value, variables: &HashMap<String, String>, missing: &mut BTreeSet<String>) {
+    match value {
+        Value::String(text) => *text = render_text(text, variables, missing),
+        Value::Array(items) => items.iter_mut().for_each(|v| render_value(v, variables, missing)),
+        Value::Object(fields) => fields.values_mut().for_each(|v| render_value(v, variables, missing)),
+        _ => {}
+    }
+}
+
+/// The template with the call's variables in it, 422 listing every variable it needs and didn't get
+fn render_template(template: &MessageDetails, variables: &HashMap<String, String>) -> Result<MessageDetails, ApiError> {
+    let mut value = serde_json::to_value(template).map_err(template_error)?;
+    let mut missing = BTreeSet::new();
+    render_value(&mut value, variables, &mut missing);
+
+    if !missing.is_empty() {
+        let errors = missing
+            .into_iter()
+            .map(|name| FieldError::new(format!("variables.{name}"), "Used by the template but not given"))
+            .collect();
+        return Err(ApiError::invalid_fields(errors));
+    }
+
+    serde_json::from_value(value).map_err(template_error)
+}
+
+/// The channel to post to and the trimmed name, or every problem with the request at once
+async fn check_request(state: &AppState, guild_id: GuildId, request: &IntegrationRequest) -> Result<(ChannelId, String), ApiError> {
+    let mut errors = Vec::new();
+
+    let name = request.name.trim().to_string();
+    if name.is_empty() {
+        errors.push(FieldError::new("name", "Must not be empty"));
+    } else if name.chars().count() > MAX_NAME {
+        errors.push(FieldError::new("name", format!("Must be at most {MAX_NAME} characters")));
+    }
+
+    let channel_id = match request.channel_id.parse::<u64>() {
+        Ok(channel_id) if channel_guild(state, ChannelId(channel_id)).await == Some(guild_id) => Some(ChannelId(channel_id)),
+        Ok(_) => {
+            errors.push(FieldError::new("channel_id", "Not a channel of this guild"));
+            None
+        }
+        Err(_) => {
+            errors.push(FieldError::new("channel_id", "Not a snowflake"));
+            None
+        }
+    };
+
+    //checked as written, a template has to make a valid message before any variables are in it
+    request.template.validate("template.", &mut errors);
+
+    match channel_id {
+        Some(channel_id) if errors.is_empty() => Ok((channel_id, name)),
+        _ => Err(ApiError::invalid_fields(errors)),
+    }
+}
+
+fn timestamp(unix: i64) -> String {
+    Timestamp::from_unix_timestamp(unix).map(|t| t.to_string()).unwrap_or_default()
+}
+
+async fn guild_integration(pool: &Pool<Sqlite>, guild_id: GuildId, integration_id: i64) -> Result<IntegrationDto, ApiError> {
+    let guild_id_i64 = guild_id.0 as i64;
+    let row = query!(
+        "SELECT IntegrationId, ChannelId, Name, Template, CreatedBy, CreatedAt, LastUsedAt FROM Integration
+        WHERE IntegrationId = ? AND GuildId = ?",
+        integration_id,
+        guild_id_i64
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| ApiError::not_found(format!("There is no integration {integration_id} in this guild")))?;
+
+    Ok(IntegrationDto {
+        id: row.IntegrationId,
+        name: row.Name,
+        channel_id: row.ChannelId.to_string(),
+        template: serde_json::from_str(&row.Template).map_err(template_error)?,
+        created_by: row.CreatedBy,
+        created_at: timestamp(row.CreatedAt),
+        last_used_at: row.LastUsedAt.map(timestamp),
+    })
+}
+
+/// /api/guilds/:guild_id/integrations
+#[utoipa::path(
+    get,
+    path = "/api/guilds/{guild_id}/integrations",
+    params(("guild_id" = String, Path, description = "Guild snowflake")),
+    responses(
+        (status = 200, description = "The guild's integrations, without their tokens", body = [IntegrationDto]),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_integrations(
+    State(state): State<AppState>,
+    ApiPath(guild_id): ApiPath<u64>,
+) -> Result<Json<Vec<IntegrationDto>>, ApiError> {
+    let guild_id_i64 = guild_id as i64;
+    let ids = query!("SELECT IntegrationId FROM Integration WHERE GuildId = ? ORDER BY IntegrationId", guild_id_i64)
+        .fetch_all(&state.db_pool)
+        .await?;
+
+    let mut integrations = Vec::new();
+    for row in ids {
+        integrations.push(guild_integration(&state.db_pool, GuildId(guild_id), row.IntegrationId).await?);
+    }
+
+    Ok(Json(integrations))
+}
+
+/// /api/guilds/:guild_id/integrations
+#[utoipa::path(
+    post,
+    path = "/api/guilds/{guild_id}/integrations",
+    params(("guild_id" = String, Path, description = "Guild snowflake")),
+    request_body = IntegrationRequest,
+    responses(
+        (status = 201, description = "The integration and its token, which isn't shown again", body = CreatedIntegrationDto),
+        (status = 400, description = "Malformed body", body = ApiErrorBody),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+        (status = 422, description = "Invalid name, channel or template, listed in `details.errors`", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn post_integration(
+    State(state): State<AppState>,
+    Extension(principal): Extension<ApiPrincipal>,
+    ApiPath(guild_id): ApiPath<u64>,
+    ApiJson(request): ApiJson<IntegrationRequest>,
+) -> Result<(StatusCode, Json<CreatedIntegrationDto>), ApiError> {
+    let (channel_id, name) = check_request(&state, GuildId(guild_id), &request).await?;
+
+    let token = format!("{TOKEN_PREFIX}{}", random_token());
+    let token_hash = hash_token(&token);
+    let template = serde_json::to_string(&request.template).map_err(template_error)?;
+    let guild_id_i64 = guild_id as i64;
+    let channel_id_i64 = channel_id.0 as i64;
+    let created_at = Utc::now().timestamp();
+
+    let integration_id = query!(
+        "INSERT INTO Integration (GuildId, ChannelId, Name, TokenHash, Template, CreatedBy, CreatedAt) VALUES (?, ?, ?, ?, ?, ?, ?)",
+        guild_id_i64,
+        channel_id_i64,
+        name,
+        token_hash,
+        template,
+        principal.name,
+        created_at
+    )
+    .execute(&state.db_pool)
+    .await?
+    .last_insert_rowid();
+
+    let integration = guild_integration(&state.db_pool, GuildId(guild_id), integration_id).await?;
+
+    Ok((StatusCode::CREATED, Json(CreatedIntegrationDto { token, integration })))
+}
+
+/// /api/guilds/:guild_id/integrations/:integration_id
+#[utoipa::path(
+    put,
+    path = "/api/guilds/{guild_id}/integrations/{integration_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("integration_id" = i64, Path, description = "Integration number"),
+    ),
+    request_body = IntegrationRequest,
+    responses(
+        (status = 200, description = "The changed integration, its token stays the same", body = IntegrationDto),
+        (status = 400, description = "Malformed body", body = ApiErrorBody),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+        (status = 404, description = "No such integration in this guild", body = ApiErrorBody),
+        (status = 422, description = "Invalid name, channel or template, listed in `details.errors`", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn put_integration(
+    State(state): State<AppState>,
+    ApiPath((guild_id, integration_id)): ApiPath<(u64, i64)>,
+    ApiJson(request): ApiJson<IntegrationRequest>,
+) -> Result<Json<IntegrationDto>, ApiError> {
+    guild_integration(&state.db_pool, GuildId(guild_id), integration_id).await?;
+    let (channel_id, name) = check_request(&state, GuildId(guild_id), &request).await?;
+
+    let template = serde_json::to_string(&request.template).map_err(template_error)?;
+    let channel_id_i64 = channel_id.0 as i64;
+    query!(
+        "UPDATE Integration SET ChannelId = ?, Name = ?, Template = ? WHERE IntegrationId = ?",
+        channel_id_i64,
+        name,
+        template,
+        integration_id
+    )
+    .execute(&state.db_pool)
+    .await?;
+
+    Ok(Json(guild_integration(&state.db_pool, GuildId(guild_id), integration_id).await?))
+}
+
+/// /api/guilds/:guild_id/integrations/:integration_id
+#[utoipa::path(
+    delete,
+    path = "/api/guilds/{guild_id}/integrations/{integration_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("integration_id" = i64, Path, description = "Integration number"),
+    ),
+    responses(
+        (status = 204, description = "Deleted, its token stops working"),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+        (status = 404, description = "No such integration in this guild", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn delete_integration(
+    State(state): State<AppState>,
+    ApiPath((guild_id, integration_id)): ApiPath<(u64, i64)>,
+) -> Result<StatusCode, ApiError> {
+    let guild_id_i64 = guild_id as i64;
+    let result = query!(
+        "DELETE FROM Integration WHERE IntegrationId = ? AND GuildId = ?",
+        integration_id,
+        guild_id_i64
+    )
+    .execute(&state.db_pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::not_found(format!("There is no integration {integration_id} in this guild")));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// /api/hooks/:integration_id, authenticated by the integration's own token rather than an API key
+#[utoipa::path(
+    post,
+    path = "/api/hooks/{integration_id}",
+    params(("integration_id" = i64, Path, description = "Integration number")),
+    request_body = IncomingMessageRequest,
+    responses(
+        (status = 201, description = "The template was rendered and posted", body = MessageDto),
+        (status = 400, description = "Malformed body", body = ApiErrorBody),
+        (status = 401, description = "Not this integration's token", body = ApiErrorBody),
+        (status = 403, description = "The bot can't post in the integration's channel", body = ApiErrorBody),
+        (status = 422, description = "Missing variables, or the rendered message is invalid, listed in `details.errors`", body = ApiErrorBody),
+        (status = 429, description = "Too many messages, see `Retry-After`", body = ApiErrorBody),
+    ),
+    security(("integration_token" = []))
+)]
+pub async fn post_incoming_message(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ApiPath(integration_id): ApiPath<i64>,
+    ApiJson(request): ApiJson<IncomingMessageRequest>,
+) -> Result<(StatusCode, Json<MessageDto>), ApiError> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .ok_or_else(ApiError::unauthorized)?;
+    let token_hash = hash_token(token);
+
+    //taken before the token is checked so guessing it is limited as well
+    if let Err(wait) = state.rate_limiter.take(&format!("integration:{integration_id}")) {
+        return Err(ApiError::rate_limited(wait.as_secs() + 1));
+    }
+
+    let lookup = query!(
+        "SELECT ChannelId, Template FROM Integration WHERE IntegrationId = ? AND TokenHash = ?",
+        integration_id,
+        token_hash
+    )
+    .fetch_optional(&state.db_pool);
+    //an unknown id answers the same as a wrong token
+    let row = state
+        .metrics
+        .time_query("integration_lookup", lookup)
+        .await?
+        .ok_or_else(ApiError::unauthorized)?;
+
+    let template: MessageDetails = serde_json::from_str(&row.Template).map_err(template_error)?;
+    let (content, embeds) = render_template(&template, &request.variables)?.build()?;
+
+    let message = ChannelId(row.ChannelId as u64)
+        .send_message(&state.discord, |m| {
+            if let Some(content) = content {
+                m.content(content);
+            }
+            //variables come from outside the guild, a `@everyone` in one mustn't ping anybody
+            m.allowed_mentions(|a| a.empty_parse());
+            m.set_embeds(embeds)
+        })
+        .await?;
+
+    let now = Utc::now().timestamp();
+    query!("UPDATE Integration SET LastUsedAt = ? WHERE IntegrationId = ?", now, integration_id)
+        .execute(&state.db_pool)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(message.into())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap};
+
+    use serde_json::json;
+
+    use super::{render_template, render_text};
+    use crate::rest_api::{error::ApiErrorCode, routes::channels::MessageDetails};
+
+    #[test]
+    fn variables_are_filled_in() {
+        let variables = HashMap::from([("repo".to_string(), "zangra".to_string()), ("status".to_string(), "passed".to_string())]);
+        let mut missing = BTreeSet::new();
+
+        assert_eq!(render_text("{{repo}}: {{ status }}", &variables, &mut missing), "zangra: passed");
+        assert_eq!(render_text("{{ not a variable }} {{", &variables, &mut missing), "{{ not a variable }} {{");
+        assert!(missing.is_empty());
+
+        assert_eq!(render_text("{{repo}} by {{actor}}", &variables, &mut missing), "zangra by ");
+        assert_eq!(missing, BTreeSet::from(["actor".to_string()]));
+    }
+
+    #[test]
+    fn templates_need_every_variable_and_a_valid_result() {
+        let template: MessageDetails = serde_json::from_value(json!({
+            "content": "{{repo}} by {{actor}}",
+            "embed_title": "{{repo}} #{{run}}",
+            "embed_color": "{{colour}}",
+        }))
+        .unwrap();
+
+        let variables = HashMap::from([("repo".to_string(), "zangra".to_string())]);
+        let missing = render_template(&template, &variables).unwrap_err();
+        assert_eq!(missing.code, ApiErrorCode::ValidationFailed);
+        assert_eq!(
+            missing.details.unwrap(),
+            json!({"errors": [
+                {"field": "variables.actor", "message": "Used by the template but not given"},
+                {"field": "variables.colour", "message": "Used by the template but not given"},
+                {"field": "variables.run", "message": "Used by the template but not given"},
+            ]})
+        );
+
+        //every variable is there, but what they make isn't something Discord takes
+        let variables = HashMap::from([
+            ("repo".to_string(), "zangra".to_string()),
+            ("actor".to_string(), "x".repeat(2000)),
+            ("run".to_string(), "42".to_string()),
+            ("colour".to_string(), "green".to_string()),
+        ]);
+        let rendered = render_template(&template, &variables).unwrap();
+        assert_eq!(serde_json::to_value(&rendered).unwrap()["embed_title"], "zangra #42");
+        let invalid = rendered.build().unwrap_err();
+        assert_eq!(invalid.code, ApiErrorCode::ValidationFailed);
+        let fields: Vec<&str> = invalid.details.as_ref().unwrap()["errors"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["field"].as_str().unwrap())
+            .collect();
+        assert_eq!(fields, ["content", "embed_color"]);
+    }
+}
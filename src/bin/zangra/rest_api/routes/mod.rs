@@ -1,4 +1,8 @@
 pub mod channels;
 pub mod embed_edit;
 pub mod embed;
-pub mod guilds;
\ No newline at end of file
+pub mod events;
+pub mod guilds;
+pub mod health;
+pub mod integrations;
+pub mod role_selector;
\ No newline at end of file
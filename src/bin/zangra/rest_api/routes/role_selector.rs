@@ -0,0 +1,156 @@
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use serenity::model::id::{ChannelId, GuildId, MessageId};
+
+use crate::{
+    rest_api::{
+        auth::ApiPrincipal,
+        dto::{RoleSelectorDto, RoleSelectorRequest},
+        entry::AppState,
+        error::ApiError,
+        extract::{ApiJson, ApiPath},
+    },
+    utils::role_selectors::{
+        delete_role_selector_message, edit_role_selector_message, post_role_selector_message, saved_role_selector,
+        RoleSelector, RoleSelectorError,
+    },
+};
+
+/// /api/roleselector/:guild_id/:channel_id/:message_id
+#[utoipa::path(
+    get,
+    path = "/api/roleselector/{guild_id}/{channel_id}/{message_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+        ("message_id" = String, Path, description = "Message snowflake"),
+    ),
+    responses(
+        (status = 200, description = "The selector", body = RoleSelectorDto),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+        (status = 404, description = "Not a role selector in this channel", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn get_role_selector(
+    State(state): State<AppState>,
+    ApiPath((guild_id, channel_id, message_id)): ApiPath<(u64, u64, u64)>,
+) -> Result<Json<RoleSelectorDto>, ApiError> {
+    let channel_id = ChannelId(channel_id);
+    let saved = match saved_role_selector(&state.db_pool, GuildId(guild_id), MessageId(message_id)).await? {
+        Some(saved) if saved.channel_id == channel_id => saved,
+        _ => return Err(RoleSelectorError::UnknownSelector.into()),
+    };
+
+    let message = channel_id.message(&state.discord, message_id).await?;
+
+    Ok(Json(RoleSelectorDto::new(message, saved.options, saved.max_selections, saved.style)))
+}
+
+/// /api/roleselector/:guild_id/:channel_id
+#[utoipa::path(
+    post,
+    path = "/api/roleselector/{guild_id}/{channel_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+    ),
+    request_body = RoleSelectorRequest,
+    responses(
+        (status = 201, description = "Selector posted and saved", body = RoleSelectorDto),
+        (status = 400, description = "Malformed body", body = ApiErrorBody),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild, or the bot can't post there", body = ApiErrorBody),
+        (status = 422, description = "Invalid roles or settings, listed in `details.errors`. Roles must be below the bot's highest role and, for dashboard users, their own. Roles with moderation permissions need an administrator.", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn post_role_selector(
+    State(state): State<AppState>,
+    Extension(principal): Extension<ApiPrincipal>,
+    ApiPath((guild_id, channel_id)): ApiPath<(u64, u64)>,
+    ApiJson(request): ApiJson<RoleSelectorRequest>,
+) -> Result<(StatusCode, Json<RoleSelectorDto>), ApiError> {
+    let selector = RoleSelector::try_from(request)?;
+    let message = post_role_selector_message(
+        &state.discord,
+        &state.db_pool,
+        GuildId(guild_id),
+        ChannelId(channel_id),
+        principal.member(),
+        &selector,
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(RoleSelectorDto::new(message, selector.options, selector.max_selections, selector.style)),
+    ))
+}
+
+/// /api/roleselector/:guild_id/:channel_id/:message_id
+#[utoipa::path(
+    put,
+    path = "/api/roleselector/{guild_id}/{channel_id}/{message_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+        ("message_id" = String, Path, description = "Message snowflake"),
+    ),
+    request_body = RoleSelectorRequest,
+    responses(
+        (status = 200, description = "The replaced selector", body = RoleSelectorDto),
+        (status = 400, description = "Malformed body", body = ApiErrorBody),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild, or not the bot's message", body = ApiErrorBody),
+        (status = 404, description = "Not a role selector in this channel", body = ApiErrorBody),
+        (status = 422, description = "Invalid roles or settings, listed in `details.errors`, with the same role rules as creating one", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn put_role_selector(
+    State(state): State<AppState>,
+    Extension(principal): Extension<ApiPrincipal>,
+    ApiPath((guild_id, channel_id, message_id)): ApiPath<(u64, u64, u64)>,
+    ApiJson(request): ApiJson<RoleSelectorRequest>,
+) -> Result<Json<RoleSelectorDto>, ApiError> {
+    let selector = RoleSelector::try_from(request)?;
+    let message = edit_role_selector_message(
+        &state.discord,
+        &state.db_pool,
+        GuildId(guild_id),
+        ChannelId(channel_id),
+        MessageId(message_id),
+        principal.member(),
+        &selector,
+    )
+    .await?;
+
+    Ok(Json(RoleSelectorDto::new(message, selector.options, selector.max_selections, selector.style)))
+}
+
+/// /api/roleselector/:guild_id/:channel_id/:message_id
+#[utoipa::path(
+    delete,
+    path = "/api/roleselector/{guild_id}/{channel_id}/{message_id}",
+    params(
+        ("guild_id" = String, Path, description = "Guild snowflake"),
+        ("channel_id" = String, Path, description = "Channel snowflake"),
+        ("message_id" = String, Path, description = "Message snowflake"),
+    ),
+    responses(
+        (status = 204, description = "Deleted"),
+        (status = 401, description = "Not authenticated", body = ApiErrorBody),
+        (status = 403, description = "Not allowed in this guild", body = ApiErrorBody),
+        (status = 404, description = "Not a role selector in this channel", body = ApiErrorBody),
+    ),
+    security(("api_key" = []), ("session" = []))
+)]
+pub async fn delete_role_selector(
+    State(state): State<AppState>,
+    ApiPath((guild_id, channel_id, message_id)): ApiPath<(u64, u64, u64)>,
+) -> Result<StatusCode, ApiError> {
+    delete_role_selector_message(&state.discord, &state.db_pool, GuildId(guild_id), ChannelId(channel_id), MessageId(message_id)).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,986 @@
+//! Contract tests, every route is called against a mock Discord and its response checked against
+//! the schema `/api/openapi.json` publishes for that status.
+
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use reqwest::Method;
+use serde_json::{json, Value};
+use serenity::{
+    cache::Cache,
+    http::HttpBuilder,
+    model::{
+        event::GuildCreateEvent,
+        id::{ChannelId, GuildId, UserId},
+    },
+};
+use sqlx::{
+    query,
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+};
+
+use crate::{
+    config::{ApiSettings, ConfiguredApiKey},
+    rest_api::{
+        auth::hash_token,
+        entry::{router, AppState, Discord},
+        layers::RateLimiter,
+    },
+    utils::{
+        guild_events::{GuildEventKind, GuildEvents},
+        shutdown::Shutdown,
+        message_store::MessageStore,
+        metrics::Metrics,
+    },
+};
+
+const KEY: &str = "test-key";
+//only allowed in a guild the mock channels aren't in
+const OTHER_GUILD_KEY: &str = "other-guild-key";
+
+const GUILD: u64 = 1;
+const CHANNEL: u64 = 10;
+const MESSAGE: u64 = 100;
+//saved in the Embed table but deleted in Discord
+const DELETED_MESSAGE: u64 = 404;
+//saved in the Embed table but the bot can't read it
+const HIDDEN_MESSAGE: u64 = 403;
+
+//ids for posted messages, shared by every test's mock
+static NEXT_MESSAGE: AtomicU64 = AtomicU64::new(1000);
+
+fn default_embeds() -> Value {
+    json!([{"title": "Rules", "type": "rich", "color": 16711680, "fields": [{"name": "1", "value": "Be nice", "inline": false}]}])
+}
+
+/// Messages are authored by user 0, the id an empty cache thinks the bot has, so they can be edited
+fn message(channel_id: &str, message_id: &str, embeds: Value) -> Value {
+    json!({
+        "id": message_id,
+        "channel_id": channel_id,
+        "attachments": [],
+        "author": {"id": "0", "username": "Zangra", "discriminator": "0001", "avatar": null, "bot": true},
+        "content": "",
+        "edited_timestamp": null,
+        "embeds": embeds,
+        "type": 0,
+        "mention_everyone": false,
+        "mention_roles": [],
+        "mentions": [],
+        "pinned": false,
+        "timestamp": "2026-10-19T09:00:00+00:00",
+        "tts": false
+    })
+}
+
+fn role(role_id: &str, name: &str, managed: bool) -> Value {
+    json!({
+        "id": role_id, "name": name, "color": 0, "hoist": false, "managed": managed,
+        "mentionable": false, "permissions": "0", "position": 1
+    })
+}
+
+//the bot has Artist, which can view channels and send messages, and its own Zangra role on top.
+//Moderator can manage messages and Admin is above the bot.
+fn roles() -> Value {
+    let mut artist = role("21", "Artist", false);
+    artist["permissions"] = json!("3072");
+    artist["color"] = json!(0xff0000);
+    artist["position"] = json!(2);
+    let mut zangra = role("23", "Zangra", true);
+    zangra["position"] = json!(5);
+    let mut moderator = role("24", "Moderator", false);
+    moderator["permissions"] = json!("8192");
+    moderator["position"] = json!(3);
+    let mut admin = role("25", "Admin", false);
+    admin["permissions"] = json!("8");
+    admin["position"] = json!(6);
+    json!([
+        role(&GUILD.to_string(), "@everyone", false),
+        artist,
+        role("22", "Writer", false),
+        zangra,
+        moderator,
+        admin,
+    ])
+}
+
+fn emoji() -> Value {
+    json!({"id": "31", "name": "wave", "roles": [], "animated": false, "available": true, "managed": false, "require_colons": true})
+}
+
+fn channel(channel_id: &str, kind: u8, name: &str) -> Value {
+    json!({"id": channel_id, "guild_id": GUILD.to_string(), "type": kind, "name": name})
+}
+
+fn unknown_message() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({"code": 10008, "message": "Unknown Message"})),
+    )
+        .into_response()
+}
+
+fn message_response(channel_id: String, message_id: String, embeds: Value) -> Response {
+    if message_id == DELETED_MESSAGE.to_string() {
+        return unknown_message();
+    }
+    if message_id == HIDDEN_MESSAGE.to_string() {
+        return (StatusCode::FORBIDDEN, Json(json!({"code": 50001, "message": "Missing Access"}))).into_response();
+    }
+
+    Json(message(&channel_id, &message_id, embeds)).into_response()
+}
+
+/// Serenity sends message edits as multipart with the JSON in a `payload_json` part
+fn payload_json(body: &str) -> Value {
+    let part = body
+        .split_once("name=\"payload_json\"")
+        .and_then(|(_, rest)| rest.split_once("\r\n\r\n"))
+        .and_then(|(_, rest)| rest.split_once("\r\n--"))
+        .map_or(body, |(json, _)| json);
+
+    serde_json::from_str(part).unwrap_or(Value::Null)
+}
+
+/// Stands in for the parts of Discord's API the routes use, served where serenity's proxy expects
+async fn start_mock_discord() -> SocketAddr {
+    let app = Router::new()
+        .route(
+            "/api/v10/channels/:channel_id",
+            get(|Path(channel_id): Path<String>| async move { Json(channel(&channel_id, 0, "rules")) }),
+        )
+        .route(
+            "/api/v10/channels/:channel_id/messages",
+            post(|Path(channel_id): Path<String>, Json(body): Json<Value>| async move {
+                let message_id = NEXT_MESSAGE.fetch_add(1, Ordering::Relaxed).to_string();
+                let mut posted = message(&channel_id, &message_id, body["embeds"].clone());
+                if let Some(content) = body.get("content").filter(|c| c.is_string()) {
+                    posted["content"] = content.clone();
+                }
+                Json(posted)
+            }),
+        )
+        .route(
+            "/api/v10/channels/:channel_id/messages/:message_id",
+            get(|Path((c, m)): Path<(String, String)>| async move { message_response(c, m, default_embeds()) })
+                .patch(|Path((c, m)): Path<(String, String)>, body: String| async move {
+                    message_response(c, m, payload_json(&body)["embeds"].clone())
+                })
+                .delete(|Path((_, m)): Path<(String, String)>| async move {
+                    if m == DELETED_MESSAGE.to_string() {
+                        unknown_message()
+                    } else {
+                        StatusCode::NO_CONTENT.into_response()
+                    }
+                }),
+        )
+        .route(
+            "/api/v10/guilds/:guild_id/channels",
+            get(|| async {
+                let mut rules = channel(&CHANNEL.to_string(), 0, "rules");
+                rules["parent_id"] = json!("12");
+                Json(json!([
+                    rules,
+                    channel("11", 2, "Voice"),
+                    channel("12", 4, "Text Channels"),
+                    channel("13", 5, "news"),
+                ]))
+            }),
+        )
+        .route("/api/v10/guilds/:guild_id/roles", get(|| async { Json(roles()) }))
+        .route(
+            "/api/v10/guilds/:guild_id",
+            get(|| async {
+                Json(json!({
+                    "id": GUILD.to_string(), "name": "Home", "icon": null, "owner_id": "5", "afk_channel_id": null,
+                    "afk_timeout": 300, "default_message_notifications": 0, "emojis": [emoji()], "features": [],
+                    "mfa_level": 0, "roles": roles(), "splash": null, "discovery_splash": null, "system_channel_id": null,
+                    "system_channel_flags": 0, "rules_channel_id": null, "public_updates_channel_id": null,
+                    "verification_level": 0, "description": null, "premium_tier": 0, "premium_subscription_count": 0,
+                    "banner": null, "vanity_url_code": null, "nsfw_level": 0, "stickers": [], "explicit_content_filter": 0,
+                    "preferred_locale": "en-US", "approximate_member_count": 42, "approximate_presence_count": 7
+                }))
+            }),
+        )
+        .route("/api/v10/guilds/:guild_id/emojis", get(|| async { Json(json!([emoji()])) }))
+        .route(
+            "/api/v10/guilds/:guild_id/threads/active",
+            get(|| async {
+                let mut thread = channel("14", 11, "questions");
+                thread["parent_id"] = json!(CHANNEL.to_string());
+                thread["thread_metadata"] = json!({"archived": false, "auto_archive_duration": 1440, "archive_timestamp": "2026-10-19T09:00:00+00:00", "locked": false});
+                Json(json!({"threads": [thread], "members": []}))
+            }),
+        )
+        .route(
+            "/api/v10/guilds/:guild_id/members/:user_id",
+            get(|| async {
+                Json(json!({
+                    "user": {"id": "0", "username": "Zangra", "discriminator": "0001", "avatar": null, "bot": true},
+                    "roles": ["21", "23"], "joined_at": "2026-10-19T09:00:00+00:00", "deaf": false, "mute": false
+                }))
+            }),
+        )
+        .route(
+            "/api/v10/users/@me",
+            get(|| async {
+                Json(json!({"id": "0", "username": "Zangra", "discriminator": "0001", "avatar": null, "bot": true, "mfa_enabled": false}))
+            }),
+        )
+        .route(
+            "/api/v10/users/@me/guilds",
+            get(|| async {
+                Json(json!([{"id": GUILD.to_string(), "name": "Home", "icon": null, "owner": false, "permissions": "0"}]))
+            }),
+        );
+
+    let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    addr
+}
+
+/// The whole API on a random port, backed by the mock Discord and a migrated in-memory database.
+/// The returned state is the one the routes use, e.g. to publish events.
+async fn start_api() -> (String, AppState) {
+    start_api_with(ApiSettings::default()).await
+}
+
+async fn start_api_with(settings: ApiSettings) -> (String, AppState) {
+    let discord_addr = start_mock_discord().await;
+    let http = HttpBuilder::new("token")
+        .proxy(format!("http://{discord_addr}/"))
+        .unwrap()
+        .ratelimiter_disabled(true)
+        .build();
+
+    //one connection, every connection to :memory: is its own database
+    let db_pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(SqliteConnectOptions::from_str("sqlite::memory:").unwrap())
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations").run(&db_pool).await.unwrap();
+
+    for embed_id in [MESSAGE as i64, DELETED_MESSAGE as i64, HIDDEN_MESSAGE as i64] {
+        let guild_id = GUILD as i64;
+        let channel_id = CHANNEL as i64;
+        query!(
+            "INSERT INTO Embed (EmbedId, GuildId, ChannelId) VALUES (?, ?, ?)",
+            embed_id,
+            guild_id,
+            channel_id
+        )
+        .execute(&db_pool)
+        .await
+        .unwrap();
+    }
+
+    let state = AppState {
+        discord: Discord {
+            cache: Arc::new(Cache::new()),
+            http: Arc::new(http),
+        },
+        db_pool,
+        configured_keys: Arc::new(vec![
+            ConfiguredApiKey {
+                name: "tests".to_string(),
+                key_hash: hash_token(KEY),
+                guilds: None,
+            },
+            ConfiguredApiKey {
+                name: "other guild".to_string(),
+                key_hash: hash_token(OTHER_GUILD_KEY),
+                guilds: Some(vec![2]),
+            },
+        ]),
+        oauth: None,
+        http_client: reqwest::Client::new(),
+        message_store: MessageStore::new(),
+        events: GuildEvents::new(),
+        rate_limiter: RateLimiter::new(settings.rate_limit_burst, settings.rate_limit_per_minute),
+        address_rate_limiter: RateLimiter::new(settings.address_rate_limit_burst, settings.address_rate_limit_per_minute),
+        settings: Arc::new(settings),
+        shutdown: Shutdown::new(),
+        metrics: Metrics::new(),
+        shard_manager: None,
+    };
+
+    let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(router(state.clone()).into_make_service_with_connect_info::<SocketAddr>());
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    (format!("http://{addr}"), state)
+}
+
+/// Checks `value` against an OpenAPI 3.0 schema, only the keywords utoipa generates are supported
+fn validate(doc: &Value, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference.trim_start_matches("#/components/schemas/");
+        let resolved = &doc["components"]["schemas"][name];
+        if resolved.is_null() {
+            return Err(format!("{at}: unresolved {reference}"));
+        }
+        return validate(doc, resolved, value, at);
+    }
+
+    if value.is_null() && schema.get("nullable") == Some(&Value::Bool(true)) {
+        return Ok(());
+    }
+
+    if let Some(all_of) = schema.get("allOf").and_then(Value::as_array) {
+        for sub_schema in all_of {
+            validate(doc, sub_schema, value, at)?;
+        }
+    }
+
+    for keyword in ["oneOf", "anyOf"] {
+        if let Some(any_of) = schema.get(keyword).and_then(Value::as_array) {
+            if !any_of.iter().any(|s| validate(doc, s, value, at).is_ok()) {
+                return Err(format!("{at}: {value} matches nothing in {keyword}"));
+            }
+        }
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            return Err(format!("{at}: {value} isn't one of {allowed:?}"));
+        }
+    }
+
+    let matches_type = match schema.get("type").and_then(Value::as_str) {
+        None => true,
+        Some("object") => value.is_object(),
+        Some("array") => value.is_array(),
+        Some("string") => value.is_string(),
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some("number") => value.is_number(),
+        Some("boolean") => value.is_boolean(),
+        Some(other) => return Err(format!("{at}: unsupported type {other}")),
+    };
+    if !matches_type {
+        return Err(format!("{at}: {value} isn't of type {}", schema["type"]));
+    }
+
+    if let Some(object) = value.as_object() {
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    return Err(format!("{at}: missing required {key}"));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, property) in object {
+            let property_at = format!("{at}.{key}");
+            match (properties.and_then(|p| p.get(key)), schema.get("additionalProperties")) {
+                (Some(property_schema), _) => validate(doc, property_schema, property, &property_at)?,
+                (None, Some(Value::Bool(false))) => return Err(format!("{property_at} isn't documented")),
+                (None, Some(additional)) if additional.is_object() => {
+                    validate(doc, additional, property, &property_at)?
+                }
+                (None, _) => {}
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (i, item) in array.iter().enumerate() {
+            validate(doc, items, item, &format!("{at}[{i}]"))?;
+        }
+    }
+
+    Ok(())
+}
+
+struct Contract {
+    base: String,
+    doc: Value,
+    state: AppState,
+    client: reqwest::Client,
+    //documented operations called so far, as (method, path template)
+    covered: HashSet<(String, String)>,
+}
+
+impl Contract {
+    async fn new() -> Contract {
+        //the routes are called more often than the default burst allows
+        let (base, state) = start_api_with(ApiSettings {
+            rate_limit_burst: 1000,
+            address_rate_limit_burst: 1000,
+            ..ApiSettings::default()
+        })
+        .await;
+        let client = reqwest::Client::new();
+        let response = client.get(format!("{base}/api/openapi.json")).send().await.unwrap();
+        assert_eq!(response.status(), 200, "the document needs no authentication");
+        let doc: Value = response.json().await.unwrap();
+
+        Contract {
+            base,
+            doc,
+            state,
+            client,
+            covered: HashSet::new(),
+        }
+    }
+
+    /// Calls `template` with its parameters filled in from `path`, asserts the status and that the
+    /// body matches the schema documented for it
+    async fn call(&mut self, method: Method, template: &str, path: &str, key: Option<&str>, body: Option<Value>, expected: u16) -> Value {
+        let operation = &self.doc["paths"][template][method.as_str().to_lowercase()];
+        assert!(operation.is_object(), "{method} {template} isn't documented");
+
+        let mut request = self.client.request(method.clone(), format!("{}{path}", self.base));
+        if let Some(key) = key {
+            request = request.bearer_auth(key);
+        }
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.unwrap();
+        let status = response.status().as_u16();
+        let text = response.text().await.unwrap();
+        assert_eq!(status, expected, "{method} {path} answered {text}");
+
+        let documented = &operation["responses"][status.to_string()];
+        assert!(documented.is_object(), "{method} {template} doesn't document {status}");
+
+        self.covered.insert((method.as_str().to_lowercase(), template.to_string()));
+
+        match documented["content"]["application/json"]["schema"].as_object() {
+            Some(_) => {
+                let value: Value = serde_json::from_str(&text).unwrap_or_else(|_| panic!("{method} {path} didn't answer JSON: {text}"));
+                let schema = &documented["content"]["application/json"]["schema"];
+                if let Err(why) = validate(&self.doc, schema, &value, "body") {
+                    panic!("{method} {path} {status} breaks the contract: {why}\n{value}");
+                }
+                value
+            }
+            None => {
+                assert!(text.is_empty(), "{method} {path} {status} should have no body, got {text}");
+                Value::Null
+            }
+        }
+    }
+
+    /// Opens the event stream at `path`, asserting it's documented as one
+    async fn stream(&mut self, template: &str, path: &str, key: &str) -> reqwest::Response {
+        let documented = &self.doc["paths"][template]["get"]["responses"]["200"]["content"];
+        assert!(documented["text/event-stream"].is_object(), "GET {template} isn't documented as an event stream");
+
+        let response = self.client.get(format!("{}{path}", self.base)).bearer_auth(key).send().await.unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "text/event-stream");
+        self.covered.insert(("get".to_string(), template.to_string()));
+
+        response
+    }
+
+    fn documented_operations(&self) -> HashSet<(String, String)> {
+        self.doc["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| {
+                item.as_object()
+                    .unwrap()
+                    .keys()
+                    .map(move |method| (method.clone(), path.clone()))
+            })
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn every_route_honours_its_documented_schema() {
+    let mut api = Contract::new().await;
+    let mut events = api.stream("/api/guilds/{guild_id}/events", &format!("/api/guilds/{GUILD}/events"), KEY).await;
+    let embed = json!({"title": "Rules", "color": 16711680, "fields": [{"name": "1", "value": "Be nice"}]});
+    let details = json!({"embed_title": "Rules", "embed_color": "#ff0000"});
+    let single = format!("/api/embed/{GUILD}/{CHANNEL}/{MESSAGE}");
+
+    let me = api.call(Method::GET, "/api/auth/me", "/api/auth/me", Some(KEY), None, 200).await;
+    assert_eq!(me["name"], "tests");
+
+    let from_form = api.call(Method::POST, "/api/channels/{channel_id}/embed", &format!("/api/channels/{CHANNEL}/embed"), Some(KEY), Some(details.clone()), 201).await;
+    let full_form = api
+        .call(Method::POST, "/api/channels/{channel_id}/embed", &format!("/api/channels/{CHANNEL}/embed"), Some(KEY), Some(json!({
+            "content": "Read these first",
+            "embeds": [
+                {
+                    "embed_title": "Rules",
+                    "embed_fields": [{"name": "1", "value": "Be nice", "inline": true}, {"name": "2", "value": "No spam"}],
+                    "embed_footer_text": "Moderators",
+                    "embed_footer_icon_url": "https://example.com/mod.png",
+                    "embed_author_name": "Zangra",
+                    "embed_timestamp": "2026-10-19T09:00:00Z",
+                },
+                {"embed_description": "Appeals go to the moderators"},
+            ],
+        })), 201)
+        .await;
+    assert_eq!(full_form["content"], "Read these first");
+    assert_eq!(full_form["embeds"].as_array().unwrap().len(), 2);
+    assert_eq!(full_form["embeds"][0]["fields"][0]["inline"], true);
+    assert_eq!(full_form["embeds"][0]["footer"]["text"], "Moderators");
+    assert_eq!(full_form["embeds"][0]["author"]["name"], "Zangra");
+
+    let guilds = api.call(Method::POST, "/api/guilds/filter", "/api/guilds/filter", Some(KEY), None, 200).await;
+    assert_eq!(guilds, json!([]), "the cache is empty");
+
+    let channels = api.call(Method::GET, "/api/guilds/{guild_id}/channels", &format!("/api/guilds/{GUILD}/channels"), Some(KEY), None, 200).await;
+
+    let guild = api.call(Method::GET, "/api/guilds/{guild_id}", &format!("/api/guilds/{GUILD}"), Some(KEY), None, 200).await;
+    assert_eq!((&guild["member_count"], &guild["online_count"]), (&json!(42), &json!(7)));
+    let categories = api.call(Method::GET, "/api/guilds/{guild_id}/categories", &format!("/api/guilds/{GUILD}/categories"), Some(KEY), None, 200).await;
+    let names: Vec<&Value> = categories[0]["channels"].as_array().unwrap().iter().map(|c| &c["name"]).collect();
+    assert_eq!(names, [&json!("news"), &json!("Voice")], "voice channels come after text ones");
+    let rules = &categories[1]["channels"][0];
+    assert_eq!((&categories[1]["name"], &rules["kind"]), (&json!("Text Channels"), &json!("text")));
+    assert_eq!(rules["bot_permissions"], json!(["Send Messages", "View Channel"]));
+    assert_eq!((&rules["threads"][0]["kind"], &rules["threads"][0]["bot_permission_bits"]), (&json!("public_thread"), &json!("3072")));
+    let roles = api.call(Method::GET, "/api/guilds/{guild_id}/roles", &format!("/api/guilds/{GUILD}/roles"), Some(KEY), None, 200).await;
+    assert_eq!(roles.as_array().unwrap().len(), 6);
+    let emojis = api.call(Method::GET, "/api/guilds/{guild_id}/emojis", &format!("/api/guilds/{GUILD}/emojis"), Some(KEY), None, 200).await;
+    assert_eq!(emojis[0]["url"], "https://cdn.discordapp.com/emojis/31.png");
+    assert_eq!(channels, json!([{"id": CHANNEL.to_string(), "name": "rules"}]));
+
+    api.call(Method::POST, "/api/embededit/{channel_id}/{message_id}", &format!("/api/embededit/{CHANNEL}/{MESSAGE}"), Some(KEY), Some(details), 200).await;
+
+    let posted = api.call(Method::POST, "/api/embed/{guild_id}/{channel_id}", &format!("/api/embed/{GUILD}/{CHANNEL}"), Some(KEY), Some(embed.clone()), 201).await;
+    assert_eq!(posted["embeds"][0]["fields"][0]["inline"], false);
+
+    let template = "/api/embed/{guild_id}/{channel_id}/{message_id}";
+    let fetched = api.call(Method::GET, template, &single, Some(KEY), None, 200).await;
+    assert_eq!(fetched["id"], MESSAGE.to_string());
+    api.call(Method::PUT, template, &single, Some(KEY), Some(embed), 200).await;
+    api.call(Method::DELETE, template, &single, Some(KEY), None, 204).await;
+
+    let posted_single = format!("/api/embed/{GUILD}/{CHANNEL}/{}", posted["id"].as_str().unwrap());
+    let revisions = format!("{posted_single}/revisions");
+    api.call(Method::PUT, template, &posted_single, Some(KEY), Some(json!({"title": "Rules v2"})), 200).await;
+
+    let history = api.call(Method::GET, "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions", &revisions, Some(KEY), None, 200).await;
+    assert_eq!(history.as_array().unwrap().len(), 2);
+    assert_eq!(history[0]["author_name"], "tests");
+    assert_eq!(history[1]["embeds"][0]["title"], "Rules v2");
+
+    let diff = api
+        .call(Method::GET, "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions/diff", &format!("{revisions}/diff?from=1"), Some(KEY), None, 200)
+        .await;
+    assert_eq!(diff["to"], 2);
+    let title = diff["changes"].as_array().unwrap().iter().find(|c| c["path"] == "embeds[0].title").unwrap();
+    assert_eq!((&title["before"], &title["after"]), (&json!("Rules"), &json!("Rules v2")));
+
+    let rolled_back = api
+        .call(Method::POST, "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions/{revision}/rollback", &format!("{revisions}/1/rollback"), Some(KEY), None, 200)
+        .await;
+    assert_eq!(rolled_back["embeds"][0]["title"], "Rules");
+    let history = api.call(Method::GET, "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions", &revisions, Some(KEY), None, 200).await;
+    assert_eq!(history.as_array().unwrap().len(), 3, "a rollback is a revision too");
+
+    let all = api.call(Method::GET, "/api/embed/all/{guild_id}", &format!("/api/embed/all/{GUILD}"), Some(KEY), None, 200).await;
+    let ids: Vec<&Value> = all["messages"][CHANNEL.to_string()].as_array().unwrap().iter().map(|m| &m["id"]).collect();
+    assert_eq!(ids, [&from_form["id"], &full_form["id"], &posted["id"]], "deleted messages lose their row, posted ones are saved");
+    assert_eq!(all["errors"].as_array().unwrap().len(), 1, "a message that can't be read doesn't fail the rest");
+    assert_eq!((&all["errors"][0]["message_id"], &all["errors"][0]["error"]), (&json!(HIDDEN_MESSAGE.to_string()), &json!("MissingPermissions")));
+    assert_eq!(all["next"], Value::Null);
+
+    let channel_all = format!("/api/embed/all/{GUILD}/{CHANNEL}");
+    let first = api.call(Method::GET, "/api/embed/all/{guild_id}/{channel_id}", &format!("{channel_all}?limit=2"), Some(KEY), None, 200).await;
+    assert_eq!(first["next"], json!(from_form["id"]), "the hidden message and the first post fill the page");
+    let rest = api
+        .call(Method::GET, "/api/embed/all/{guild_id}/{channel_id}", &format!("{channel_all}?after={}", first["next"].as_str().unwrap()), Some(KEY), None, 200)
+        .await;
+    let ids: Vec<&Value> = rest["messages"][CHANNEL.to_string()].as_array().unwrap().iter().map(|m| &m["id"]).collect();
+    assert_eq!(ids, [&full_form["id"], &posted["id"]]);
+
+    let selectors = format!("/api/roleselector/{GUILD}/{CHANNEL}");
+    let selector = api
+        .call(Method::POST, "/api/roleselector/{guild_id}/{channel_id}", &selectors, Some(KEY), Some(json!({
+            "content": "Pick your roles",
+            "options": [{"role_id": "21", "label": "Artist", "description": "Draws things"}, {"role_id": "22", "label": "Writer"}],
+        })), 201)
+        .await;
+    assert_eq!((&selector["max_selections"], &selector["style"]), (&json!(2), &json!("menu")), "defaults to a menu allowing every role");
+    let selector_template = "/api/roleselector/{guild_id}/{channel_id}/{message_id}";
+    let selector_single = format!("{selectors}/{}", selector["message"]["id"].as_str().unwrap());
+    let fetched = api.call(Method::GET, selector_template, &selector_single, Some(KEY), None, 200).await;
+    assert_eq!(fetched["options"], selector["options"]);
+    let replaced = api
+        .call(Method::PUT, selector_template, &selector_single, Some(KEY), Some(json!({
+            "options": [{"role_id": "22", "label": "Writer"}],
+            "max_selections": 1,
+            "style": "buttons",
+        })), 200)
+        .await;
+    assert_eq!(replaced["style"], "buttons");
+    let fetched = api.call(Method::GET, selector_template, &selector_single, Some(KEY), None, 200).await;
+    assert_eq!(fetched["options"], json!([{"role_id": "22", "label": "Writer", "description": null}]));
+    api.call(Method::DELETE, selector_template, &selector_single, Some(KEY), None, 204).await;
+    api.call(Method::GET, selector_template, &selector_single, Some(KEY), None, 404).await;
+
+    let integrations = format!("/api/guilds/{GUILD}/integrations");
+    let created = api
+        .call(Method::POST, "/api/guilds/{guild_id}/integrations", &integrations, Some(KEY), Some(json!({
+            "name": "CI",
+            "channel_id": CHANNEL.to_string(),
+            "template": {"content": "{{repo}} built", "embed_title": "{{repo}} #{{run}}", "embed_description": "Status: {{ status }}", "embed_color": "#00ff00"},
+        })), 201)
+        .await;
+    let token = created["token"].as_str().unwrap().to_string();
+    assert!(token.starts_with("zangra_hook_"));
+    let integration_single = format!("{integrations}/{}", created["integration"]["id"]);
+    let hook = format!("/api/hooks/{}", created["integration"]["id"]);
+    let hooked = api
+        .call(Method::POST, "/api/hooks/{integration_id}", &hook, Some(&token), Some(json!({"variables": {"repo": "zangra", "run": "42", "status": "passed"}})), 201)
+        .await;
+    assert_eq!(hooked["content"], "zangra built");
+    assert_eq!((&hooked["embeds"][0]["title"], &hooked["embeds"][0]["description"]), (&json!("zangra #42"), &json!("Status: passed")));
+    let changed = api
+        .call(Method::PUT, "/api/guilds/{guild_id}/integrations/{integration_id}", &integration_single, Some(KEY), Some(json!({
+            "name": "Deploys",
+            "channel_id": CHANNEL.to_string(),
+            "template": {"content": "{{repo}} deployed"},
+        })), 200)
+        .await;
+    assert_eq!(changed["name"], "Deploys");
+    let listed = api.call(Method::GET, "/api/guilds/{guild_id}/integrations", &integrations, Some(KEY), None, 200).await;
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert!(listed[0]["last_used_at"].is_string());
+    assert!(listed[0].get("token").is_none(), "tokens are only shown once");
+    api.call(Method::DELETE, "/api/guilds/{guild_id}/integrations/{integration_id}", &integration_single, Some(KEY), None, 204).await;
+    api.call(Method::POST, "/api/hooks/{integration_id}", &hook, Some(&token), Some(json!({"variables": {"repo": "zangra"}})), 401).await;
+
+    api.state.events.publish(GuildId(2), GuildEventKind::MemberJoined { user_id: UserId(50), name: "Elsewhere".to_string() });
+    api.state.events.publish(GuildId(GUILD), GuildEventKind::VoiceMoved { user_id: UserId(51), from: None, to: Some(ChannelId(11)) });
+    let chunk = String::from_utf8(events.chunk().await.unwrap().unwrap().to_vec()).unwrap();
+    assert!(chunk.starts_with("event:voice_moved\n"), "other guilds' events aren't sent: {chunk}");
+    let data = chunk.lines().find_map(|l| l.strip_prefix("data:")).unwrap();
+    let event: Value = serde_json::from_str(data).unwrap();
+    let schema = &api.doc["paths"]["/api/guilds/{guild_id}/events"]["get"]["responses"]["200"]["content"]["text/event-stream"]["schema"];
+    validate(&api.doc, schema, &event, "event").unwrap();
+    assert_eq!(event["event"], json!({"type": "voice_moved", "user_id": "51", "from": null, "to": "11"}));
+    api.state.shutdown.trigger();
+    assert!(events.chunk().await.unwrap().is_none(), "streams end on shutdown so the server can drain");
+
+    assert_eq!(api.covered, api.documented_operations(), "every documented operation is exercised");
+}
+
+#[tokio::test]
+async fn errors_honour_their_documented_schema() {
+    let mut api = Contract::new().await;
+    let single = format!("/api/embed/{GUILD}/{CHANNEL}/{MESSAGE}");
+    let template = "/api/embed/{guild_id}/{channel_id}/{message_id}";
+
+    let unauthorized = api.call(Method::GET, template, &single, None, None, 401).await;
+    assert_eq!(unauthorized["error"], "Unauthorized");
+    api.call(Method::GET, template, &single, Some("wrong-key"), None, 401).await;
+
+    let forbidden = api.call(Method::GET, template, &single, Some(OTHER_GUILD_KEY), None, 403).await;
+    assert_eq!(forbidden["error"], "Forbidden");
+    api.call(Method::GET, "/api/guilds/{guild_id}/events", &format!("/api/guilds/{GUILD}/events"), Some(OTHER_GUILD_KEY), None, 403).await;
+
+    let missing = api.call(Method::GET, template, &format!("/api/embed/{GUILD}/{CHANNEL}/{DELETED_MESSAGE}"), Some(KEY), None, 404).await;
+    assert_eq!(missing["details"]["discord_code"], 10008);
+
+    let bad_colour = api
+        .call(Method::POST, "/api/channels/{channel_id}/embed", &format!("/api/channels/{CHANNEL}/embed"), Some(KEY), Some(json!({"embed_color": "red"})), 422)
+        .await;
+    assert_eq!(bad_colour["error"], "ValidationFailed");
+
+    let fields: Vec<Value> = (0..26).map(|i| json!({"name": i.to_string(), "value": "x".repeat(if i == 3 { 1025 } else { 1 })})).collect();
+    let too_much = api
+        .call(Method::POST, "/api/channels/{channel_id}/embed", &format!("/api/channels/{CHANNEL}/embed"), Some(KEY), Some(json!({"embeds": [{"embed_title": "Rules", "embed_fields": fields}]})), 422)
+        .await;
+    let paths: Vec<&Value> = too_much["details"]["errors"].as_array().unwrap().iter().map(|e| &e["field"]).collect();
+    assert_eq!(paths, [&json!("embeds[0].embed_fields"), &json!("embeds[0].embed_fields[3].value")]);
+
+    let bad_urls = api
+        .call(Method::POST, "/api/channels/{channel_id}/embed", &format!("/api/channels/{CHANNEL}/embed"), Some(KEY), Some(json!({"embeds": [{
+            "embed_title": "Rules",
+            "embed_title_url": "javascript:alert(1)",
+            "embed_image_url": "not a url",
+            "embed_thumbnail_url": "attachment://rules.png",
+            "embed_author_name": "Zangra",
+            "embed_author_icon_url": "https://cdn.example.com/zangra.png",
+        }]})), 422)
+        .await;
+    let paths: Vec<&Value> = bad_urls["details"]["errors"].as_array().unwrap().iter().map(|e| &e["field"]).collect();
+    assert_eq!(paths, [&json!("embeds[0].embed_title_url"), &json!("embeds[0].embed_image_url")]);
+
+    let bad_timestamp = api
+        .call(Method::PUT, template, &single, Some(KEY), Some(json!({"timestamp": "yesterday"})), 422)
+        .await;
+    assert_eq!(bad_timestamp["error"], "ValidationFailed");
+
+    let bad_selector = api
+        .call(Method::POST, "/api/roleselector/{guild_id}/{channel_id}", &format!("/api/roleselector/{GUILD}/{CHANNEL}"), Some(KEY), Some(json!({
+            "options": [{"role_id": GUILD.to_string(), "label": "Everyone"}, {"role_id": "23", "label": "Bot"}, {"role_id": "99", "label": ""}],
+            "max_selections": 4,
+        })), 422)
+        .await;
+    let paths: Vec<&Value> = bad_selector["details"]["errors"].as_array().unwrap().iter().map(|e| &e["field"]).collect();
+    assert_eq!(
+        paths,
+        [&json!("options[0].role_id"), &json!("options[1].role_id"), &json!("options[2].role_id"), &json!("options[2].label"), &json!("max_selections")]
+    );
+    api.call(Method::PUT, "/api/roleselector/{guild_id}/{channel_id}/{message_id}", &format!("/api/roleselector/{GUILD}/{CHANNEL}/{MESSAGE}"), Some(KEY), Some(json!({
+        "options": [{"role_id": "21", "label": "Artist"}],
+    })), 404).await;
+    //an API key isn't an administrator, and nobody gets roles above the bot's
+    let escalating = api
+        .call(Method::POST, "/api/roleselector/{guild_id}/{channel_id}", &format!("/api/roleselector/{GUILD}/{CHANNEL}"), Some(KEY), Some(json!({
+            "options": [{"role_id": "22", "label": "Writer"}, {"role_id": "24", "label": "Moderator"}, {"role_id": "25", "label": "Admin"}],
+        })), 422)
+        .await;
+    let messages: Vec<&Value> = escalating["details"]["errors"].as_array().unwrap().iter().map(|e| &e["message"]).collect();
+    assert_eq!(
+        messages,
+        [&json!("Moderator has moderation permissions, only administrators can hand it out"), &json!("Admin isn't below the bot's highest role")]
+    );
+
+    let revisions = format!("/api/embed/{GUILD}/{CHANNEL}/{MESSAGE}/revisions");
+    let unknown = api
+        .call(Method::POST, "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions/{revision}/rollback", &format!("{revisions}/7/rollback"), Some(KEY), None, 404)
+        .await;
+    assert_eq!(unknown["error"], "NotFound");
+    api.call(Method::GET, "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions/diff", &format!("{revisions}/diff"), Some(KEY), None, 400).await;
+
+    let integrations = format!("/api/guilds/{GUILD}/integrations");
+    let bad_integration = api
+        .call(Method::POST, "/api/guilds/{guild_id}/integrations", &integrations, Some(KEY), Some(json!({
+            "name": " ",
+            "channel_id": "rules",
+            "template": {"embed_title": "{{repo}}", "embed_color": "{{colour}}"},
+        })), 422)
+        .await;
+    let paths: Vec<&Value> = bad_integration["details"]["errors"].as_array().unwrap().iter().map(|e| &e["field"]).collect();
+    assert_eq!(paths, [&json!("name"), &json!("channel_id"), &json!("template.embed_color")]);
+    let created = api
+        .call(Method::POST, "/api/guilds/{guild_id}/integrations", &integrations, Some(KEY), Some(json!({
+            "name": "CI",
+            "channel_id": CHANNEL.to_string(),
+            "template": {"content": "{{repo}} is {{status}}"},
+        })), 201)
+        .await;
+    let hook = format!("/api/hooks/{}", created["integration"]["id"]);
+    let token = created["token"].as_str().unwrap();
+    api.call(Method::POST, "/api/hooks/{integration_id}", &hook, Some(KEY), Some(json!({})), 401).await;
+    api.call(Method::POST, "/api/hooks/{integration_id}", "/api/hooks/99", Some(token), Some(json!({})), 401).await;
+    let missing = api
+        .call(Method::POST, "/api/hooks/{integration_id}", &hook, Some(token), Some(json!({"variables": {"repo": "zangra"}})), 422)
+        .await;
+    assert_eq!(missing["details"]["errors"][0]["field"], "variables.status");
+    api.call(Method::PUT, "/api/guilds/{guild_id}/integrations/{integration_id}", &format!("{integrations}/99"), Some(KEY), Some(json!({
+        "name": "CI",
+        "channel_id": CHANNEL.to_string(),
+        "template": {"content": "Hi"},
+    })), 404).await;
+
+    let malformed = api
+        .call(Method::POST, "/api/embed/{guild_id}/{channel_id}", &format!("/api/embed/{GUILD}/{CHANNEL}"), Some(KEY), Some(json!({"fields": "none"})), 400)
+        .await;
+    assert_eq!(malformed["error"], "InvalidRequest");
+}
+
+#[tokio::test]
+async fn document_describes_the_routes_and_security() {
+    let api = Contract::new().await;
+
+    assert!(api.doc["openapi"].as_str().unwrap().starts_with("3."));
+    assert_eq!(api.doc["components"]["securitySchemes"]["api_key"]["scheme"], "bearer");
+    assert_eq!(api.doc["components"]["securitySchemes"]["session"]["name"], "zangra_session");
+    assert_eq!(api.doc["components"]["securitySchemes"]["integration_token"]["scheme"], "bearer");
+
+    let documented: HashSet<String> = api.doc["paths"].as_object().unwrap().keys().cloned().collect();
+    let routes = [
+        "/api/auth/me",
+        "/api/channels/{channel_id}/embed",
+        "/api/guilds/filter",
+        "/api/guilds/{guild_id}",
+        "/api/guilds/{guild_id}/channels",
+        "/api/guilds/{guild_id}/categories",
+        "/api/guilds/{guild_id}/roles",
+        "/api/guilds/{guild_id}/emojis",
+        "/api/guilds/{guild_id}/events",
+        "/api/guilds/{guild_id}/integrations",
+        "/api/guilds/{guild_id}/integrations/{integration_id}",
+        "/api/hooks/{integration_id}",
+        "/api/embededit/{channel_id}/{message_id}",
+        "/api/embed/{guild_id}/{channel_id}",
+        "/api/embed/{guild_id}/{channel_id}/{message_id}",
+        "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions",
+        "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions/diff",
+        "/api/embed/{guild_id}/{channel_id}/{message_id}/revisions/{revision}/rollback",
+        "/api/embed/all/{guild_id}",
+        "/api/embed/all/{guild_id}/{channel_id}",
+        "/api/roleselector/{guild_id}/{channel_id}",
+        "/api/roleselector/{guild_id}/{channel_id}/{message_id}",
+    ];
+    assert_eq!(documented, routes.iter().map(|r| r.to_string()).collect());
+
+    //snowflakes are strings everywhere, JavaScript loses precision on them as numbers
+    let message = &api.doc["components"]["schemas"]["MessageDto"]["properties"];
+    assert_eq!(message["id"]["type"], "string");
+    assert_eq!(message["channel_id"]["type"], "string");
+}
+
+#[tokio::test]
+async fn clients_are_limited() {
+    let (base, _) = start_api_with(ApiSettings {
+        cors_origins: vec!["https://dashboard.example.com".to_string()],
+        rate_limit_burst: 2,
+        rate_limit_per_minute: 1,
+        max_body_bytes: 1024,
+        ..ApiSettings::default()
+    })
+    .await;
+    let client = reqwest::Client::new();
+    let doc: Value = client.get(format!("{base}/api/openapi.json")).send().await.unwrap().json().await.unwrap();
+    let error_body = json!({"$ref": "#/components/schemas/ApiErrorBody"});
+
+    let too_large = client
+        .post(format!("{base}/api/channels/{CHANNEL}/embed"))
+        .bearer_auth(KEY)
+        .json(&json!({"embed_description": "x".repeat(2000)}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(too_large.status(), 413);
+    let body: Value = too_large.json().await.unwrap();
+    validate(&doc, &error_body, &body, "body").unwrap();
+    assert_eq!(body["error"], "PayloadTooLarge");
+
+    //the refused body took one of the two tokens
+    let me = format!("{base}/api/auth/me");
+    assert_eq!(client.get(&me).bearer_auth(KEY).send().await.unwrap().status(), 200);
+    let limited = client.get(&me).bearer_auth(KEY).send().await.unwrap();
+    assert_eq!(limited.status(), 429);
+    assert_eq!(limited.headers()["retry-after"], "60");
+    let body: Value = limited.json().await.unwrap();
+    validate(&doc, &error_body, &body, "body").unwrap();
+    assert_eq!(body["error"], "RateLimited");
+    assert_eq!(client.get(&me).bearer_auth(OTHER_GUILD_KEY).send().await.unwrap().status(), 200, "every key has its own bucket");
+
+    let preflight = |origin: &'static str| {
+        client
+            .request(Method::OPTIONS, &me)
+            .header("origin", origin)
+            .header("access-control-request-method", "GET")
+            .header("access-control-request-headers", "authorization")
+            .send()
+    };
+    let allowed = preflight("https://dashboard.example.com").await.unwrap();
+    assert_eq!(allowed.headers()["access-control-allow-origin"], "https://dashboard.example.com");
+    assert_eq!(allowed.headers()["access-control-allow-credentials"], "true");
+    let refused = preflight("https://elsewhere.example.com").await.unwrap();
+    assert!(refused.headers().get("access-control-allow-origin").is_none());
+}
+
+#[tokio::test]
+async fn failed_attempts_are_limited() {
+    let (base, _) = start_api_with(ApiSettings {
+        rate_limit_burst: 2,
+        rate_limit_per_minute: 1,
+        address_rate_limit_burst: 5,
+        address_rate_limit_per_minute: 1,
+        ..ApiSettings::default()
+    })
+    .await;
+    let client = reqwest::Client::new();
+
+    //guessing an integration's token spends the integration's tokens
+    let hook = format!("{base}/api/hooks/99");
+    for status in [401, 401, 429] {
+        let response = client.post(&hook).bearer_auth("zangra_hook_guess").json(&json!({})).send().await.unwrap();
+        assert_eq!(response.status(), status);
+    }
+
+    //every request spends the address' tokens, wrong keys and logins included
+    let me = format!("{base}/api/auth/me");
+    assert_eq!(client.get(&me).bearer_auth("wrong-key").send().await.unwrap().status(), 401);
+    assert_eq!(client.get(&me).bearer_auth(KEY).send().await.unwrap().status(), 200);
+    let limited = client.get(format!("{base}/api/auth/login")).send().await.unwrap();
+    assert_eq!(limited.status(), 429);
+    assert_eq!(limited.headers()["retry-after"], "60");
+
+    //probes aren't limited, a limited address is still checked on
+    for probe in ["healthz", "metrics"] {
+        assert_eq!(client.get(format!("{base}/{probe}")).send().await.unwrap().status(), 200, "{probe}");
+    }
+}
+
+#[tokio::test]
+async fn guild_picker_reads_the_cache() {
+    let (base, state) = start_api().await;
+    for (guild_id, name) in [(GUILD, "Home"), (2, "Elsewhere")] {
+        let mut guild: GuildCreateEvent = serde_json::from_value(json!({
+            "id": guild_id.to_string(), "name": name, "icon": "a1b2", "owner_id": "5", "afk_channel_id": null,
+            "afk_timeout": 300, "default_message_notifications": 0, "emojis": [], "features": [], "mfa_level": 0,
+            "roles": [], "splash": null, "discovery_splash": null, "system_channel_id": null, "system_channel_flags": 0,
+            "rules_channel_id": null, "public_updates_channel_id": null, "verification_level": 0, "description": null,
+            "premium_tier": 0, "premium_subscription_count": 0, "banner": null, "vanity_url_code": null, "nsfw_level": 0,
+            "stickers": [], "explicit_content_filter": 0, "preferred_locale": "en-US", "joined_at": "2026-10-19T09:00:00+00:00",
+            "large": false, "member_count": 1, "members": [], "channels": [], "presences": [], "voice_states": [], "threads": []
+        }))
+        .unwrap();
+        state.discord.cache.update(&mut guild);
+    }
+    let client = reqwest::Client::new();
+    let filter = format!("{base}/api/guilds/filter");
+
+    //whatever the client claims is ignored
+    let response = client.post(&filter).bearer_auth(KEY).json(&json!([{"id": "3", "name": "Forged", "icon": null}])).send().await.unwrap();
+    let guilds: Value = response.json().await.unwrap();
+    assert_eq!(
+        guilds,
+        json!([
+            {"id": "2", "name": "Elsewhere", "icon": "a1b2"},
+            {"id": GUILD.to_string(), "name": "Home", "icon": "a1b2"},
+        ])
+    );
+
+    let guilds: Value = client.post(&filter).bearer_auth(OTHER_GUILD_KEY).send().await.unwrap().json().await.unwrap();
+    assert_eq!(guilds, json!([{"id": "2", "name": "Elsewhere", "icon": "a1b2"}]));
+}
+
+#[tokio::test]
+async fn probes_need_no_authentication() {
+    let (base, _) = start_api().await;
+    let client = reqwest::Client::new();
+
+    let health = client.get(format!("{base}/healthz")).send().await.unwrap();
+    assert_eq!((health.status().as_u16(), health.text().await.unwrap()), (200, "ok".to_string()));
+
+    let ready = client.get(format!("{base}/readyz")).send().await.unwrap();
+    assert_eq!(ready.status(), 503, "there's no gateway connection");
+    let checks: Value = ready.json().await.unwrap();
+    assert_eq!(checks, json!({"ready": false, "gateway": false, "database": true, "migrations": true}));
+
+    client.get(format!("{base}/api/auth/me")).bearer_auth("wrong-key").send().await.unwrap();
+    let metrics = client.get(format!("{base}/metrics")).send().await.unwrap();
+    assert_eq!(metrics.status(), 200);
+    assert!(metrics.headers()["content-type"].to_str().unwrap().starts_with("text/plain"));
+    let text = metrics.text().await.unwrap();
+    assert!(text.contains("zangra_db_query_duration_seconds_count{query=\"api_key_lookup\"} 1"), "{text}");
+    assert!(text.contains("zangra_db_query_duration_seconds_count{query=\"readiness\"} 1"), "{text}");
+}
@@ -0,0 +1,233 @@
+use std::collections::BTreeSet;
+use std::fmt::{self, Display, Formatter};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone};
+use chrono_tz::Tz;
+
+/// Standard five field cron expression, `minute hour day-of-month month day-of-week`.
+///
+/// Fields take `*`, numbers, `a-b` ranges, `,` lists and `/n` steps, months and weekdays also
+/// take three letter names. `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are accepted.
+/// Like cron, when both day fields are restricted a day matching either one runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    expression: String,
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days_of_month: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    days_of_week: BTreeSet<u32>,
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+//how far ahead to look before deciding an expression never fires, e.g. 30 feb
+const SEARCH_DAYS: i64 = 366 * 5;
+
+impl CronSchedule {
+    pub fn parse(expression: &str) -> Result<CronSchedule> {
+        let expression = expression.trim();
+        let expanded = match expression.to_lowercase().as_str() {
+            "@hourly" => "0 * * * *".to_string(),
+            "@daily" | "@midnight" => "0 0 * * *".to_string(),
+            "@weekly" => "0 0 * * 0".to_string(),
+            "@monthly" => "0 0 1 * *".to_string(),
+            "@yearly" | "@annually" => "0 0 1 1 *".to_string(),
+            other => other.to_string(),
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(anyhow!(
+                "`{expression}` needs 5 fields: minute hour day-of-month month day-of-week"
+            ));
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7, &WEEKDAY_NAMES, 0)
+            .map_err(|why| anyhow!("day-of-week: {why}"))?;
+        //7 is another way of writing sunday
+        if days_of_week.remove(&7) {
+            days_of_week.insert(0);
+        }
+
+        Ok(CronSchedule {
+            expression: expression.to_string(),
+            minutes: parse_field(fields[0], 0, 59, &[], 0).map_err(|why| anyhow!("minute: {why}"))?,
+            hours: parse_field(fields[1], 0, 23, &[], 0).map_err(|why| anyhow!("hour: {why}"))?,
+            days_of_month: parse_field(fields[2], 1, 31, &[], 0)
+                .map_err(|why| anyhow!("day-of-month: {why}"))?,
+            months: parse_field(fields[3], 1, 12, &MONTH_NAMES, 1).map_err(|why| anyhow!("month: {why}"))?,
+            days_of_week,
+            day_of_month_restricted: !fields[2].starts_with('*'),
+            day_of_week_restricted: !fields[4].starts_with('*'),
+        })
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        if !self.months.contains(&date.month()) {
+            return false;
+        }
+
+        let dom = self.days_of_month.contains(&date.day());
+        let dow = self.days_of_week.contains(&date.weekday().num_days_from_sunday());
+
+        match (self.day_of_month_restricted, self.day_of_week_restricted) {
+            (true, true) => dom || dow,
+            (true, false) => dom,
+            (false, true) => dow,
+            (false, false) => true,
+        }
+    }
+
+    /// The first run strictly after `after`, in the wall clock of the timezone `after` is in.
+    /// Times skipped by a DST change don't run, repeated ones run once.
+    pub fn next_after(&self, after: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let timezone = after.timezone();
+        let start_date = after.date_naive();
+
+        for offset in 0..SEARCH_DAYS {
+            let date = start_date + Duration::days(offset);
+            if !self.day_matches(date) {
+                continue;
+            }
+
+            for hour in &self.hours {
+                for minute in &self.minutes {
+                    let time = NaiveTime::from_hms_opt(*hour, *minute, 0)?;
+                    if let Some(candidate) = timezone.from_local_datetime(&date.and_time(time)).earliest() {
+                        if candidate > after {
+                            return Some(candidate);
+                        }
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl Display for CronSchedule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+fn parse_value(value: &str, names: &[&str], name_offset: u32) -> Result<u32> {
+    if let Some(i) = names.iter().position(|n| *n == value) {
+        return Ok(i as u32 + name_offset);
+    }
+
+    value.parse().map_err(|_| anyhow!("`{value}` is not a number"))
+}
+
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], name_offset: u32) -> Result<BTreeSet<u32>> {
+    let mut values = BTreeSet::new();
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| anyhow!("`{step}` is not a step"))?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(anyhow!("step can't be 0"));
+        }
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start, names, name_offset)?, parse_value(end, names, name_offset)?),
+                //`5/15` means from 5 to the end in steps of 15
+                None if part.contains('/') => (parse_value(range, names, name_offset)?, max),
+                None => {
+                    let value = parse_value(range, names, name_offset)?;
+                    (value, value)
+                }
+            },
+        };
+
+        if start < min || end > max || start > end {
+            return Err(anyhow!("`{part}` is outside {min}-{max}"));
+        }
+
+        values.extend((start..=end).step_by(step as usize));
+    }
+
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use chrono::{TimeZone, Utc};
+    use chrono_tz::{Europe::Berlin, Tz};
+
+    use super::CronSchedule;
+
+    fn set(values: &[u32]) -> BTreeSet<u32> {
+        values.iter().copied().collect()
+    }
+
+    fn next(expression: &str, after: chrono::DateTime<Tz>) -> Option<chrono::DateTime<Tz>> {
+        CronSchedule::parse(expression).unwrap().next_after(after)
+    }
+
+    #[test]
+    fn fields_take_ranges_steps_lists_and_names() {
+        let schedule = CronSchedule::parse("*/15 9-17/4 1,15,20-22 jan-mar mon-fri").unwrap();
+        assert_eq!(schedule.minutes, set(&[0, 15, 30, 45]));
+        assert_eq!(schedule.hours, set(&[9, 13, 17]));
+        assert_eq!(schedule.days_of_month, set(&[1, 15, 20, 21, 22]));
+        assert_eq!(schedule.months, set(&[1, 2, 3]));
+        assert_eq!(schedule.days_of_week, set(&[1, 2, 3, 4, 5]));
+
+        assert_eq!(CronSchedule::parse("5/20 * * * *").unwrap().minutes, set(&[5, 25, 45]));
+        assert_eq!(CronSchedule::parse("0 0 * * 7").unwrap().days_of_week, set(&[0]), "7 is sunday too");
+        assert_eq!(CronSchedule::parse("@daily").unwrap().hours, set(&[0]));
+        assert_eq!(CronSchedule::parse("@weekly").unwrap().to_string(), "@weekly");
+
+        for expression in ["* * * *", "60 * * * *", "* 24 * * *", "* * 0 * *", "*/0 * * * *", "5-1 * * * *", "* * * foo *", "@often"] {
+            assert!(CronSchedule::parse(expression).is_err(), "{expression}");
+        }
+    }
+
+    #[test]
+    fn days_match_either_day_field_when_both_are_set() {
+        //a Monday
+        let now = Tz::UTC.with_ymd_and_hms(2026, 10, 19, 12, 0, 0).unwrap();
+
+        assert_eq!(next("0 12 13 * fri", now), Some(Tz::UTC.with_ymd_and_hms(2026, 10, 23, 12, 0, 0).unwrap()));
+        assert_eq!(next("0 12 13 * *", now), Some(Tz::UTC.with_ymd_and_hms(2026, 11, 13, 12, 0, 0).unwrap()));
+        assert_eq!(next("0 12 * * fri", now), Some(Tz::UTC.with_ymd_and_hms(2026, 10, 23, 12, 0, 0).unwrap()));
+        assert_eq!(next("0 0 31 * *", now), Some(Tz::UTC.with_ymd_and_hms(2026, 10, 31, 0, 0, 0).unwrap()));
+        assert_eq!(
+            next("0 0 31 * *", Tz::UTC.with_ymd_and_hms(2026, 11, 1, 0, 0, 0).unwrap()),
+            Some(Tz::UTC.with_ymd_and_hms(2026, 12, 31, 0, 0, 0).unwrap()),
+            "months without a 31st are skipped"
+        );
+        assert_eq!(next("0 12 * * *", now), Some(Tz::UTC.with_ymd_and_hms(2026, 10, 20, 12, 0, 0).unwrap()), "strictly after");
+        assert_eq!(next("0 0 30 feb *", now), None);
+    }
+
+    #[test]
+    fn dst_changes_skip_or_run_once() {
+        let at = |y, m, d, h, min| Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap().with_timezone(&Berlin);
+
+        //02:30 doesn't exist on 29 march, the clocks go from 02:00 to 03:00
+        let spring = next("30 2 * * *", at(2026, 3, 28, 12, 0)).unwrap();
+        assert_eq!(spring, at(2026, 3, 30, 0, 30));
+        assert_eq!(next("0 9 * * *", at(2026, 3, 28, 12, 0)).unwrap(), at(2026, 3, 29, 7, 0), "09:00 is summer time");
+
+        //02:30 happens twice on 25 october, only the first one runs
+        let autumn = next("30 2 * * *", at(2026, 10, 24, 12, 0)).unwrap();
+        assert_eq!(autumn, at(2026, 10, 25, 0, 30));
+        assert_eq!(next("30 2 * * *", autumn).unwrap(), at(2026, 10, 26, 1, 30));
+    }
+}
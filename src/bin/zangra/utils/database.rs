@@ -1,7 +1,12 @@
 use serenity::prelude::TypeMapKey;
+use sqlx::migrate::Migrator;
 use sqlx::sqlite::{SqlitePoolOptions, SqlitePool, SqliteConnectOptions};
+use std::collections::HashSet;
 use std::str::FromStr;
 
+/// Every migration the binary was built with
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
 pub struct DatabasePool;
 
 impl TypeMapKey for DatabasePool {
@@ -16,4 +21,14 @@ pub async fn get_sqlite_pool<S: AsRef<str>>(sqlite_url: S) -> Result<SqlitePool,
         .max_connections(20)
         .connect_with(connect_options).await?;
     Ok(pool)
-}
\ No newline at end of file
+}
+/// Whether every migration in [`MIGRATOR`] has been applied to the database
+pub async fn migrations_applied(pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+    let applied: HashSet<i64> = sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = 1")
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect();
+
+    Ok(MIGRATOR.iter().all(|m| applied.contains(&m.version)))
+}
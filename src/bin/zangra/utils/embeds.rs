@@ -0,0 +1,549 @@
+//! Every embed message the bot manages has a row in `Embed` holding the embeds as last posted, and
+//! every version it has had in `EmbedRevision`, which is kept after the message is deleted. The
+//! REST API and `/embed` go through here so that the tables and Discord stay in step.
+
+use std::fmt::{self, Display, Formatter};
+
+use chrono::Utc;
+use serenity::{
+    builder::CreateEmbed,
+    http::{CacheHttp, Http, HttpError},
+    model::{
+        channel::{Embed, Message},
+        id::{ChannelId, GuildId, MessageId, UserId},
+        ModelError,
+    },
+    Error as SerenityError,
+};
+use serde_json::Value;
+use sqlx::{query, Error as SqlxError, SqlitePool};
+use tracing::{error, info};
+
+#[derive(Debug)]
+pub enum EmbedError {
+    Discord(SerenityError),
+    Database(SqlxError),
+    /// The message isn't a saved embed, or has no such revision
+    UnknownRevision,
+    /// A stored revision no longer parses as embeds
+    InvalidJson(serde_json::Error),
+}
+
+impl Display for EmbedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EmbedError::Discord(why) => write!(f, "{why}"),
+            EmbedError::Database(why) => write!(f, "{why}"),
+            EmbedError::UnknownRevision => write!(f, "No such embed revision"),
+            EmbedError::InvalidJson(why) => write!(f, "Stored embed revision is invalid: {why}"),
+        }
+    }
+}
+
+impl std::error::Error for EmbedError {}
+
+impl From<SerenityError> for EmbedError {
+    fn from(e: SerenityError) -> Self {
+        EmbedError::Discord(e)
+    }
+}
+
+impl From<SqlxError> for EmbedError {
+    fn from(e: SqlxError) -> Self {
+        EmbedError::Database(e)
+    }
+}
+
+impl From<serde_json::Error> for EmbedError {
+    fn from(e: serde_json::Error) -> Self {
+        EmbedError::InvalidJson(e)
+    }
+}
+
+/// Who made a revision. API keys have no Discord user behind them.
+#[derive(Debug, Clone)]
+pub struct RevisionAuthor {
+    pub user_id: Option<UserId>,
+    pub name: String,
+}
+
+/// One stored version of an embed message
+#[derive(Debug, Clone)]
+pub struct EmbedRevision {
+    pub revision: i64,
+    pub author: RevisionAuthor,
+    /// Unix seconds
+    pub created_at: i64,
+    pub embeds: Vec<Embed>,
+}
+
+/// A value that differs between two revisions. `path` is like `embeds[0].fields[2].value`, a
+/// missing `before` means it was added and a missing `after` that it was removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbedChange {
+    pub path: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Discord answered 404, the message or its whole channel is gone
+pub fn is_unknown(why: &SerenityError) -> bool {
+    match why {
+        SerenityError::Http(http_error) => matches!(
+            &**http_error,
+            HttpError::UnsuccessfulRequest(response) if response.status_code.as_u16() == 404
+        ),
+        _ => false,
+    }
+}
+
+/// Discord refused for good, the channel or message is gone or the bot may not use it. Asking
+/// again won't help, unlike outages and rate limits.
+pub fn is_refused(why: &SerenityError) -> bool {
+    match why {
+        SerenityError::Http(http_error) => matches!(
+            &**http_error,
+            HttpError::UnsuccessfulRequest(response) if matches!(response.status_code.as_u16(), 403 | 404)
+        ),
+        SerenityError::Model(ModelError::InvalidPermissions(_)) => true,
+        _ => false,
+    }
+}
+
+fn embeds_json(message: &Message) -> String {
+    serde_json::to_string(&message.embeds).unwrap_or_else(|_| "[]".to_string())
+}
+
+/// Stores the message's embeds as its next revision, adding the row if it's new. The row and its
+/// history are written in one transaction.
+async fn save_revision(
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    message: &Message,
+    author: &RevisionAuthor,
+) -> Result<(), SqlxError> {
+    let embed_id = message.id.0 as i64;
+    let guild_id = guild_id.0 as i64;
+    let channel_id = message.channel_id.0 as i64;
+    let embed_json = embeds_json(message);
+    let author_id = author.user_id.map(|u| u.0 as i64);
+    let now = Utc::now().timestamp();
+
+    let mut tx = pool.begin().await?;
+
+    query!(
+        "INSERT INTO Embed (EmbedId, GuildId, ChannelId, EmbedJson, Revision, UpdatedAt) VALUES (?, ?, ?, ?, 1, ?)
+        ON CONFLICT(EmbedId) DO UPDATE SET EmbedJson = excluded.EmbedJson, Revision = Revision + 1, UpdatedAt = excluded.UpdatedAt",
+        embed_id,
+        guild_id,
+        channel_id,
+        embed_json,
+        now
+    )
+    .execute(&mut tx)
+    .await?;
+
+    query!(
+        "INSERT INTO EmbedRevision (EmbedId, GuildId, ChannelId, Revision, EmbedJson, AuthorId, AuthorName, CreatedAt)
+        SELECT EmbedId, GuildId, ChannelId, Revision, EmbedJson, ?, ?, ? FROM Embed WHERE EmbedId = ?",
+        author_id,
+        author.name,
+        now,
+        embed_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await
+}
+
+/// Posts the message and saves its embeds. Discord can't take part in a database transaction, so when
+/// saving fails the message is deleted again rather than left behind untracked.
+pub async fn post_embed_message(
+    cache_http: impl CacheHttp,
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    content: Option<String>,
+    embeds: Vec<CreateEmbed>,
+    author: &RevisionAuthor,
+) -> Result<Message, EmbedError> {
+    let message = channel_id
+        .send_message(cache_http.http(), |m| {
+            if let Some(content) = content {
+                m.content(content);
+            }
+            m.set_embeds(embeds)
+        })
+        .await?;
+
+    if let Err(why) = save_revision(pool, guild_id, &message, author).await {
+        if let Err(why) = message.delete(&cache_http).await {
+            error!("Unable to remove unsaved embed {}: {}", message.id, why);
+        }
+        return Err(why.into());
+    }
+
+    Ok(message)
+}
+
+/// Edits one of the bot's messages and saves the result as a new revision. Messages posted before
+/// the table existed get their row here. Content is left alone when `None`. When saving fails the
+/// old message is put back.
+#[allow(clippy::too_many_arguments)]
+pub async fn edit_embed_message(
+    cache_http: impl CacheHttp,
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    content: Option<String>,
+    embeds: Vec<CreateEmbed>,
+    author: &RevisionAuthor,
+) -> Result<Message, EmbedError> {
+    let mut message = channel_id.message(cache_http.http(), message_id).await?;
+    let previous_content = message.content.clone();
+    let previous: Vec<CreateEmbed> = message.embeds.iter().cloned().map(CreateEmbed::from).collect();
+
+    message
+        .edit(&cache_http, |m| {
+            if let Some(content) = content {
+                m.content(content);
+            }
+            m.set_embeds(embeds)
+        })
+        .await?;
+
+    if let Err(why) = save_revision(pool, guild_id, &message, author).await {
+        if let Err(why) = message
+            .edit(&cache_http, |m| m.content(previous_content).set_embeds(previous))
+            .await
+        {
+            error!("Unable to restore embed {} after a failed save: {}", message.id, why);
+        }
+        return Err(why.into());
+    }
+
+    Ok(message)
+}
+
+/// Deletes the message, then its row. A message already deleted in Discord only loses its row,
+/// and if removing the row fails the next [`reconcile_embeds`] does it.
+pub async fn delete_embed_message(
+    http: impl AsRef<Http>,
+    pool: &SqlitePool,
+    channel_id: ChannelId,
+    message_id: MessageId,
+) -> Result<(), EmbedError> {
+    if let Err(why) = channel_id.delete_message(&http, message_id).await {
+        //unknown to both Discord and the table, the caller gets Discord's 404
+        if !is_unknown(&why) || !forget_embed(pool, message_id).await? {
+            return Err(why.into());
+        }
+        return Ok(());
+    }
+
+    forget_embed(pool, message_id).await?;
+
+    Ok(())
+}
+
+/// Drops the row of a deleted message, true if there was one. Its revisions are kept.
+pub async fn forget_embed(pool: &SqlitePool, message_id: MessageId) -> Result<bool, SqlxError> {
+    let embed_id = message_id.0 as i64;
+    let result = query!("DELETE FROM Embed WHERE EmbedId = ?", embed_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Drops the rows of a deleted channel, keeping their revisions
+pub async fn forget_channel_embeds(pool: &SqlitePool, channel_id: ChannelId) -> Result<u64, SqlxError> {
+    let channel_id = channel_id.0 as i64;
+    let result = query!("DELETE FROM Embed WHERE ChannelId = ?", channel_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Removes rows whose message was deleted while the bot wasn't watching, and fills in the JSON of
+/// rows saved before it was stored. Returns how many rows were removed.
+pub async fn reconcile_embeds(http: &Http, pool: &SqlitePool) -> Result<u64, EmbedError> {
+    let rows = query!("SELECT EmbedId, ChannelId, EmbedJson FROM Embed")
+        .fetch_all(pool)
+        .await?;
+
+    let mut removed = 0;
+    for row in rows {
+        let message_id = MessageId(row.EmbedId as u64);
+        match ChannelId(row.ChannelId as u64).message(http, message_id).await {
+            Ok(message) if row.EmbedJson.is_none() => {
+                let embed_json = embeds_json(&message);
+                let now = Utc::now().timestamp();
+                let mut tx = pool.begin().await?;
+                query!("UPDATE Embed SET EmbedJson = ?, UpdatedAt = ? WHERE EmbedId = ?", embed_json, now, row.EmbedId)
+                    .execute(&mut tx)
+                    .await?;
+                query!(
+                    "INSERT OR IGNORE INTO EmbedRevision (EmbedId, GuildId, ChannelId, Revision, EmbedJson, AuthorName, CreatedAt)
+                    SELECT EmbedId, GuildId, ChannelId, Revision, EmbedJson, 'Unknown', ? FROM Embed WHERE EmbedId = ?",
+                    now,
+                    row.EmbedId
+                )
+                .execute(&mut tx)
+                .await?;
+                tx.commit().await?;
+            }
+            Ok(_) => {}
+            Err(why) if is_unknown(&why) => {
+                if forget_embed(pool, message_id).await? {
+                    removed += 1;
+                }
+            }
+            //missing access isn't proof the message is gone, keep the row
+            Err(why) => error!("Unable to check saved embed {}: {}", message_id, why),
+        }
+    }
+
+    if removed > 0 {
+        info!("Removed {} saved embeds deleted in Discord", removed);
+    }
+
+    Ok(removed)
+}
+
+/// The guild's saved embed's channel, `None` when the message isn't one of them
+pub async fn saved_embed_channel(
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    message_id: MessageId,
+) -> Result<Option<ChannelId>, SqlxError> {
+    let embed_id = message_id.0 as i64;
+    let guild_id = guild_id.0 as i64;
+    let row = query!(
+        "SELECT ChannelId FROM Embed WHERE EmbedId = ? AND GuildId = ?",
+        embed_id,
+        guild_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| ChannelId(row.ChannelId as u64)))
+}
+
+/// Every revision of an embed in the channel, oldest first, including ones whose message has since
+/// been deleted. Empty when it was never saved.
+pub async fn embed_revisions(
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    message_id: MessageId,
+) -> Result<Vec<EmbedRevision>, EmbedError> {
+    let embed_id = message_id.0 as i64;
+    let guild_id = guild_id.0 as i64;
+    let channel_id = channel_id.0 as i64;
+
+    let rows = query!(
+        "SELECT Revision, EmbedJson, AuthorId, AuthorName, CreatedAt FROM EmbedRevision
+        WHERE EmbedId = ? AND GuildId = ? AND ChannelId = ?
+        ORDER BY Revision",
+        embed_id,
+        guild_id,
+        channel_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut revisions = Vec::with_capacity(rows.len());
+    for row in rows {
+        revisions.push(EmbedRevision {
+            revision: row.Revision,
+            author: RevisionAuthor {
+                user_id: row.AuthorId.map(|u| UserId(u as u64)),
+                name: row.AuthorName,
+            },
+            created_at: row.CreatedAt,
+            embeds: serde_json::from_str(&row.EmbedJson)?,
+        });
+    }
+
+    Ok(revisions)
+}
+
+/// One revision of a saved embed in the channel
+pub async fn embed_revision(
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    revision: i64,
+) -> Result<EmbedRevision, EmbedError> {
+    embed_revisions(pool, guild_id, channel_id, message_id)
+        .await?
+        .into_iter()
+        .find(|r| r.revision == revision)
+        .ok_or(EmbedError::UnknownRevision)
+}
+
+/// Puts an old revision's embeds back on the message. The rollback is itself a new revision, so
+/// it can be undone the same way.
+pub async fn rollback_embed_message(
+    cache_http: impl CacheHttp,
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    revision: i64,
+    author: &RevisionAuthor,
+) -> Result<Message, EmbedError> {
+    let old = embed_revision(pool, guild_id, channel_id, message_id, revision).await?;
+    let embeds = old.embeds.into_iter().map(CreateEmbed::from).collect();
+
+    edit_embed_message(cache_http, pool, guild_id, channel_id, message_id, None, embeds, author).await
+}
+
+/// What changed between two revisions' embeds. Nulls count as missing, Discord leaves most
+/// fields out rather than sending null.
+pub fn diff_embeds(before: &[Embed], after: &[Embed]) -> Vec<EmbedChange> {
+    let before = serde_json::to_value(before).unwrap_or(Value::Null);
+    let after = serde_json::to_value(after).unwrap_or(Value::Null);
+
+    let mut changes = Vec::new();
+    diff_values("embeds", &before, &after, &mut changes);
+    changes
+}
+
+fn diff_values(path: &str, before: &Value, after: &Value, changes: &mut Vec<EmbedChange>) {
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            let mut keys: Vec<&String> = before.keys().chain(after.keys().filter(|k| !before.contains_key(*k))).collect();
+            keys.sort();
+            for key in keys {
+                diff_values(
+                    &format!("{path}.{key}"),
+                    before.get(key).unwrap_or(&Value::Null),
+                    after.get(key).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        (Value::Array(before), Value::Array(after)) => {
+            for i in 0..before.len().max(after.len()) {
+                diff_values(
+                    &format!("{path}[{i}]"),
+                    before.get(i).unwrap_or(&Value::Null),
+                    after.get(i).unwrap_or(&Value::Null),
+                    changes,
+                );
+            }
+        }
+        _ if before == after => {}
+        _ => changes.push(EmbedChange {
+            path: path.to_string(),
+            before: Some(before.clone()).filter(|v| !v.is_null()),
+            after: Some(after.clone()).filter(|v| !v.is_null()),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+
+    use super::*;
+
+    fn embeds(value: Value) -> Vec<Embed> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn message(channel_id: u64, message_id: u64, title: &str) -> Message {
+        serde_json::from_value(serde_json::json!({
+            "id": message_id.to_string(),
+            "channel_id": channel_id.to_string(),
+            "attachments": [],
+            "author": {"id": "0", "username": "Zangra", "discriminator": "0001", "avatar": null, "bot": true},
+            "content": "",
+            "edited_timestamp": null,
+            "embeds": [{"title": title, "type": "rich", "fields": []}],
+            "type": 0,
+            "mention_everyone": false,
+            "mention_roles": [],
+            "mentions": [],
+            "pinned": false,
+            "timestamp": "2026-10-19T09:00:00+00:00",
+            "tts": false
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn history_outlives_the_message() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(SqliteConnectOptions::from_str("sqlite::memory:").unwrap())
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+
+        let (guild_id, channel_id) = (GuildId(1), ChannelId(10));
+        let moderator = RevisionAuthor {
+            user_id: Some(UserId(5)),
+            name: "Moderator".to_string(),
+        };
+        save_revision(&pool, guild_id, &message(10, 100, "Rules"), &moderator).await.unwrap();
+        save_revision(&pool, guild_id, &message(10, 100, "No rules"), &moderator).await.unwrap();
+        save_revision(&pool, guild_id, &message(10, 101, "FAQ"), &moderator).await.unwrap();
+
+        assert!(forget_embed(&pool, MessageId(100)).await.unwrap());
+        assert_eq!(forget_channel_embeds(&pool, channel_id).await.unwrap(), 1);
+        assert_eq!(saved_embed_channel(&pool, guild_id, MessageId(100)).await.unwrap(), None);
+
+        let revisions = embed_revisions(&pool, guild_id, channel_id, MessageId(100)).await.unwrap();
+        let titles: Vec<_> = revisions.iter().map(|r| r.embeds[0].title.clone().unwrap()).collect();
+        assert_eq!(titles, ["Rules", "No rules"]);
+        assert_eq!(revisions[1].author.name, "Moderator");
+        assert_eq!(embed_revisions(&pool, guild_id, channel_id, MessageId(101)).await.unwrap().len(), 1);
+        assert!(
+            embed_revisions(&pool, GuildId(2), channel_id, MessageId(100)).await.unwrap().is_empty(),
+            "still only found from its own guild"
+        );
+    }
+
+    #[test]
+    fn identical_revisions_have_no_changes() {
+        let rules = embeds(serde_json::json!([{"title": "Rules", "type": "rich", "fields": []}]));
+
+        assert!(diff_embeds(&rules, &rules).is_empty());
+    }
+
+    #[test]
+    fn changes_are_reported_by_path() {
+        let before = embeds(serde_json::json!([
+            {"title": "Rules", "type": "rich", "fields": [{"name": "1", "value": "Be nice", "inline": false}]}
+        ]));
+        let after = embeds(serde_json::json!([
+            {"title": "Rules", "description": "Read them", "type": "rich", "fields": []},
+            {"title": "FAQ", "type": "rich", "fields": []}
+        ]));
+
+        let changes = diff_embeds(&before, &after);
+        let change = |path: &str| changes.iter().find(|c| c.path == path).cloned();
+
+        assert_eq!(change("embeds[0].title"), None);
+        assert_eq!(
+            change("embeds[0].description").map(|c| (c.before, c.after)),
+            Some((None, Some(Value::from("Read them"))))
+        );
+        //a removed field is one change, not one per property
+        assert_eq!(
+            change("embeds[0].fields[0]").map(|c| (c.before, c.after)),
+            Some((Some(serde_json::json!({"name": "1", "value": "Be nice", "inline": false})), None))
+        );
+        let added = change("embeds[1]").unwrap();
+        assert_eq!((added.before, added.after.unwrap()["title"].clone()), (None, Value::from("FAQ")));
+    }
+}
@@ -0,0 +1,199 @@
+//! Gateway events the dashboard wants to hear about, passed from the event handler to whoever is
+//! listening. The channels are bounded and sending never waits, a listener that falls behind loses
+//! the oldest events rather than holding up the handler. Every guild with a listener gets its own
+//! channel so a busy guild can't push a quiet one's events out.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use serenity::{
+    client::Context,
+    model::{
+        id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+        Timestamp,
+    },
+    prelude::TypeMapKey,
+};
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+const CAPACITY: usize = 256;
+
+#[derive(Clone, Debug)]
+pub enum GuildEventKind {
+    MemberJoined {
+        user_id: UserId,
+        name: String,
+    },
+    /// Roles picked or dropped on a role selector
+    SelectorRolesChanged {
+        channel_id: ChannelId,
+        message_id: MessageId,
+        user_id: UserId,
+        added: Vec<RoleId>,
+        removed: Vec<RoleId>,
+    },
+    /// Joining has no `from`, leaving has no `to`
+    VoiceMoved {
+        user_id: UserId,
+        from: Option<ChannelId>,
+        to: Option<ChannelId>,
+    },
+    MemberBanned {
+        user_id: UserId,
+        name: String,
+    },
+    MemberUnbanned {
+        user_id: UserId,
+        name: String,
+    },
+    /// `until` is `None` when the timeout was lifted
+    MemberTimedOut {
+        user_id: UserId,
+        until: Option<Timestamp>,
+    },
+    /// A message with a link on the guild's block list, `deleted` when webblock removed it
+    //published by webblock, which is switched off in main for now
+    #[allow(dead_code)]
+    LinkBlocked {
+        channel_id: ChannelId,
+        message_id: MessageId,
+        user_id: UserId,
+        deleted: bool,
+    },
+    /// A saved embed's message changed, from the dashboard, a command or anything else
+    EmbedEdited {
+        channel_id: ChannelId,
+        message_id: MessageId,
+    },
+}
+
+impl GuildEventKind {
+    /// Snake case, what the dashboard calls it
+    pub fn name(&self) -> &'static str {
+        match self {
+            GuildEventKind::MemberJoined { .. } => "member_joined",
+            GuildEventKind::SelectorRolesChanged { .. } => "selector_roles_changed",
+            GuildEventKind::VoiceMoved { .. } => "voice_moved",
+            GuildEventKind::MemberBanned { .. } => "member_banned",
+            GuildEventKind::MemberUnbanned { .. } => "member_unbanned",
+            GuildEventKind::MemberTimedOut { .. } => "member_timed_out",
+            GuildEventKind::LinkBlocked { .. } => "link_blocked",
+            GuildEventKind::EmbedEdited { .. } => "embed_edited",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GuildEvent {
+    pub guild_id: GuildId,
+    pub at: Timestamp,
+    pub kind: GuildEventKind,
+}
+
+#[derive(Clone)]
+pub struct GuildEvents {
+    /// Every guild's events, for the webhook dispatcher
+    sender: Sender<Arc<GuildEvent>>,
+    /// Made when a guild's first listener subscribes, dropped once nobody listens
+    guilds: Arc<Mutex<HashMap<GuildId, Sender<Arc<GuildEvent>>>>>,
+}
+
+impl TypeMapKey for GuildEvents {
+    type Value = GuildEvents;
+}
+
+impl Default for GuildEvents {
+    fn default() -> Self {
+        GuildEvents::new()
+    }
+}
+
+impl GuildEvents {
+    pub fn new() -> GuildEvents {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        GuildEvents {
+            sender,
+            guilds: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Hands the event to every current listener, nobody listening isn't an error
+    pub fn publish(&self, guild_id: GuildId, kind: GuildEventKind) {
+        let event = Arc::new(GuildEvent {
+            guild_id,
+            at: Timestamp::now(),
+            kind,
+        });
+
+        let mut guilds = self.guilds.lock().unwrap();
+        if let Some(sender) = guilds.get(&guild_id) {
+            if sender.send(event.clone()).is_err() {
+                //the last listener went away
+                guilds.remove(&guild_id);
+            }
+        }
+        drop(guilds);
+
+        let _ = self.sender.send(event);
+    }
+
+    /// Events of every guild from now on
+    pub fn subscribe(&self) -> Receiver<Arc<GuildEvent>> {
+        self.sender.subscribe()
+    }
+
+    /// Events of one guild from now on, only that guild's events count toward falling behind
+    pub fn subscribe_guild(&self, guild_id: GuildId) -> Receiver<Arc<GuildEvent>> {
+        self.guilds
+            .lock()
+            .unwrap()
+            .entry(guild_id)
+            .or_insert_with(|| broadcast::channel(CAPACITY).0)
+            .subscribe()
+    }
+}
+
+/// [`GuildEvents::publish`] through the bus kept in the client's data
+pub async fn publish_guild_event(ctx: &Context, guild_id: GuildId, kind: GuildEventKind) {
+    if let Some(events) = ctx.data.read().await.get::<GuildEvents>() {
+        events.publish(guild_id, kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::model::id::{GuildId, UserId};
+    use tokio::sync::broadcast::error::TryRecvError;
+
+    use super::{GuildEventKind, GuildEvents, CAPACITY};
+
+    fn joined(user_id: u64) -> GuildEventKind {
+        GuildEventKind::MemberJoined {
+            user_id: UserId(user_id),
+            name: "Nguyen".to_string(),
+        }
+    }
+
+    #[test]
+    fn busy_guilds_dont_crowd_out_quiet_ones() {
+        let events = GuildEvents::new();
+        let mut quiet = events.subscribe_guild(GuildId(1));
+        let mut busy = events.subscribe_guild(GuildId(2));
+
+        events.publish(GuildId(1), joined(1));
+        for user_id in 0..CAPACITY as u64 + 1 {
+            events.publish(GuildId(2), joined(user_id));
+        }
+
+        let event = quiet.try_recv().unwrap();
+        assert!(matches!(event.kind, GuildEventKind::MemberJoined { user_id: UserId(1), .. }));
+        assert_eq!(quiet.try_recv().unwrap_err(), TryRecvError::Empty, "other guilds' events aren't sent");
+        assert_eq!(busy.try_recv().unwrap_err(), TryRecvError::Lagged(1));
+
+        drop(quiet);
+        events.publish(GuildId(1), joined(1));
+        assert!(!events.guilds.lock().unwrap().contains_key(&GuildId(1)), "guilds nobody listens to are dropped");
+    }
+}
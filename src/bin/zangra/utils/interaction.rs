@@ -0,0 +1,47 @@
+use serenity::builder::CreateEmbed;
+use serenity::client::Context;
+use serenity::model::application::interaction::{
+    application_command::{ApplicationCommandInteraction, CommandDataOption, CommandDataOptionValue},
+    InteractionResponseType,
+};
+
+fn option<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a CommandDataOption> {
+    options.iter().find(|o| o.name == name)
+}
+
+pub fn option_str<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a str> {
+    option(options, name)
+        .and_then(|o| o.value.as_ref())
+        .and_then(|v| v.as_str())
+}
+
+pub fn option_i64(options: &[CommandDataOption], name: &str) -> Option<i64> {
+    option(options, name)
+        .and_then(|o| o.value.as_ref())
+        .and_then(|v| v.as_i64())
+}
+
+pub fn option_bool(options: &[CommandDataOption], name: &str) -> Option<bool> {
+    option(options, name)
+        .and_then(|o| o.value.as_ref())
+        .and_then(|v| v.as_bool())
+}
+
+/// Users, roles and channels come back resolved by Discord
+pub fn option_resolved<'a>(options: &'a [CommandDataOption], name: &str) -> Option<&'a CommandDataOptionValue> {
+    option(options, name).and_then(|o| o.resolved.as_ref())
+}
+
+pub async fn respond_embed(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    embed: CreateEmbed,
+    ephemeral: bool,
+) -> serenity::Result<()> {
+    command
+        .create_interaction_response(&ctx, |response| {
+            response.kind(InteractionResponseType::ChannelMessageWithSource);
+            response.interaction_response_data(|data| data.add_embed(embed).ephemeral(ephemeral))
+        })
+        .await
+}
@@ -0,0 +1,100 @@
+//! Copies of the saved embed messages, so listing them doesn't cost a Discord request per row every
+//! time. Filled when a message is first fetched and kept current from gateway events, entries
+//! still expire in case an event was missed while disconnected.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    http::Http,
+    model::{
+        channel::Message,
+        event::MessageUpdateEvent,
+        id::{ChannelId, MessageId},
+    },
+    prelude::TypeMapKey,
+    Error as SerenityError,
+};
+
+const FRESH_FOR: Duration = Duration::from_secs(10 * 60);
+const MAX_MESSAGES: usize = 10_000;
+
+#[derive(Clone, Default)]
+pub struct MessageStore {
+    messages: Arc<RwLock<HashMap<MessageId, (Instant, Message)>>>,
+}
+
+impl TypeMapKey for MessageStore {
+    type Value = MessageStore;
+}
+
+impl MessageStore {
+    pub fn new() -> MessageStore {
+        MessageStore::default()
+    }
+
+    /// The stored copy if it's fresh, otherwise the message from Discord
+    pub async fn message(&self, http: impl AsRef<Http>, channel_id: ChannelId, message_id: MessageId) -> Result<Message, SerenityError> {
+        let cached = self
+            .messages
+            .read()
+            .unwrap()
+            .get(&message_id)
+            .filter(|(stored, message)| stored.elapsed() < FRESH_FOR && message.channel_id == channel_id)
+            .map(|(_, message)| message.clone());
+        if let Some(message) = cached {
+            return Ok(message);
+        }
+
+        let message = channel_id.message(http, message_id).await?;
+        self.insert(message.clone());
+
+        Ok(message)
+    }
+
+    pub fn insert(&self, message: Message) {
+        let mut messages = self.messages.write().unwrap();
+        if messages.len() >= MAX_MESSAGES && !messages.contains_key(&message.id) {
+            messages.retain(|_, (stored, _)| stored.elapsed() < FRESH_FOR);
+            if messages.len() >= MAX_MESSAGES {
+                let stalest = messages.iter().min_by_key(|(_, (stored, _))| *stored).map(|(id, _)| *id);
+                if let Some(stalest) = stalest {
+                    messages.remove(&stalest);
+                }
+            }
+        }
+
+        messages.insert(message.id, (Instant::now(), message));
+    }
+
+    /// Applies an edit seen on the gateway, messages that aren't stored are left to be fetched
+    pub fn update(&self, event: &MessageUpdateEvent) {
+        let mut messages = self.messages.write().unwrap();
+        if let Some((stored, message)) = messages.get_mut(&event.id) {
+            if let Some(content) = &event.content {
+                message.content = content.clone();
+            }
+            if let Some(embeds) = &event.embeds {
+                message.embeds = embeds.clone();
+            }
+            if event.edited_timestamp.is_some() {
+                message.edited_timestamp = event.edited_timestamp;
+            }
+            *stored = Instant::now();
+        }
+    }
+
+    pub fn remove(&self, message_id: MessageId) {
+        self.messages.write().unwrap().remove(&message_id);
+    }
+
+    pub fn remove_channel(&self, channel_id: ChannelId) {
+        self.messages
+            .write()
+            .unwrap()
+            .retain(|_, (_, message)| message.channel_id != channel_id);
+    }
+}
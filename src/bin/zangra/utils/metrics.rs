@@ -0,0 +1,227 @@
+//! Counters for `/metrics`, rendered in Prometheus' text format. Kept by hand rather than with a
+//! metrics crate, there are few enough of them.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    async_trait,
+    client::{Context, RawEventHandler},
+    model::event::Event,
+    prelude::TypeMapKey,
+};
+
+//seconds, SQLite answers most queries in well under a millisecond
+const QUERY_BUCKETS: [f64; 9] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5];
+
+#[derive(Default)]
+struct Histogram {
+    //counts per bucket, not cumulative
+    buckets: [u64; QUERY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(i) = QUERY_BUCKETS.iter().position(|le| seconds <= *le) {
+            self.buckets[i] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    events: BTreeMap<String, u64>,
+    commands: BTreeMap<String, u64>,
+    command_errors: BTreeMap<String, u64>,
+    roles_added: u64,
+    roles_removed: u64,
+    webblock_deletions: u64,
+    queries: BTreeMap<&'static str, Histogram>,
+}
+
+/// A shard as the shard manager sees it, read when `/metrics` is scraped
+pub struct ShardStatus {
+    pub id: u64,
+    pub connected: bool,
+    /// Until the first heartbeat is acknowledged there's none
+    pub latency: Option<Duration>,
+}
+
+#[derive(Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+impl TypeMapKey for Metrics {
+    type Value = Metrics;
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    pub fn event(&self, kind: &str) {
+        *self.registry.lock().unwrap().events.entry(kind.to_string()).or_default() += 1;
+    }
+
+    pub fn command(&self, name: &str) {
+        *self.registry.lock().unwrap().commands.entry(name.to_string()).or_default() += 1;
+    }
+
+    pub fn command_error(&self, name: &str) {
+        *self.registry.lock().unwrap().command_errors.entry(name.to_string()).or_default() += 1;
+    }
+
+    /// Roles given and taken through role selectors
+    pub fn role_assignments(&self, added: usize, removed: usize) {
+        let mut registry = self.registry.lock().unwrap();
+        registry.roles_added += added as u64;
+        registry.roles_removed += removed as u64;
+    }
+
+    //counted by webblock, which is switched off in main for now
+    #[allow(dead_code)]
+    pub fn webblock_deletion(&self) {
+        self.registry.lock().unwrap().webblock_deletions += 1;
+    }
+
+    /// Runs the query, recording how long it took under `query`
+    pub async fn time_query<T>(&self, query: &'static str, future: impl Future<Output = T>) -> T {
+        let started = Instant::now();
+        let result = future.await;
+        let seconds = started.elapsed().as_secs_f64();
+
+        self.registry.lock().unwrap().queries.entry(query).or_default().observe(seconds);
+
+        result
+    }
+
+    /// Everything in Prometheus' text exposition format
+    pub fn render(&self, shards: &[ShardStatus]) -> String {
+        let registry = self.registry.lock().unwrap();
+        let mut out = String::new();
+
+        header(&mut out, "zangra_shard_connected", "gauge", "1 while the shard's gateway connection is up");
+        for shard in shards {
+            let _ = writeln!(out, "zangra_shard_connected{{shard=\"{}\"}} {}", shard.id, u8::from(shard.connected));
+        }
+
+        header(&mut out, "zangra_gateway_latency_seconds", "gauge", "Time between a heartbeat and its acknowledgement");
+        for shard in shards {
+            if let Some(latency) = shard.latency {
+                let _ = writeln!(out, "zangra_gateway_latency_seconds{{shard=\"{}\"}} {}", shard.id, latency.as_secs_f64());
+            }
+        }
+
+        header(&mut out, "zangra_gateway_events_total", "counter", "Gateway events received by type");
+        labelled(&mut out, "zangra_gateway_events_total", "type", &registry.events);
+
+        header(&mut out, "zangra_command_invocations_total", "counter", "Slash and context menu commands run");
+        labelled(&mut out, "zangra_command_invocations_total", "command", &registry.commands);
+
+        header(&mut out, "zangra_command_errors_total", "counter", "Commands that failed");
+        labelled(&mut out, "zangra_command_errors_total", "command", &registry.command_errors);
+
+        header(&mut out, "zangra_role_assignments_total", "counter", "Roles given or taken through role selectors");
+        let _ = writeln!(out, "zangra_role_assignments_total{{action=\"added\"}} {}", registry.roles_added);
+        let _ = writeln!(out, "zangra_role_assignments_total{{action=\"removed\"}} {}", registry.roles_removed);
+
+        header(&mut out, "zangra_webblock_deletions_total", "counter", "Messages deleted for containing a blocked link");
+        let _ = writeln!(out, "zangra_webblock_deletions_total {}", registry.webblock_deletions);
+
+        header(&mut out, "zangra_db_query_duration_seconds", "histogram", "Database query timings");
+        for (query, histogram) in &registry.queries {
+            let query = escape(query);
+            let mut cumulative = 0;
+            for (le, count) in QUERY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(out, "zangra_db_query_duration_seconds_bucket{{query=\"{query}\",le=\"{le}\"}} {cumulative}");
+            }
+            let _ = writeln!(out, "zangra_db_query_duration_seconds_bucket{{query=\"{query}\",le=\"+Inf\"}} {}", histogram.count);
+            let _ = writeln!(out, "zangra_db_query_duration_seconds_sum{{query=\"{query}\"}} {}", histogram.sum);
+            let _ = writeln!(out, "zangra_db_query_duration_seconds_count{{query=\"{query}\"}} {}", histogram.count);
+        }
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn labelled(out: &mut String, name: &str, label: &str, values: &BTreeMap<String, u64>) {
+    for (value, count) in values {
+        let _ = writeln!(out, "{name}{{{label}=\"{}\"}} {count}", escape(value));
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// The client's [`Metrics`], or a detached set when they're missing so callers needn't care
+pub async fn client_metrics(ctx: &Context) -> Metrics {
+    ctx.data.read().await.get::<Metrics>().cloned().unwrap_or_default()
+}
+
+/// Counts every gateway event, whether or not [`Handler`](crate::Handler) does anything with it
+pub struct EventCounter {
+    pub metrics: Metrics,
+}
+
+#[async_trait]
+impl RawEventHandler for EventCounter {
+    async fn raw_event(&self, _ctx: Context, event: Event) {
+        let event_type = event.event_type();
+        self.metrics.event(event_type.name().unwrap_or("UNKNOWN"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Metrics, ShardStatus};
+
+    #[tokio::test]
+    async fn renders_the_text_format() {
+        let metrics = Metrics::new();
+        metrics.event("MESSAGE_CREATE");
+        metrics.command("ping");
+        metrics.command_error("ping");
+        metrics.role_assignments(2, 1);
+        metrics.webblock_deletion();
+        metrics.time_query("test", async {}).await;
+
+        let text = metrics.render(&[ShardStatus {
+            id: 0,
+            connected: true,
+            latency: Some(Duration::from_millis(42)),
+        }]);
+
+        for line in [
+            "zangra_shard_connected{shard=\"0\"} 1",
+            "zangra_gateway_latency_seconds{shard=\"0\"} 0.042",
+            "zangra_gateway_events_total{type=\"MESSAGE_CREATE\"} 1",
+            "zangra_command_errors_total{command=\"ping\"} 1",
+            "zangra_role_assignments_total{action=\"added\"} 2",
+            "zangra_webblock_deletions_total 1",
+            "zangra_db_query_duration_seconds_bucket{query=\"test\",le=\"+Inf\"} 1",
+            "zangra_db_query_duration_seconds_count{query=\"test\"} 1",
+        ] {
+            assert!(text.lines().any(|l| l == line), "missing {line} in\n{text}");
+        }
+    }
+}
@@ -1,2 +1,16 @@
+pub mod cron;
 pub mod database;
-pub mod voice;
\ No newline at end of file
+pub mod embeds;
+pub mod guild_events;
+pub mod interaction;
+pub mod message_store;
+pub mod metrics;
+pub mod role_selectors;
+pub mod scheduler;
+pub mod shutdown;
+pub mod time;
+pub mod voice;
+pub mod voice_hubs;
+pub mod voice_log;
+pub mod voice_sessions;
+pub mod webhooks;
\ No newline at end of file
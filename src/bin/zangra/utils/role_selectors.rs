@@ -0,0 +1,673 @@
+//! Role selectors are messages with a select menu or buttons that members use to give themselves
+//! roles. Each has a row in `AutoRoleMessage` and its choices in `AutoRoleOption`. The setup wizard
+//! and the REST API both validate, build and save them here so either way gives the same selector.
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+
+use serenity::{
+    builder::{CreateActionRow, CreateEmbed, CreateSelectMenuOption},
+    http::CacheHttp,
+    model::{
+        application::component::ButtonStyle,
+        channel::Message,
+        guild::Role,
+        id::{ChannelId, GuildId, MessageId, RoleId, UserId},
+        permissions::Permissions,
+    },
+    Error as SerenityError,
+};
+use sqlx::{query, Error as SqlxError, SqlitePool};
+use tracing::error;
+
+use crate::utils::embeds::is_unknown;
+
+/// Custom id of a selector's menu
+pub const MENU_CUSTOM_ID: &str = "selectmenu";
+/// Buttons are `roleselector:<role id>`
+pub const ROLE_BUTTON_PREFIX: &str = "roleselector:";
+
+//Discord's limits
+const MAX_OPTIONS: usize = 25;
+const MAX_LABEL: usize = 100;
+const MAX_DESCRIPTION: usize = 100;
+const BUTTONS_PER_ROW: usize = 5;
+
+//roles with any of these are only handed out when an administrator sets the selector up
+const ELEVATED_PERMISSIONS: Permissions = Permissions::ADMINISTRATOR
+    .union(Permissions::MANAGE_CHANNELS)
+    .union(Permissions::MANAGE_GUILD)
+    .union(Permissions::MANAGE_MESSAGES)
+    .union(Permissions::MANAGE_NICKNAMES)
+    .union(Permissions::MANAGE_ROLES)
+    .union(Permissions::MANAGE_WEBHOOKS)
+    .union(Permissions::MANAGE_EMOJIS_AND_STICKERS)
+    .union(Permissions::MANAGE_EVENTS)
+    .union(Permissions::MANAGE_THREADS)
+    .union(Permissions::KICK_MEMBERS)
+    .union(Permissions::BAN_MEMBERS)
+    .union(Permissions::MODERATE_MEMBERS);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoleSelectorStyle {
+    /// A single select menu, the only style the wizard makes
+    Menu,
+    /// One button per role, clicking toggles it. Descriptions aren't shown.
+    Buttons,
+}
+
+impl RoleSelectorStyle {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RoleSelectorStyle::Menu => "menu",
+            RoleSelectorStyle::Buttons => "buttons",
+        }
+    }
+
+    fn parse(style: &str) -> RoleSelectorStyle {
+        match style {
+            "buttons" => RoleSelectorStyle::Buttons,
+            _ => RoleSelectorStyle::Menu,
+        }
+    }
+}
+
+/// One role members can pick
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleOption {
+    pub role_id: RoleId,
+    pub label: String,
+    pub description: Option<String>,
+}
+
+/// Everything a selector message shows
+#[derive(Debug, Clone)]
+pub struct RoleSelector {
+    pub content: Option<String>,
+    pub embeds: Vec<CreateEmbed>,
+    pub options: Vec<RoleOption>,
+    pub max_selections: u64,
+    pub style: RoleSelectorStyle,
+}
+
+/// The stored part of a selector, the content and embeds are only kept in Discord
+#[derive(Debug, Clone)]
+pub struct SavedRoleSelector {
+    pub channel_id: ChannelId,
+    pub options: Vec<RoleOption>,
+    pub max_selections: u64,
+    pub style: RoleSelectorStyle,
+}
+
+/// A value the selector can't be built with, `field` is like `options[2].label`
+#[derive(Debug, Clone)]
+pub struct SelectorProblem {
+    pub field: String,
+    pub message: String,
+}
+
+impl SelectorProblem {
+    fn new<F: Into<String>, M: Into<String>>(field: F, message: M) -> SelectorProblem {
+        SelectorProblem {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RoleSelectorError {
+    Discord(SerenityError),
+    Database(SqlxError),
+    Invalid(Vec<SelectorProblem>),
+    /// The message isn't a role selector of the guild
+    UnknownSelector,
+}
+
+impl Display for RoleSelectorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RoleSelectorError::Discord(why) => write!(f, "{why}"),
+            RoleSelectorError::Database(why) => write!(f, "{why}"),
+            RoleSelectorError::Invalid(problems) => {
+                let problems: Vec<String> = problems.iter().map(|p| format!("{}: {}", p.field, p.message)).collect();
+                write!(f, "{}", problems.join(", "))
+            }
+            RoleSelectorError::UnknownSelector => write!(f, "Not a role selector"),
+        }
+    }
+}
+
+impl std::error::Error for RoleSelectorError {}
+
+impl From<SerenityError> for RoleSelectorError {
+    fn from(e: SerenityError) -> Self {
+        RoleSelectorError::Discord(e)
+    }
+}
+
+impl From<SqlxError> for RoleSelectorError {
+    fn from(e: SqlxError) -> Self {
+        RoleSelectorError::Database(e)
+    }
+}
+
+/// What whoever sets a selector up may hand out, roles are compared by position like Discord does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoleGrantor {
+    /// Position of the bot's highest role
+    pub bot_top: i64,
+    /// Position of the caller's highest role, `None` for the guild owner and API keys
+    pub caller_top: Option<i64>,
+    /// Only administrators and the owner may hand out roles with [`ELEVATED_PERMISSIONS`]
+    pub caller_is_admin: bool,
+}
+
+/// Looks up the bot's roles and those of `caller`, `None` being an API key that isn't anyone in
+/// the guild
+pub async fn role_grantor(
+    cache_http: &impl CacheHttp,
+    guild_id: GuildId,
+    guild_roles: &HashMap<RoleId, Role>,
+    caller: Option<UserId>,
+) -> Result<RoleGrantor, SerenityError> {
+    let top = |roles: &[RoleId]| roles.iter().filter_map(|r| guild_roles.get(r)).map(|r| r.position).max().unwrap_or(0);
+
+    let bot_id = match cache_http.cache() {
+        Some(cache) => cache.current_user_id(),
+        None => cache_http.http().get_current_user().await?.id,
+    };
+    let bot_top = top(&guild_id.member(cache_http, bot_id).await?.roles);
+
+    let caller = match caller {
+        Some(caller) => caller,
+        None => {
+            return Ok(RoleGrantor {
+                bot_top,
+                caller_top: None,
+                caller_is_admin: false,
+            })
+        }
+    };
+
+    let owner_id = match cache_http.cache().and_then(|cache| cache.guild_field(guild_id, |g| g.owner_id)) {
+        Some(owner_id) => owner_id,
+        None => guild_id.to_partial_guild(cache_http.http()).await?.owner_id,
+    };
+    if caller == owner_id {
+        return Ok(RoleGrantor {
+            bot_top,
+            caller_top: None,
+            caller_is_admin: true,
+        });
+    }
+
+    let member = guild_id.member(cache_http, caller).await?;
+    //@everyone shares the guild's id and applies to everyone
+    let caller_is_admin = member
+        .roles
+        .iter()
+        .chain([RoleId(guild_id.0)].iter())
+        .filter_map(|r| guild_roles.get(r))
+        .any(|r| r.permissions.administrator());
+
+    Ok(RoleGrantor {
+        bot_top,
+        caller_top: Some(top(&member.roles)),
+        caller_is_admin,
+    })
+}
+
+/// The role of a selector button's custom id
+pub fn button_role(custom_id: &str) -> Option<RoleId> {
+    custom_id
+        .strip_prefix(ROLE_BUTTON_PREFIX)
+        .and_then(|role_id| role_id.parse().ok())
+        .map(RoleId)
+}
+
+/// Every reason the selector can't be posted in the guild, empty when it can
+pub fn validate_role_selector(
+    guild_id: GuildId,
+    guild_roles: &HashMap<RoleId, Role>,
+    grantor: &RoleGrantor,
+    selector: &RoleSelector,
+) -> Vec<SelectorProblem> {
+    let mut problems = Vec::new();
+
+    if selector.options.is_empty() {
+        problems.push(SelectorProblem::new("options", "Pick at least one role"));
+    } else if selector.options.len() > MAX_OPTIONS {
+        problems.push(SelectorProblem::new(
+            "options",
+            format!("At most {MAX_OPTIONS} roles are allowed, got {}", selector.options.len()),
+        ));
+    }
+
+    for (i, option) in selector.options.iter().enumerate() {
+        if selector.options[..i].iter().any(|o| o.role_id == option.role_id) {
+            problems.push(SelectorProblem::new(format!("options[{i}].role_id"), "The role is listed twice"));
+        }
+
+        match guild_roles.get(&option.role_id) {
+            None => problems.push(SelectorProblem::new(format!("options[{i}].role_id"), "No such role in this server")),
+            //@everyone shares the guild's id
+            Some(role) if role.id.0 == guild_id.0 => {
+                problems.push(SelectorProblem::new(format!("options[{i}].role_id"), "Everyone already has @everyone"))
+            }
+            Some(role) if role.managed => problems.push(SelectorProblem::new(
+                format!("options[{i}].role_id"),
+                format!("{} is managed by an integration and can't be given out", role.name),
+            )),
+            Some(role) if role.position >= grantor.bot_top => problems.push(SelectorProblem::new(
+                format!("options[{i}].role_id"),
+                format!("{} isn't below the bot's highest role", role.name),
+            )),
+            Some(role) if grantor.caller_top.is_some_and(|top| role.position >= top) => problems.push(SelectorProblem::new(
+                format!("options[{i}].role_id"),
+                format!("{} isn't below your highest role", role.name),
+            )),
+            Some(role) if !grantor.caller_is_admin && role.permissions.intersects(ELEVATED_PERMISSIONS) => {
+                problems.push(SelectorProblem::new(
+                    format!("options[{i}].role_id"),
+                    format!("{} has moderation permissions, only administrators can hand it out", role.name),
+                ))
+            }
+            Some(_) => {}
+        }
+
+        let label = option.label.chars().count();
+        if option.label.trim().is_empty() {
+            problems.push(SelectorProblem::new(format!("options[{i}].label"), "Must not be empty"));
+        } else if label > MAX_LABEL {
+            problems.push(SelectorProblem::new(
+                format!("options[{i}].label"),
+                format!("Must be at most {MAX_LABEL} characters, got {label}"),
+            ));
+        }
+
+        if let Some(description) = &option.description {
+            let length = description.chars().count();
+            if length > MAX_DESCRIPTION {
+                problems.push(SelectorProblem::new(
+                    format!("options[{i}].description"),
+                    format!("Must be at most {MAX_DESCRIPTION} characters, got {length}"),
+                ));
+            }
+        }
+    }
+
+    if selector.max_selections == 0 || selector.max_selections as usize > selector.options.len().max(1) {
+        problems.push(SelectorProblem::new(
+            "max_selections",
+            format!("Must be between 1 and the number of roles, got {}", selector.max_selections),
+        ));
+    }
+
+    problems
+}
+
+/// The menu or buttons members pick from
+pub fn role_selector_components(options: &[RoleOption], max_selections: u64, style: RoleSelectorStyle) -> Vec<CreateActionRow> {
+    match style {
+        RoleSelectorStyle::Menu => {
+            let menu_options: Vec<CreateSelectMenuOption> = options
+                .iter()
+                .map(|option| {
+                    let mut o = CreateSelectMenuOption::default();
+                    o.label(&option.label);
+                    o.value(option.role_id);
+                    if let Some(description) = &option.description {
+                        o.description(description);
+                    }
+                    o
+                })
+                .collect();
+
+            let mut row = CreateActionRow::default();
+            row.create_select_menu(|sm| {
+                sm.custom_id(MENU_CUSTOM_ID);
+                sm.min_values(0);
+                sm.max_values(max_selections);
+                sm.options(|ops| ops.set_options(menu_options))
+            });
+
+            vec![row]
+        }
+        RoleSelectorStyle::Buttons => options
+            .chunks(BUTTONS_PER_ROW)
+            .map(|chunk| {
+                let mut row = CreateActionRow::default();
+                for option in chunk {
+                    row.create_button(|b| {
+                        b.custom_id(format!("{ROLE_BUTTON_PREFIX}{}", option.role_id));
+                        b.label(&option.label);
+                        b.style(ButtonStyle::Secondary)
+                    });
+                }
+                row
+            })
+            .collect(),
+    }
+}
+
+/// Stores the selector's settings for the message, replacing any it had, in one transaction
+pub async fn save_role_selector(
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    message: &Message,
+    selector: &RoleSelector,
+) -> Result<(), SqlxError> {
+    let message_id = message.id.0 as i64;
+    let guild_id = guild_id.0 as i64;
+    let channel_id = message.channel_id.0 as i64;
+    let max_selections = selector.max_selections as i64;
+    let style = selector.style.as_str();
+
+    let mut tx = pool.begin().await?;
+
+    query!(
+        "INSERT INTO AutoRoleMessage (AutoRoleMessageId, GuildId, ChannelId, MaxSelections, Style) VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT(AutoRoleMessageId) DO UPDATE SET GuildId = excluded.GuildId, ChannelId = excluded.ChannelId,
+        MaxSelections = excluded.MaxSelections, Style = excluded.Style",
+        message_id,
+        guild_id,
+        channel_id,
+        max_selections,
+        style
+    )
+    .execute(&mut tx)
+    .await?;
+
+    query!("DELETE FROM AutoRoleOption WHERE AutoRoleMessageId = ?", message_id)
+        .execute(&mut tx)
+        .await?;
+
+    for (position, option) in selector.options.iter().enumerate() {
+        let position = position as i64;
+        let role_id = option.role_id.0 as i64;
+        query!(
+            "INSERT INTO AutoRoleOption (AutoRoleMessageId, Position, RoleId, Label, Description) VALUES (?, ?, ?, ?, ?)",
+            message_id,
+            position,
+            role_id,
+            option.label,
+            option.description
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
+
+/// The guild's selector saved for the message, `None` when it isn't one. Selectors made before
+/// their settings were stored have no guild and only show up through [`is_role_selector`].
+pub async fn saved_role_selector(
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    message_id: MessageId,
+) -> Result<Option<SavedRoleSelector>, SqlxError> {
+    let message_id = message_id.0 as i64;
+    let guild_id = guild_id.0 as i64;
+    let row = query!(
+        "SELECT ChannelId, MaxSelections, Style FROM AutoRoleMessage WHERE AutoRoleMessageId = ? AND GuildId = ?",
+        message_id,
+        guild_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let row = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+
+    let options = query!(
+        "SELECT RoleId, Label, Description FROM AutoRoleOption WHERE AutoRoleMessageId = ? ORDER BY Position",
+        message_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|o| RoleOption {
+        role_id: RoleId(o.RoleId as u64),
+        label: o.Label,
+        description: o.Description,
+    })
+    .collect::<Vec<_>>();
+
+    Ok(Some(SavedRoleSelector {
+        channel_id: ChannelId(row.ChannelId.unwrap_or_default() as u64),
+        max_selections: row.MaxSelections.map_or(options.len() as u64, |m| m as u64),
+        style: RoleSelectorStyle::parse(&row.Style),
+        options,
+    }))
+}
+
+/// Whether the message has a row, however old
+pub async fn is_role_selector(pool: &SqlitePool, message_id: MessageId) -> Result<bool, SqlxError> {
+    let message_id = message_id.0 as i64;
+    let row = query!("SELECT AutoRoleMessageId FROM AutoRoleMessage WHERE AutoRoleMessageId = ?", message_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.is_some())
+}
+
+async fn check_role_selector(
+    cache_http: &impl CacheHttp,
+    guild_id: GuildId,
+    caller: Option<UserId>,
+    selector: &RoleSelector,
+) -> Result<(), RoleSelectorError> {
+    let guild_roles = guild_id.roles(cache_http.http()).await?;
+    let grantor = role_grantor(cache_http, guild_id, &guild_roles, caller).await?;
+    let problems = validate_role_selector(guild_id, &guild_roles, &grantor, selector);
+    if !problems.is_empty() {
+        return Err(RoleSelectorError::Invalid(problems));
+    }
+
+    Ok(())
+}
+
+/// Validates, posts and saves a new selector. When saving fails the message is deleted again.
+/// `caller` is the member setting it up, `None` for API keys.
+pub async fn post_role_selector_message(
+    cache_http: impl CacheHttp,
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    caller: Option<UserId>,
+    selector: &RoleSelector,
+) -> Result<Message, RoleSelectorError> {
+    check_role_selector(&cache_http, guild_id, caller, selector).await?;
+
+    let rows = role_selector_components(&selector.options, selector.max_selections, selector.style);
+    let message = channel_id
+        .send_message(cache_http.http(), |m| {
+            if let Some(content) = &selector.content {
+                m.content(content);
+            }
+            m.set_embeds(selector.embeds.clone());
+            m.components(|c| c.set_action_rows(rows))
+        })
+        .await?;
+
+    if let Err(why) = save_role_selector(pool, guild_id, &message, selector).await {
+        if let Err(why) = message.delete(&cache_http).await {
+            error!("Unable to remove unsaved role selector {}: {}", message.id, why);
+        }
+        return Err(why.into());
+    }
+
+    Ok(message)
+}
+
+/// Replaces the selector's content, embeds and choices. Selectors made before their settings were
+/// stored get them here.
+pub async fn edit_role_selector_message(
+    cache_http: impl CacheHttp,
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    caller: Option<UserId>,
+    selector: &RoleSelector,
+) -> Result<Message, RoleSelectorError> {
+    let known = match saved_role_selector(pool, guild_id, message_id).await? {
+        Some(saved) => saved.channel_id == channel_id,
+        None => is_role_selector(pool, message_id).await?,
+    };
+    if !known {
+        return Err(RoleSelectorError::UnknownSelector);
+    }
+
+    check_role_selector(&cache_http, guild_id, caller, selector).await?;
+
+    let mut message = channel_id.message(cache_http.http(), message_id).await?;
+    let rows = role_selector_components(&selector.options, selector.max_selections, selector.style);
+    message
+        .edit(&cache_http, |m| {
+            m.content(selector.content.clone().unwrap_or_default());
+            m.set_embeds(selector.embeds.clone());
+            m.components(|c| c.set_action_rows(rows))
+        })
+        .await?;
+
+    save_role_selector(pool, guild_id, &message, selector).await?;
+
+    Ok(message)
+}
+
+/// Deletes the selector's message, then its row. A message already deleted in Discord only loses
+/// its row.
+pub async fn delete_role_selector_message(
+    cache_http: impl CacheHttp,
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    message_id: MessageId,
+) -> Result<(), RoleSelectorError> {
+    match saved_role_selector(pool, guild_id, message_id).await? {
+        Some(saved) if saved.channel_id == channel_id => {}
+        _ => return Err(RoleSelectorError::UnknownSelector),
+    }
+
+    if let Err(why) = channel_id.delete_message(cache_http.http(), message_id).await {
+        if !is_unknown(&why) {
+            return Err(why.into());
+        }
+    }
+
+    forget_role_selector(pool, message_id).await?;
+
+    Ok(())
+}
+
+/// Drops the row of a deleted message, true if there was one
+pub async fn forget_role_selector(pool: &SqlitePool, message_id: MessageId) -> Result<bool, SqlxError> {
+    let message_id = message_id.0 as i64;
+    let result = query!("DELETE FROM AutoRoleMessage WHERE AutoRoleMessageId = ?", message_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Drops the rows of a deleted channel
+pub async fn forget_channel_role_selectors(pool: &SqlitePool, channel_id: ChannelId) -> Result<u64, SqlxError> {
+    let channel_id = channel_id.0 as i64;
+    let result = query!("DELETE FROM AutoRoleMessage WHERE ChannelId = ?", channel_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+    use serenity::model::{
+        guild::Role,
+        id::{GuildId, RoleId},
+        permissions::Permissions,
+    };
+
+    use super::{validate_role_selector, RoleGrantor, RoleOption, RoleSelector, RoleSelectorStyle};
+
+    const GUILD: GuildId = GuildId(1);
+
+    fn role(id: u64, name: &str, position: i64, permissions: Permissions) -> (RoleId, Role) {
+        let role: Role = serde_json::from_value(json!({
+            "id": id.to_string(), "guild_id": GUILD.to_string(), "name": name, "color": 0, "hoist": false, "managed": false,
+            "mentionable": false, "permissions": permissions.bits().to_string(), "position": position
+        }))
+        .unwrap();
+        (RoleId(id), role)
+    }
+
+    fn roles() -> HashMap<RoleId, Role> {
+        HashMap::from([
+            role(10, "Member", 1, Permissions::SEND_MESSAGES),
+            role(11, "Helper", 2, Permissions::MANAGE_MESSAGES),
+            role(12, "Trusted", 3, Permissions::empty()),
+            role(13, "Admin", 4, Permissions::ADMINISTRATOR),
+            role(14, "Bot", 5, Permissions::empty()),
+        ])
+    }
+
+    fn selector(role_ids: &[u64]) -> RoleSelector {
+        RoleSelector {
+            content: None,
+            embeds: Vec::new(),
+            options: role_ids
+                .iter()
+                .map(|id| RoleOption {
+                    role_id: RoleId(*id),
+                    label: id.to_string(),
+                    description: None,
+                })
+                .collect(),
+            max_selections: 1,
+            style: RoleSelectorStyle::Menu,
+        }
+    }
+
+    fn problems(grantor: RoleGrantor, role_ids: &[u64]) -> Vec<String> {
+        validate_role_selector(GUILD, &roles(), &grantor, &selector(role_ids))
+            .into_iter()
+            .map(|p| p.message)
+            .collect()
+    }
+
+    #[test]
+    fn roles_at_or_above_the_bot_are_refused() {
+        let grantor = RoleGrantor { bot_top: 3, caller_top: None, caller_is_admin: true };
+        assert_eq!(problems(grantor, &[10]), Vec::<String>::new());
+        assert_eq!(problems(grantor, &[12]), ["Trusted isn't below the bot's highest role"]);
+        assert_eq!(problems(grantor, &[14]), ["Bot isn't below the bot's highest role"]);
+    }
+
+    #[test]
+    fn members_only_hand_out_roles_below_their_own() {
+        let grantor = RoleGrantor { bot_top: 5, caller_top: Some(3), caller_is_admin: true };
+        assert_eq!(problems(grantor, &[11]), Vec::<String>::new());
+        assert_eq!(problems(grantor, &[12]), ["Trusted isn't below your highest role"]);
+    }
+
+    #[test]
+    fn moderation_roles_need_an_administrator() {
+        let api_key = RoleGrantor { bot_top: 5, caller_top: None, caller_is_admin: false };
+        assert_eq!(problems(api_key, &[10, 12]), Vec::<String>::new());
+        assert_eq!(problems(api_key, &[11]), ["Helper has moderation permissions, only administrators can hand it out"]);
+        assert_eq!(problems(api_key, &[13]), ["Admin has moderation permissions, only administrators can hand it out"]);
+
+        let admin = RoleGrantor { caller_is_admin: true, ..api_key };
+        assert_eq!(problems(admin, &[11]), Vec::<String>::new());
+    }
+}
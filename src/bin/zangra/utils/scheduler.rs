@@ -0,0 +1,60 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use serenity::http::Http;
+use sqlx::SqlitePool;
+use tracing::{error, info};
+
+use crate::{
+    commands::{announce::run_due_announcements, reminder::deliver_due_reminders},
+    utils::{embeds::reconcile_embeds, shutdown::Shutdown, webhooks::prune_webhook_deliveries},
+};
+
+//cron schedules have minute resolution, checking twice a minute keeps posts on time
+const TICK: Duration = Duration::from_secs(30);
+//deletes are normally seen on the gateway, this only catches the ones missed while offline
+const RECONCILE_EVERY: Duration = Duration::from_secs(6 * 60 * 60);
+const PRUNE_EVERY: Duration = Duration::from_secs(60 * 60);
+
+/// Background loop for everything that happens at a set time, started once from main. Returns
+/// between ticks once the shutdown is triggered.
+pub async fn run_scheduler(http: Arc<Http>, pool: SqlitePool, shutdown: Shutdown) {
+    let mut interval = tokio::time::interval(TICK);
+    let mut last_reconcile: Option<Instant> = None;
+    let mut last_prune: Option<Instant> = None;
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.clone().wait() => {
+                info!("Scheduler stopped");
+                return;
+            }
+        }
+
+        if let Err(why) = run_due_announcements(&http, &pool).await {
+            error!("Scheduled announcements failed: {}", why);
+        }
+
+        if let Err(why) = deliver_due_reminders(&http, &pool).await {
+            error!("Reminder delivery failed: {}", why);
+        }
+
+        if last_reconcile.is_none_or(|last| last.elapsed() >= RECONCILE_EVERY) {
+            last_reconcile = Some(Instant::now());
+            //one request per saved embed, kept off the loop so posts aren't held up
+            let (http, pool) = (http.clone(), pool.clone());
+            tokio::spawn(async move {
+                if let Err(why) = reconcile_embeds(&http, &pool).await {
+                    error!("Saved embed reconciliation failed: {}", why);
+                }
+            });
+        }
+
+        if last_prune.is_none_or(|last| last.elapsed() >= PRUNE_EVERY) {
+            last_prune = Some(Instant::now());
+            if let Err(why) = prune_webhook_deliveries(&pool).await {
+                error!("Unable to prune the webhook delivery log: {}", why);
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! Stopping the bot in order: the HTTP server and scheduler are told to finish first, so nothing
+//! is still using the database or gateway when those go.
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// Shared by everything that runs until the bot stops, any clone can start the shutdown
+#[derive(Clone)]
+pub struct Shutdown {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        let (sender, _) = watch::channel(false);
+        Shutdown {
+            sender: Arc::new(sender),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Resolves once [`Shutdown::trigger`] has been called, right away if it already was
+    pub async fn wait(self) {
+        let mut receiver = self.sender.subscribe();
+        while !*receiver.borrow_and_update() {
+            if receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Resolves on Ctrl+C, or SIGTERM where there is such a thing
+pub async fn stop_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(why) => tracing::error!("Unable to listen for SIGTERM: {}", why),
+        }
+    }
+
+    if let Err(why) = tokio::signal::ctrl_c().await {
+        tracing::error!("Unable to listen for Ctrl+C: {}", why);
+        std::future::pending::<()>().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Shutdown;
+
+    #[tokio::test]
+    async fn waiters_before_and_after_the_trigger_are_released() {
+        let shutdown = Shutdown::new();
+        let early = tokio::spawn(shutdown.clone().wait());
+        tokio::task::yield_now().await;
+
+        shutdown.trigger();
+
+        tokio::time::timeout(Duration::from_secs(1), early).await.unwrap().unwrap();
+        tokio::time::timeout(Duration::from_secs(1), shutdown.wait()).await.unwrap();
+    }
+}
@@ -0,0 +1,356 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::{Tz, TZ_VARIANTS};
+use regex::Regex;
+use serenity::model::id::UserId;
+use sqlx::{query, SqlitePool};
+
+/// Discord renders `<t:unix:style>` in every reader's own timezone, (style, description)
+pub const TIMESTAMP_STYLES: [(char, &str); 7] = [
+    ('t', "Short time"),
+    ('T', "Long time"),
+    ('d', "Short date"),
+    ('D', "Long date"),
+    ('f', "Short date/time"),
+    ('F', "Long date/time"),
+    ('R', "Relative"),
+];
+
+pub fn discord_timestamp<T: TimeZone>(time: &DateTime<T>, style: char) -> String {
+    format!("<t:{}:{}>", time.timestamp(), style)
+}
+
+/// IANA timezone name such as `Europe/Berlin`, case insensitive
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    TZ_VARIANTS
+        .iter()
+        .find(|tz| tz.name().eq_ignore_ascii_case(name))
+        .copied()
+}
+
+/// Durations like `10m`, `1h30m` or `2 days and 3 hours`
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim().to_lowercase();
+    if input.is_empty() {
+        return None;
+    }
+
+    let unit_regex = Regex::new(r"(\d+)\s*([a-z]+)").unwrap();
+    let mut seconds: i64 = 0;
+    let mut consumed = 0;
+
+    for capture in unit_regex.captures_iter(&input) {
+        let whole = capture.get(0).unwrap();
+        //only separators are allowed between the parts
+        let gap = &input[consumed..whole.start()];
+        if !gap.split(|c: char| c.is_whitespace() || c == ',').all(|w| w.is_empty() || w == "and") {
+            return None;
+        }
+        consumed = whole.end();
+
+        let amount: i64 = capture[1].parse().ok()?;
+        let unit: i64 = match &capture[2] {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+            "d" | "day" | "days" => 24 * 60 * 60,
+            "w" | "wk" | "wks" | "week" | "weeks" => 7 * 24 * 60 * 60,
+            "mo" | "month" | "months" => 30 * 24 * 60 * 60,
+            "y" | "yr" | "yrs" | "year" | "years" => 365 * 24 * 60 * 60,
+            _ => return None,
+        };
+        seconds = amount.checked_mul(unit)?.checked_add(seconds)?;
+    }
+
+    //Duration holds at most i64::MAX milliseconds
+    if consumed == 0 || !input[consumed..].trim().is_empty() || seconds > i64::MAX / 1000 {
+        return None;
+    }
+
+    Some(Duration::seconds(seconds))
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thur" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn parse_month(word: &str) -> Option<u32> {
+    let months = [
+        "january", "february", "march", "april", "may", "june", "july", "august", "september",
+        "october", "november", "december",
+    ];
+    months
+        .iter()
+        .position(|m| word.len() >= 3 && m.starts_with(word))
+        .map(|i| i as u32 + 1)
+}
+
+/// `18:00`, `8pm`, `8:30am`, `noon`
+fn parse_clock(word: &str, meridiem: Option<&str>) -> Option<NaiveTime> {
+    match word {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+
+    let clock_regex = Regex::new(r"^(\d{1,2})(?::(\d{2}))?(?::(\d{2}))?(am|pm)?$").unwrap();
+    let capture = clock_regex.captures(word)?;
+
+    let mut hour: u32 = capture[1].parse().ok()?;
+    let minute: u32 = capture.get(2).map_or(Some(0), |m| m.as_str().parse().ok())?;
+    let second: u32 = capture.get(3).map_or(Some(0), |s| s.as_str().parse().ok())?;
+
+    //a bare number is only a time with am/pm attached, otherwise it's a day of the month
+    let meridiem = capture.get(4).map(|m| m.as_str()).or(meridiem);
+    if capture.get(2).is_none() && meridiem.is_none() {
+        return None;
+    }
+
+    match meridiem {
+        Some(_) if hour == 0 || hour > 12 => return None,
+        Some("am") if hour == 12 => hour = 0,
+        Some("pm") if hour != 12 => hour += 12,
+        _ => {}
+    }
+
+    NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+/// Parses natural date/time input relative to `now`, falling back to `timezone` unless the
+/// input names its own IANA zone, e.g. `tomorrow 8pm`, `in 2 hours`, `friday 18:30`,
+/// `2026-11-01 18:00 Europe/Berlin`, `nov 1 9am`.
+///
+/// A time without a date is the next time the clock shows it, weekdays are the next such day.
+pub fn parse_datetime(input: &str, now: DateTime<Utc>, timezone: Tz) -> Result<DateTime<Tz>> {
+    let mut words: Vec<&str> = input.split_whitespace().collect();
+    let mut timezone = timezone;
+
+    if let Some(tz) = words.last().and_then(|w| parse_timezone(w)) {
+        timezone = tz;
+        words.pop();
+    } else if let Some(tz) = words.first().and_then(|w| parse_timezone(w)) {
+        timezone = tz;
+        words.remove(0);
+    }
+
+    let words: Vec<String> = words
+        .iter()
+        .map(|w| w.to_lowercase().trim_matches(',').to_string())
+        .filter(|w| !matches!(w.as_str(), "at" | "on" | "next" | "the" | ""))
+        .collect();
+    let local_now = now.with_timezone(&timezone);
+
+    if words.is_empty() || words == ["now"] {
+        return Ok(local_now);
+    }
+
+    let phrase = words.join(" ");
+    if let Some(duration) = parse_duration(phrase.strip_prefix("in ").unwrap_or(&phrase)) {
+        return local_now
+            .checked_add_signed(duration)
+            .ok_or_else(|| anyhow!("`{phrase}` is too far away"));
+    }
+
+    let iso_date = Regex::new(r"^(\d{4})-(\d{1,2})-(\d{1,2})$").unwrap();
+    let ordinal = Regex::new(r"^(\d{1,2})(st|nd|rd|th)?$").unwrap();
+
+    let mut date: Option<NaiveDate> = None;
+    let mut relative_date = false;
+    let mut weekday: Option<Weekday> = None;
+    let mut month: Option<u32> = None;
+    let mut day: Option<u32> = None;
+    let mut year: Option<i32> = None;
+    let mut time: Option<NaiveTime> = None;
+
+    let mut i = 0;
+    while i < words.len() {
+        let word = words[i].as_str();
+        let next = words.get(i + 1).map(|w| w.as_str());
+
+        //2026-11-01T18:00
+        let (word, attached_time) = match word.split_once('t') {
+            Some((d, t)) if iso_date.is_match(d) => (d, Some(t)),
+            _ => (word, None),
+        };
+
+        if let Some(capture) = iso_date.captures(word) {
+            date = Some(
+                NaiveDate::from_ymd_opt(capture[1].parse()?, capture[2].parse()?, capture[3].parse()?)
+                    .ok_or_else(|| anyhow!("`{word}` is not a valid date"))?,
+            );
+            if let Some(t) = attached_time {
+                time = Some(parse_clock(t, None).ok_or_else(|| anyhow!("`{t}` is not a valid time"))?);
+            }
+        } else if matches!(word, "today" | "tonight" | "tomorrow" | "yesterday") {
+            let offset = match word {
+                "tomorrow" => 1,
+                "yesterday" => -1,
+                _ => 0,
+            };
+            date = Some(local_now.date_naive() + Duration::days(offset));
+            relative_date = true;
+        } else if let Some(wd) = parse_weekday(word) {
+            weekday = Some(wd);
+        } else if let Some(m) = parse_month(word) {
+            month = Some(m);
+        } else if let Some(t) = parse_clock(word, next.filter(|n| matches!(*n, "am" | "pm"))) {
+            time = Some(t);
+            if matches!(next, Some("am") | Some("pm")) {
+                i += 1;
+            }
+        } else if let Some(capture) = ordinal.captures(word) {
+            day = Some(capture[1].parse()?);
+        } else if word.len() == 4 && word.chars().all(|c| c.is_ascii_digit()) {
+            year = Some(word.parse()?);
+        } else {
+            return Err(anyhow!("I don't understand `{word}`"));
+        }
+
+        i += 1;
+    }
+
+    if let Some(m) = month {
+        let d = day.ok_or_else(|| anyhow!("Which day of the month?"))?;
+        let y = year.unwrap_or(local_now.year());
+        let mut calendar_date = NaiveDate::from_ymd_opt(y, m, d).ok_or_else(|| anyhow!("That isn't a valid date"))?;
+        //without a year the next occurrence of the date is meant
+        if year.is_none() && calendar_date < local_now.date_naive() {
+            calendar_date = NaiveDate::from_ymd_opt(y + 1, m, d).ok_or_else(|| anyhow!("That isn't a valid date"))?;
+        }
+        date = Some(calendar_date);
+    } else if day.is_some() || year.is_some() {
+        return Err(anyhow!("Which month?"));
+    }
+
+    let resolve = |date: NaiveDate, time: NaiveTime| {
+        timezone
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .ok_or_else(|| anyhow!("{} {} doesn't exist in {}", date, time, timezone.name()))
+    };
+
+    let time_or_now = time.unwrap_or_else(|| local_now.time());
+
+    match (date, weekday) {
+        (Some(date), _) if relative_date => resolve(date, time_or_now),
+        (Some(date), _) => resolve(date, time.unwrap_or_default()),
+        (None, Some(weekday)) => {
+            let today = local_now.date_naive();
+            let days_ahead = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+            let candidate = resolve(today + Duration::days(days_ahead as i64), time_or_now)?;
+            if candidate <= local_now {
+                resolve(today + Duration::days(days_ahead as i64 + 7), time_or_now)
+            } else {
+                Ok(candidate)
+            }
+        }
+        (None, None) => {
+            let time = time.ok_or_else(|| anyhow!("I couldn't find a date or time in `{input}`"))?;
+            let today = local_now.date_naive();
+            let candidate = resolve(today, time)?;
+            if candidate <= local_now {
+                resolve(today + Duration::days(1), time)
+            } else {
+                Ok(candidate)
+            }
+        }
+    }
+}
+
+/// The member's saved timezone, if they've set one with `/time timezone`
+pub async fn get_user_timezone(pool: &SqlitePool, user_id: UserId) -> Result<Option<Tz>> {
+    let user_id_i64 = *user_id.as_u64() as i64;
+    let row = query!("SELECT Timezone FROM UserTimezone WHERE UserId = ?", user_id_i64)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.and_then(|r| parse_timezone(&r.Timezone)))
+}
+
+pub async fn set_user_timezone(pool: &SqlitePool, user_id: UserId, timezone: Tz) -> Result<()> {
+    let user_id_i64 = *user_id.as_u64() as i64;
+    let name = timezone.name();
+    query!(
+        "INSERT INTO UserTimezone (UserId, Timezone) VALUES (?, ?)
+        ON CONFLICT(UserId) DO UPDATE SET Timezone = excluded.Timezone",
+        user_id_i64,
+        name
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use chrono_tz::{America::New_York, Europe::Berlin};
+
+    use super::{parse_datetime, parse_duration};
+
+    #[test]
+    fn durations_add_up_their_parts() {
+        assert_eq!(parse_duration("10m"), Some(Duration::minutes(10)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("2 days and 3 hours"), Some(Duration::hours(51)));
+        assert_eq!(parse_duration("1 week, 2d"), Some(Duration::days(9)));
+        assert_eq!(parse_duration("1 Year"), Some(Duration::days(365)));
+
+        for input in ["", "10", "10 parsecs", "5m later", "tomorrow", "99999999999999999999y", "999999999999 years", "1000000000 years"] {
+            assert_eq!(parse_duration(input), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn natural_input_is_the_next_matching_time() {
+        //a Monday, 14:00 in Berlin
+        let now = Utc.with_ymd_and_hms(2026, 10, 19, 12, 0, 0).unwrap();
+        let parse = |input| parse_datetime(input, now, Berlin).unwrap();
+
+        assert_eq!(parse("now"), now);
+        assert_eq!(parse("in 2 hours"), now + Duration::hours(2));
+        assert_eq!(parse("tomorrow 8pm"), Berlin.with_ymd_and_hms(2026, 10, 20, 20, 0, 0).unwrap());
+        assert_eq!(parse("tomorrow"), now + Duration::days(1));
+        assert_eq!(parse("friday 18:30"), Berlin.with_ymd_and_hms(2026, 10, 23, 18, 30, 0).unwrap());
+        assert_eq!(parse("monday at 9am"), Berlin.with_ymd_and_hms(2026, 10, 26, 9, 0, 0).unwrap(), "today's 9am has passed");
+        assert_eq!(parse("10:00"), Berlin.with_ymd_and_hms(2026, 10, 20, 10, 0, 0).unwrap(), "today's 10:00 has passed");
+        assert_eq!(parse("noon"), Berlin.with_ymd_and_hms(2026, 10, 20, 12, 0, 0).unwrap());
+        assert_eq!(parse("nov 1 9am"), Berlin.with_ymd_and_hms(2026, 11, 1, 9, 0, 0).unwrap());
+        assert_eq!(parse("jan 5th"), Berlin.with_ymd_and_hms(2027, 1, 5, 0, 0, 0).unwrap(), "without a year it's the next one");
+        assert_eq!(parse("2026-11-01T18:00"), Berlin.with_ymd_and_hms(2026, 11, 1, 18, 0, 0).unwrap());
+        assert_eq!(
+            parse("2026-11-01 18:00 America/New_York"),
+            New_York.with_ymd_and_hms(2026, 11, 1, 18, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn nonsense_and_impossible_times_are_errors() {
+        let now = Utc.with_ymd_and_hms(2026, 10, 19, 12, 0, 0).unwrap();
+
+        for input in [
+            "sometime",
+            "the 5th",
+            "feb 30",
+            "25:00",
+            "2026-13-01",
+            //skipped when the clocks went forward
+            "2026-03-29 02:30",
+            //further than DateTime goes
+            "in 300000 years",
+            "in 1000000000 years",
+        ] {
+            assert!(parse_datetime(input, now, Berlin).is_err(), "{input}");
+        }
+    }
+}
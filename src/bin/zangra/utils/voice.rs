@@ -1,8 +1,8 @@
-use serenity::model::id::GuildId;
 use serenity::model::voice::VoiceState;
+use std::collections::BTreeSet;
 use std::fmt::{self, Display, Formatter};
 
-
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum VoiceStateChange {
     LeftVoiceChannel,
     JoinedVoiceChannel,
@@ -11,11 +11,42 @@ pub enum VoiceStateChange {
     ServerMuted,
     SelfDeafened,
     SelfMuted,
-    _SelfStream,
+    SelfStream,
     SelfVideo,
     Suppress,
 }
 
+impl VoiceStateChange {
+    pub const ALL: [VoiceStateChange; 10] = [
+        VoiceStateChange::JoinedVoiceChannel,
+        VoiceStateChange::LeftVoiceChannel,
+        VoiceStateChange::MovedVoiceChannel,
+        VoiceStateChange::ServerDeafened,
+        VoiceStateChange::ServerMuted,
+        VoiceStateChange::SelfDeafened,
+        VoiceStateChange::SelfMuted,
+        VoiceStateChange::SelfStream,
+        VoiceStateChange::SelfVideo,
+        VoiceStateChange::Suppress,
+    ];
+
+    /// What `/voicelog` calls it
+    pub fn name(&self) -> &'static str {
+        match self {
+            VoiceStateChange::JoinedVoiceChannel => "join",
+            VoiceStateChange::LeftVoiceChannel => "leave",
+            VoiceStateChange::MovedVoiceChannel => "move",
+            VoiceStateChange::ServerDeafened => "server_deafen",
+            VoiceStateChange::ServerMuted => "server_mute",
+            VoiceStateChange::SelfDeafened => "self_deafen",
+            VoiceStateChange::SelfMuted => "self_mute",
+            VoiceStateChange::SelfStream => "stream",
+            VoiceStateChange::SelfVideo => "video",
+            VoiceStateChange::Suppress => "suppress",
+        }
+    }
+}
+
 impl Display for VoiceStateChange {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match *self {
@@ -26,54 +57,157 @@ impl Display for VoiceStateChange {
             VoiceStateChange::ServerMuted => f.write_str("Server muted"),
             VoiceStateChange::SelfDeafened => f.write_str("Self deafened"),
             VoiceStateChange::SelfMuted => f.write_str("Self muted"),
-            VoiceStateChange::_SelfStream => f.write_str("Self stream"),
+            VoiceStateChange::SelfStream => f.write_str("Self stream"),
             VoiceStateChange::SelfVideo => f.write_str("Self video"),
             VoiceStateChange::Suppress => f.write_str("Suppress"),
         }
     }
 }
 
-pub fn identify_state(_guild_id: &GuildId, old_state: &Option<VoiceState>, new: &VoiceState) -> Option<VoiceStateChange> {
-    if old_state.is_none() {
-        return Some(VoiceStateChange::JoinedVoiceChannel)
-    }
-    let old_state: &VoiceState = old_state.as_ref().unwrap();
+/// Everything that changed between the two states. Joining counts whatever the member joined with,
+/// e.g. already muted, as changed too. Leaving is only a leave, the rest of the state goes with it.
+pub fn identify_state(old_state: &Option<VoiceState>, new: &VoiceState) -> BTreeSet<VoiceStateChange> {
+    let mut changes = BTreeSet::new();
 
-    if let Some(old_id) = old_state.channel_id {
-        if let Some(new_id) = new.channel_id {
-            if old_id.as_u64() != new_id.as_u64() {
-                return Some(VoiceStateChange::MovedVoiceChannel)
+    let old_state = match old_state {
+        Some(old_state) if old_state.channel_id.is_some() => old_state,
+        //not in a channel before, so every flag that's on now was just turned on
+        _ => {
+            if new.channel_id.is_none() {
+                return changes;
             }
+            changes.insert(VoiceStateChange::JoinedVoiceChannel);
+            flag_changes(&mut changes, &Flags::default(), &Flags::from(new));
+            return changes;
         }
-    }
+    };
 
-    if new.channel_id.is_none() {
-        return Some(VoiceStateChange::LeftVoiceChannel)
+    match new.channel_id {
+        None => {
+            changes.insert(VoiceStateChange::LeftVoiceChannel);
+            return changes;
+        }
+        Some(new_id) if Some(new_id) != old_state.channel_id => {
+            changes.insert(VoiceStateChange::MovedVoiceChannel);
+        }
+        Some(_) => {}
     }
 
-    if old_state.deaf != new.deaf {
-        return Some(VoiceStateChange::ServerDeafened)
-    }
+    flag_changes(&mut changes, &Flags::from(old_state), &Flags::from(new));
+
+    changes
+}
+
+/// The on/off parts of a voice state
+#[derive(Default)]
+struct Flags {
+    deaf: bool,
+    mute: bool,
+    self_deaf: bool,
+    self_mute: bool,
+    self_stream: bool,
+    self_video: bool,
+    suppress: bool,
+}
 
-    if old_state.mute != new.mute {
-        return Some(VoiceStateChange::ServerMuted)
+impl From<&VoiceState> for Flags {
+    fn from(state: &VoiceState) -> Self {
+        Flags {
+            deaf: state.deaf,
+            mute: state.mute,
+            self_deaf: state.self_deaf,
+            self_mute: state.self_mute,
+            self_stream: state.self_stream.unwrap_or(false),
+            self_video: state.self_video,
+            suppress: state.suppress,
+        }
     }
+}
+
+fn flag_changes(changes: &mut BTreeSet<VoiceStateChange>, old: &Flags, new: &Flags) {
+    let flags = [
+        (old.deaf != new.deaf, VoiceStateChange::ServerDeafened),
+        (old.mute != new.mute, VoiceStateChange::ServerMuted),
+        (old.self_deaf != new.self_deaf, VoiceStateChange::SelfDeafened),
+        (old.self_mute != new.self_mute, VoiceStateChange::SelfMuted),
+        (old.self_stream != new.self_stream, VoiceStateChange::SelfStream),
+        (old.self_video != new.self_video, VoiceStateChange::SelfVideo),
+        (old.suppress != new.suppress, VoiceStateChange::Suppress),
+    ];
+
+    changes.extend(flags.into_iter().filter(|(changed, _)| *changed).map(|(_, change)| change));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use serde_json::json;
+    use serenity::model::{id::ChannelId, voice::VoiceState};
+
+    use super::{identify_state, VoiceStateChange::*};
 
-    if old_state.self_deaf != new.self_deaf {
-        return Some(VoiceStateChange::SelfDeafened)
+    fn state(channel_id: Option<u64>) -> VoiceState {
+        let mut state: VoiceState = serde_json::from_value(json!({
+            "channel_id": null, "deaf": false, "mute": false, "self_deaf": false, "self_mute": false,
+            "self_video": false, "session_id": "session", "suppress": false, "user_id": "7"
+        }))
+        .unwrap();
+        state.channel_id = channel_id.map(ChannelId);
+        state
     }
 
-    if old_state.self_mute != new.self_mute {
-        return Some(VoiceStateChange::SelfMuted)
+    #[test]
+    fn joining_reports_what_the_member_joined_with() {
+        assert_eq!(identify_state(&None, &state(Some(1))), BTreeSet::from([JoinedVoiceChannel]));
+
+        let mut muted = state(Some(1));
+        muted.self_mute = true;
+        muted.self_deaf = true;
+        assert_eq!(identify_state(&None, &muted), BTreeSet::from([JoinedVoiceChannel, SelfDeafened, SelfMuted]));
+        assert_eq!(identify_state(&Some(state(None)), &muted), BTreeSet::from([JoinedVoiceChannel, SelfDeafened, SelfMuted]));
     }
 
-    if old_state.self_video != new.self_video {
-        return Some(VoiceStateChange::SelfVideo)
+    #[test]
+    fn leaving_is_only_a_leave() {
+        let mut moved = state(Some(2));
+        moved.self_video = true;
+        let mut left = state(None);
+        left.self_mute = true;
+        assert_eq!(identify_state(&Some(moved), &left), BTreeSet::from([LeftVoiceChannel]));
+
+        assert!(identify_state(&None, &state(None)).is_empty(), "a leave without its channel isn't a join");
+        assert!(identify_state(&Some(state(None)), &state(None)).is_empty());
     }
 
-    if old_state.suppress != new.suppress {
-        return Some(VoiceStateChange::Suppress)
+    #[test]
+    fn moves_keep_their_other_changes() {
+        assert_eq!(identify_state(&Some(state(Some(1))), &state(Some(2))), BTreeSet::from([MovedVoiceChannel]));
+
+        let mut moved = state(Some(2));
+        moved.mute = true;
+        assert_eq!(identify_state(&Some(state(Some(1))), &moved), BTreeSet::from([MovedVoiceChannel, ServerMuted]));
     }
 
-    None
-}
\ No newline at end of file
+    #[test]
+    fn every_flag_is_reported_together() {
+        let mut before = state(Some(1));
+        before.self_mute = true;
+        before.suppress = true;
+        let mut after = state(Some(1));
+        after.deaf = true;
+        after.self_stream = Some(true);
+        after.self_video = true;
+
+        assert_eq!(
+            identify_state(&Some(before.clone()), &after),
+            BTreeSet::from([ServerDeafened, SelfMuted, SelfStream, SelfVideo, Suppress])
+        );
+        assert!(identify_state(&Some(before.clone()), &before).is_empty());
+
+        //clients that never streamed leave it out
+        let mut stopped = after.clone();
+        stopped.self_stream = None;
+        assert_eq!(identify_state(&Some(after), &stopped), BTreeSet::from([SelfStream]));
+    }
+}
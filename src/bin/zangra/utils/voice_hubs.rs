@@ -0,0 +1,320 @@
+//! Join to create voice channels, set up per guild with `/voicehub`. Joining the hub gets a member
+//! their own channel next to it, which is deleted once everyone has left.
+
+use anyhow::Result;
+use chrono::Utc;
+use serenity::{
+    client::Context,
+    model::{
+        channel::{ChannelType, PermissionOverwrite, PermissionOverwriteType},
+        id::{ChannelId, GuildId},
+        permissions::Permissions,
+        voice::VoiceState,
+    },
+};
+use sqlx::{query, Error as SqlxError, SqlitePool};
+use tracing::{error, info};
+
+use crate::utils::{database::DatabasePool, metrics::client_metrics};
+
+pub const DEFAULT_NAME_TEMPLATE: &str = "{name}'s channel";
+
+//what the member gets in their own channel on top of what the hub allows
+const OWNER_PERMISSIONS: Permissions = Permissions::CONNECT
+    .union(Permissions::MANAGE_CHANNELS)
+    .union(Permissions::MOVE_MEMBERS);
+
+//Discord's limit on channel names
+const MAX_NAME_LENGTH: usize = 100;
+
+/// The hub of a guild and how the channels it makes are set up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoiceHubSettings {
+    pub channel_id: ChannelId,
+    /// `{name}` is replaced by the member's display name
+    pub name_template: String,
+    pub user_limit: Option<u32>,
+}
+
+pub fn hub_channel_name(template: &str, display_name: &str) -> String {
+    let name: String = template.replace("{name}", display_name).trim().chars().take(MAX_NAME_LENGTH).collect();
+    if name.is_empty() {
+        return DEFAULT_NAME_TEMPLATE.replace("{name}", display_name);
+    }
+
+    name
+}
+
+pub async fn voice_hub_settings(pool: &SqlitePool, guild_id: GuildId) -> Result<Option<VoiceHubSettings>, SqlxError> {
+    let guild_id_i64 = guild_id.0 as i64;
+    let row = query!("SELECT ChannelId, NameTemplate, UserLimit FROM VoiceHub WHERE GuildId = ?", guild_id_i64)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|row| VoiceHubSettings {
+        channel_id: ChannelId(row.ChannelId as u64),
+        name_template: row.NameTemplate,
+        user_limit: row.UserLimit.map(|limit| limit as u32),
+    }))
+}
+
+pub async fn save_voice_hub_settings(pool: &SqlitePool, guild_id: GuildId, settings: &VoiceHubSettings) -> Result<(), SqlxError> {
+    let guild_id_i64 = guild_id.0 as i64;
+    let channel_id_i64 = settings.channel_id.0 as i64;
+    let user_limit = settings.user_limit.map(i64::from);
+
+    query!(
+        "INSERT INTO VoiceHub (GuildId, ChannelId, NameTemplate, UserLimit) VALUES (?, ?, ?, ?)
+        ON CONFLICT (GuildId) DO UPDATE SET ChannelId = excluded.ChannelId, NameTemplate = excluded.NameTemplate, UserLimit = excluded.UserLimit",
+        guild_id_i64,
+        channel_id_i64,
+        settings.name_template,
+        user_limit
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// `true` when there was a hub to turn off, channels it already made are still cleaned up
+pub async fn remove_voice_hub_settings(pool: &SqlitePool, guild_id: GuildId) -> Result<bool, SqlxError> {
+    let guild_id_i64 = guild_id.0 as i64;
+    let result = query!("DELETE FROM VoiceHub WHERE GuildId = ?", guild_id_i64).execute(pool).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Drops a deleted channel, whether it was a hub or one a hub made
+pub async fn forget_channel_voice_hub(pool: &SqlitePool, channel_id: ChannelId) -> Result<u64, SqlxError> {
+    let channel_id = channel_id.0 as i64;
+
+    let hubs = query!("DELETE FROM VoiceHub WHERE ChannelId = ?", channel_id).execute(pool).await?;
+    let channels = query!("DELETE FROM HubVoiceChannel WHERE ChannelId = ?", channel_id).execute(pool).await?;
+
+    Ok(hubs.rows_affected() + channels.rows_affected())
+}
+
+async fn is_hub_voice_channel(pool: &SqlitePool, channel_id: ChannelId) -> Result<bool, SqlxError> {
+    let channel_id = channel_id.0 as i64;
+    let row = query!("SELECT ChannelId FROM HubVoiceChannel WHERE ChannelId = ?", channel_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.is_some())
+}
+
+/// Whether anyone is in the channel, `true` when the guild isn't cached so nothing is deleted blindly
+fn channel_in_use(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> bool {
+    ctx.cache
+        .guild_field(guild_id, |guild| guild.voice_states.values().any(|state| state.channel_id == Some(channel_id)))
+        .unwrap_or(true)
+}
+
+/// `true` once Discord deleted the channel. The row stays when Discord refuses, the next startup
+/// tries again
+async fn delete_hub_voice_channel(ctx: &Context, pool: &SqlitePool, channel_id: ChannelId) -> bool {
+    match channel_id.delete(ctx).await {
+        Ok(_) => {
+            if let Err(why) = forget_channel_voice_hub(pool, channel_id).await {
+                error!("Unable to forget hub voice channel {}: {}", channel_id, why);
+            }
+            true
+        }
+        Err(why) => {
+            error!("Unable to delete hub voice channel {}: {}", channel_id, why);
+            false
+        }
+    }
+}
+
+/// Makes the member their own channel under the hub's category, with the hub's permissions plus
+/// [`OWNER_PERMISSIONS`] for them, and moves them in
+async fn open_hub_voice_channel(ctx: &Context, pool: &SqlitePool, guild_id: GuildId, settings: &VoiceHubSettings, member: &VoiceState) -> Result<()> {
+    let hub = ctx.cache.guild_channel(settings.channel_id);
+    let display_name = match &member.member {
+        Some(member) => member.display_name().to_string(),
+        None => member.user_id.to_string(),
+    };
+
+    let owner = PermissionOverwriteType::Member(member.user_id);
+    let mut overwrites: Vec<PermissionOverwrite> = hub
+        .as_ref()
+        .map(|hub| hub.permission_overwrites.iter().filter(|o| o.kind != owner).cloned().collect())
+        .unwrap_or_default();
+    overwrites.push(PermissionOverwrite {
+        allow: OWNER_PERMISSIONS,
+        deny: Permissions::empty(),
+        kind: owner,
+    });
+
+    let channel = guild_id
+        .create_channel(ctx, |c| {
+            c.name(hub_channel_name(&settings.name_template, &display_name));
+            c.kind(ChannelType::Voice);
+            c.permissions(overwrites);
+            if let Some(category) = hub.as_ref().and_then(|hub| hub.parent_id) {
+                c.category(category);
+            }
+            if let Some(limit) = settings.user_limit {
+                c.user_limit(limit);
+            }
+            c
+        })
+        .await?;
+
+    let channel_id = channel.id.0 as i64;
+    let guild_id_i64 = guild_id.0 as i64;
+    let owner_id = member.user_id.0 as i64;
+    let created_at = Utc::now().timestamp();
+    query!(
+        "INSERT INTO HubVoiceChannel (ChannelId, GuildId, OwnerId, CreatedAt) VALUES (?, ?, ?, ?)",
+        channel_id,
+        guild_id_i64,
+        owner_id,
+        created_at
+    )
+    .execute(pool)
+    .await?;
+
+    if let Err(why) = guild_id.move_member(ctx, member.user_id, channel.id).await {
+        //they left the hub before they could be moved in
+        delete_hub_voice_channel(ctx, pool, channel.id).await;
+        return Err(why.into());
+    }
+
+    Ok(())
+}
+
+/// Called on every channel change: deletes the channel left behind when it was a hub's and is now
+/// empty, and opens a channel for members joining a hub
+pub async fn handle_voice_hub(ctx: &Context, guild_id: GuildId, from: Option<ChannelId>, new: &VoiceState) {
+    let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+    let metrics = client_metrics(ctx).await;
+
+    if let Some(from) = from {
+        if !channel_in_use(ctx, guild_id, from) {
+            match metrics.time_query("hub_voice_channel", is_hub_voice_channel(&pool, from)).await {
+                Ok(true) => {
+                    delete_hub_voice_channel(ctx, &pool, from).await;
+                }
+                Ok(false) => {}
+                Err(why) => error!("Unable to look up hub voice channel: {}", why),
+            }
+        }
+    }
+
+    let to = match new.channel_id {
+        Some(to) if !new.member.as_ref().is_some_and(|m| m.user.bot) => to,
+        _ => return,
+    };
+    let settings = match metrics.time_query("voice_hub_settings", voice_hub_settings(&pool, guild_id)).await {
+        Ok(Some(settings)) if settings.channel_id == to => settings,
+        Ok(_) => return,
+        Err(why) => {
+            error!("Unable to read voice hub settings: {}", why);
+            return;
+        }
+    };
+
+    if let Err(why) = open_hub_voice_channel(ctx, &pool, guild_id, &settings, new).await {
+        error!("Unable to open a hub voice channel in {}: {}", guild_id, why);
+    }
+}
+
+/// Deletes hub channels that emptied while the bot was away and forgets the ones already gone,
+/// run once the cache is ready
+pub async fn cleanup_hub_voice_channels(ctx: &Context) {
+    let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+    let rows = match query!("SELECT ChannelId, GuildId FROM HubVoiceChannel").fetch_all(&pool).await {
+        Ok(rows) => rows,
+        Err(why) => {
+            error!("Unable to read hub voice channels: {}", why);
+            return;
+        }
+    };
+
+    let mut removed = 0;
+    for row in rows {
+        let channel_id = ChannelId(row.ChannelId as u64);
+        let guild_id = GuildId(row.GuildId as u64);
+
+        //guilds that didn't come back are left alone, they may be in an outage
+        match ctx.cache.guild_field(guild_id, |guild| guild.channels.contains_key(&channel_id)) {
+            Some(false) => match forget_channel_voice_hub(&pool, channel_id).await {
+                Ok(_) => removed += 1,
+                Err(why) => error!("Unable to forget hub voice channel {}: {}", channel_id, why),
+            },
+            //failed deletes aren't counted, they're tried again next startup
+            Some(true) if !channel_in_use(ctx, guild_id, channel_id) => removed += usize::from(delete_hub_voice_channel(ctx, &pool, channel_id).await),
+            _ => {}
+        }
+    }
+
+    info!("Cleaned up {} leftover hub voice channels", removed);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serenity::model::id::{ChannelId, GuildId};
+    use sqlx::{
+        query,
+        sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    };
+
+    use super::{
+        forget_channel_voice_hub, hub_channel_name, is_hub_voice_channel, remove_voice_hub_settings, save_voice_hub_settings, voice_hub_settings,
+        VoiceHubSettings,
+    };
+
+    const GUILD: GuildId = GuildId(1);
+    const HUB: ChannelId = ChannelId(10);
+
+    #[tokio::test]
+    async fn settings_and_channels_are_forgotten() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(SqliteConnectOptions::from_str("sqlite::memory:").unwrap())
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+
+        assert_eq!(voice_hub_settings(&pool, GUILD).await.unwrap(), None);
+        let mut settings = VoiceHubSettings {
+            channel_id: HUB,
+            name_template: "{name}'s channel".to_string(),
+            user_limit: None,
+        };
+        save_voice_hub_settings(&pool, GUILD, &settings).await.unwrap();
+        settings.user_limit = Some(4);
+        save_voice_hub_settings(&pool, GUILD, &settings).await.unwrap();
+        assert_eq!(voice_hub_settings(&pool, GUILD).await.unwrap(), Some(settings.clone()), "saving again updates the hub");
+
+        query!("INSERT INTO HubVoiceChannel (ChannelId, GuildId, OwnerId, CreatedAt) VALUES (11, 1, 2, 0)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(is_hub_voice_channel(&pool, ChannelId(11)).await.unwrap());
+        assert_eq!(forget_channel_voice_hub(&pool, ChannelId(11)).await.unwrap(), 1);
+        assert!(!is_hub_voice_channel(&pool, ChannelId(11)).await.unwrap());
+        assert_eq!(voice_hub_settings(&pool, GUILD).await.unwrap(), Some(settings.clone()), "the hub outlives its channels");
+
+        assert_eq!(forget_channel_voice_hub(&pool, HUB).await.unwrap(), 1, "deleting the hub channel turns the hub off");
+        assert_eq!(voice_hub_settings(&pool, GUILD).await.unwrap(), None);
+
+        save_voice_hub_settings(&pool, GUILD, &settings).await.unwrap();
+        assert!(remove_voice_hub_settings(&pool, GUILD).await.unwrap());
+        assert!(!remove_voice_hub_settings(&pool, GUILD).await.unwrap());
+        assert_eq!(voice_hub_settings(&pool, GUILD).await.unwrap(), None);
+    }
+
+    #[test]
+    fn names_fill_in_the_member() {
+        assert_eq!(hub_channel_name("{name}'s channel", "Nguyen"), "Nguyen's channel");
+        assert_eq!(hub_channel_name("🎮 {name}", "Nguyen"), "🎮 Nguyen");
+        assert_eq!(hub_channel_name("  ", "Nguyen"), "Nguyen's channel", "blank templates fall back to the default");
+        assert_eq!(hub_channel_name("{name}", &"a".repeat(150)).len(), 100);
+    }
+}
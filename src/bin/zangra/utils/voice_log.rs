@@ -0,0 +1,192 @@
+//! Voice activity logging, set up per guild with `/voicelog`: which channel the log goes to and
+//! which changes make it in.
+
+use chrono::Utc;
+use serenity::{
+    builder::CreateEmbed,
+    client::Context,
+    model::{
+        id::{ChannelId, GuildId},
+        voice::VoiceState,
+    },
+    utils::Color,
+};
+use sqlx::{query, Error as SqlxError, SqlitePool};
+use tracing::error;
+
+use crate::utils::{
+    database::DatabasePool,
+    metrics::client_metrics,
+    time::discord_timestamp,
+    voice::{identify_state, VoiceStateChange},
+};
+
+/// Where a guild's voice log goes and what it holds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoiceLogSettings {
+    pub channel_id: ChannelId,
+    pub events: Vec<VoiceStateChange>,
+}
+
+/// Changes from a comma separated list of their names, every change when it's empty
+pub fn parse_voice_log_events(events: &str) -> Result<Vec<VoiceStateChange>, String> {
+    let mut parsed = Vec::new();
+    for name in events.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let change = VoiceStateChange::ALL
+            .into_iter()
+            .find(|c| c.name() == name)
+            .ok_or_else(|| format!("`{name}` isn't a voice event, pick from {}", event_names(&VoiceStateChange::ALL)))?;
+        if !parsed.contains(&change) {
+            parsed.push(change);
+        }
+    }
+
+    if parsed.is_empty() {
+        return Ok(VoiceStateChange::ALL.to_vec());
+    }
+
+    Ok(parsed)
+}
+
+pub fn event_names(events: &[VoiceStateChange]) -> String {
+    events.iter().map(|e| e.name()).collect::<Vec<_>>().join(", ")
+}
+
+pub async fn voice_log_settings(pool: &SqlitePool, guild_id: GuildId) -> Result<Option<VoiceLogSettings>, SqlxError> {
+    let guild_id_i64 = guild_id.0 as i64;
+    let row = query!("SELECT ChannelId, Events FROM VoiceLog WHERE GuildId = ?", guild_id_i64)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|row| VoiceLogSettings {
+        channel_id: ChannelId(row.ChannelId as u64),
+        //names a newer version dropped are skipped rather than failing the whole guild
+        events: row
+            .Events
+            .split(',')
+            .filter_map(|name| VoiceStateChange::ALL.into_iter().find(|c| c.name() == name))
+            .collect(),
+    }))
+}
+
+pub async fn save_voice_log_settings(pool: &SqlitePool, guild_id: GuildId, settings: &VoiceLogSettings) -> Result<(), SqlxError> {
+    let guild_id_i64 = guild_id.0 as i64;
+    let channel_id_i64 = settings.channel_id.0 as i64;
+    let events = settings.events.iter().map(|e| e.name()).collect::<Vec<_>>().join(",");
+
+    query!(
+        "INSERT INTO VoiceLog (GuildId, ChannelId, Events) VALUES (?, ?, ?)
+        ON CONFLICT (GuildId) DO UPDATE SET ChannelId = excluded.ChannelId, Events = excluded.Events",
+        guild_id_i64,
+        channel_id_i64,
+        events
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// `true` when there was a log to turn off
+pub async fn remove_voice_log_settings(pool: &SqlitePool, guild_id: GuildId) -> Result<bool, SqlxError> {
+    let guild_id_i64 = guild_id.0 as i64;
+    let result = query!("DELETE FROM VoiceLog WHERE GuildId = ?", guild_id_i64).execute(pool).await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+fn on_off(on: bool, when_on: &str, when_off: &str) -> String {
+    if on { when_on } else { when_off }.to_string()
+}
+
+/// The log line for a change and its colour, `old_channel` is where a leave or move came from
+fn describe(change: VoiceStateChange, old_channel: Option<ChannelId>, new: &VoiceState) -> (String, Color) {
+    let user = format!("<@{}>", new.user_id);
+    let channel = |channel_id: Option<ChannelId>| match channel_id {
+        Some(channel_id) => format!("<#{channel_id}>"),
+        None => "an unknown channel".to_string(),
+    };
+
+    match change {
+        VoiceStateChange::JoinedVoiceChannel => (format!("{user} joined {}", channel(new.channel_id)), Color::from_rgb(0, 255, 0)),
+        VoiceStateChange::LeftVoiceChannel => (format!("{user} left {}", channel(old_channel)), Color::RED),
+        VoiceStateChange::MovedVoiceChannel => (
+            format!("{user} moved from {} to {}", channel(old_channel), channel(new.channel_id)),
+            Color::GOLD,
+        ),
+        VoiceStateChange::ServerDeafened => (format!("{user} was {}", on_off(new.deaf, "server deafened", "server undeafened")), Color::ORANGE),
+        VoiceStateChange::ServerMuted => (format!("{user} was {}", on_off(new.mute, "server muted", "server unmuted")), Color::ORANGE),
+        VoiceStateChange::SelfDeafened => (format!("{user} {}", on_off(new.self_deaf, "deafened", "undeafened")), Color::BLURPLE),
+        VoiceStateChange::SelfMuted => (format!("{user} {}", on_off(new.self_mute, "muted", "unmuted")), Color::BLURPLE),
+        VoiceStateChange::SelfStream => (
+            format!("{user} {}", on_off(new.self_stream.unwrap_or(false), "started streaming", "stopped streaming")),
+            Color::PURPLE,
+        ),
+        VoiceStateChange::SelfVideo => (format!("{user} {}", on_off(new.self_video, "turned their camera on", "turned their camera off")), Color::PURPLE),
+        VoiceStateChange::Suppress => (
+            format!("{user} {}", on_off(new.suppress, "was moved to the audience", "became a speaker")),
+            Color::DARK_GREY,
+        ),
+    }
+}
+
+/// Posts the changes the guild's voice log wants to it, all in one message
+pub async fn log_voice_state(ctx: &Context, guild_id: GuildId, old: &Option<VoiceState>, new: &VoiceState) {
+    let changes = identify_state(old, new);
+    if changes.is_empty() {
+        return;
+    }
+
+    let pool = ctx.data.read().await.get::<DatabasePool>().unwrap().clone();
+    let metrics = client_metrics(ctx).await;
+    let settings = match metrics.time_query("voice_log_settings", voice_log_settings(&pool, guild_id)).await {
+        Ok(Some(settings)) => settings,
+        Ok(_) => return,
+        Err(why) => {
+            error!("Unable to read voice log settings: {}", why);
+            return;
+        }
+    };
+
+    let old_channel = old.as_ref().and_then(|o| o.channel_id);
+    let lines: Vec<(String, Color)> = changes
+        .into_iter()
+        .filter(|change| settings.events.contains(change))
+        .map(|change| describe(change, old_channel, new))
+        .collect();
+    //the first change is the channel one when there is one, it sets the colour
+    let color = match lines.first() {
+        Some((_, color)) => *color,
+        None => return,
+    };
+    let description = lines.into_iter().map(|(line, _)| line).collect::<Vec<_>>().join("\n");
+    let now = Utc::now();
+
+    let mut embed = CreateEmbed::default();
+    embed.description(format!("{description}\n{} ({})", discord_timestamp(&now, 'F'), discord_timestamp(&now, 'R')));
+    embed.color(color);
+    embed.timestamp(now.to_rfc3339());
+    if let Some(member) = &new.member {
+        embed.author(|a| a.name(member.display_name()).icon_url(member.face()));
+    }
+
+    if let Err(why) = settings.channel_id.send_message(ctx, |m| m.set_embed(embed)).await {
+        error!("Unable to post to the voice log of {}: {}", guild_id, why);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_voice_log_events;
+    use crate::utils::voice::VoiceStateChange;
+
+    #[test]
+    fn events_are_picked_by_name() {
+        assert_eq!(
+            parse_voice_log_events("join, leave,join").unwrap(),
+            [VoiceStateChange::JoinedVoiceChannel, VoiceStateChange::LeftVoiceChannel]
+        );
+        assert_eq!(parse_voice_log_events(" ").unwrap().len(), VoiceStateChange::ALL.len(), "every event by default");
+        assert!(parse_voice_log_events("join,wave").unwrap_err().contains("`wave`"));
+    }
+}
@@ -0,0 +1,313 @@
+//! Time spent in voice, one session per stay in a channel. A session is open until the member
+//! leaves or moves, `/voice stats` and `/voice leaderboard` count open sessions up to now.
+
+use std::collections::HashSet;
+
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use sqlx::{query, Error as SqlxError, SqlitePool};
+
+/// How far back `/voice` looks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoicePeriod {
+    Day,
+    Week,
+    Month,
+    All,
+}
+
+impl VoicePeriod {
+    pub const ALL: [VoicePeriod; 4] = [VoicePeriod::Day, VoicePeriod::Week, VoicePeriod::Month, VoicePeriod::All];
+
+    /// The command option value
+    pub fn name(self) -> &'static str {
+        match self {
+            VoicePeriod::Day => "day",
+            VoicePeriod::Week => "week",
+            VoicePeriod::Month => "month",
+            VoicePeriod::All => "all",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            VoicePeriod::Day => "the last 24 hours",
+            VoicePeriod::Week => "the last 7 days",
+            VoicePeriod::Month => "the last 30 days",
+            VoicePeriod::All => "all time",
+        }
+    }
+
+    /// Week when there's no period or it isn't one of the names
+    pub fn from_name(name: Option<&str>) -> VoicePeriod {
+        VoicePeriod::ALL
+            .into_iter()
+            .find(|p| Some(p.name()) == name)
+            .unwrap_or(VoicePeriod::Week)
+    }
+
+    /// Unix time the period starts at
+    pub fn since(self, now: i64) -> i64 {
+        match self {
+            VoicePeriod::Day => now - 24 * 60 * 60,
+            VoicePeriod::Week => now - 7 * 24 * 60 * 60,
+            VoicePeriod::Month => now - 30 * 24 * 60 * 60,
+            VoicePeriod::All => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoiceTime {
+    pub seconds: i64,
+    pub sessions: i64,
+}
+
+/// `1h 05m`, `12m`
+pub fn format_voice_time(seconds: i64) -> String {
+    let minutes = seconds / 60;
+    match minutes / 60 {
+        0 => format!("{minutes}m"),
+        hours => format!("{hours}h {:02}m", minutes % 60),
+    }
+}
+
+/// Ends the member's open session in the guild and starts one in `to` when they're still in voice
+pub async fn record_voice_session(
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    user_id: UserId,
+    to: Option<ChannelId>,
+    now: i64,
+) -> Result<(), SqlxError> {
+    let guild_id = guild_id.0 as i64;
+    let user_id = user_id.0 as i64;
+
+    let mut tx = pool.begin().await?;
+
+    query!(
+        "UPDATE VoiceSession SET LeftAt = ? WHERE GuildId = ? AND UserId = ? AND LeftAt IS NULL",
+        now,
+        guild_id,
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    if let Some(channel_id) = to {
+        let channel_id = channel_id.0 as i64;
+        query!(
+            "INSERT INTO VoiceSession (GuildId, UserId, ChannelId, JoinedAt) VALUES (?, ?, ?, ?)",
+            guild_id,
+            user_id,
+            channel_id,
+            now
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await
+}
+
+/// Matches the open sessions in `guilds` to who is in voice there right now, after a restart or
+/// reconnect the bot has missed the leaves and joins in between. Sessions of members who left are
+/// closed at `now`, the real time isn't known, and members in voice without a session get one.
+/// Other guilds' sessions are left alone, they may only be unavailable. Returns how many sessions
+/// were (closed, opened).
+pub async fn reconcile_voice_sessions(
+    pool: &SqlitePool,
+    guilds: &[GuildId],
+    in_voice: &[(GuildId, UserId, ChannelId)],
+    now: i64,
+) -> Result<(u64, u64), SqlxError> {
+    let in_voice: HashSet<(i64, i64, i64)> = in_voice
+        .iter()
+        .filter(|(guild_id, _, _)| guilds.contains(guild_id))
+        .map(|(guild_id, user_id, channel_id)| (guild_id.0 as i64, user_id.0 as i64, channel_id.0 as i64))
+        .collect();
+
+    let mut tx = pool.begin().await?;
+
+    let mut still_open = HashSet::new();
+    let mut closed = 0;
+    for guild_id in guilds {
+        let guild_id = guild_id.0 as i64;
+        let open = query!(
+            "SELECT VoiceSessionId, GuildId, UserId, ChannelId FROM VoiceSession WHERE GuildId = ? AND LeftAt IS NULL",
+            guild_id
+        )
+        .fetch_all(&mut tx)
+        .await?;
+
+        for session in open {
+            let key = (session.GuildId, session.UserId, session.ChannelId);
+            if in_voice.contains(&key) && still_open.insert(key) {
+                continue;
+            }
+
+            query!("UPDATE VoiceSession SET LeftAt = ? WHERE VoiceSessionId = ?", now, session.VoiceSessionId)
+                .execute(&mut tx)
+                .await?;
+            closed += 1;
+        }
+    }
+
+    let mut opened = 0;
+    for (guild_id, user_id, channel_id) in in_voice.difference(&still_open) {
+        query!(
+            "INSERT INTO VoiceSession (GuildId, UserId, ChannelId, JoinedAt) VALUES (?, ?, ?, ?)",
+            guild_id,
+            user_id,
+            channel_id,
+            now
+        )
+        .execute(&mut tx)
+        .await?;
+        opened += 1;
+    }
+
+    tx.commit().await?;
+
+    Ok((closed, opened))
+}
+
+/// The member's time per channel between `since` and `now`, most first
+pub async fn member_voice_time(
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    user_id: UserId,
+    since: i64,
+    now: i64,
+) -> Result<Vec<(ChannelId, VoiceTime)>, SqlxError> {
+    let guild_id = guild_id.0 as i64;
+    let user_id = user_id.0 as i64;
+
+    //sessions are clipped to the period, open ones count up to now
+    let rows = query!(
+        r#"SELECT ChannelId AS "ChannelId!", SUM(MIN(COALESCE(LeftAt, ?), ?) - MAX(JoinedAt, ?)) AS "seconds!: i64", COUNT(*) AS "sessions!: i64"
+        FROM VoiceSession
+        WHERE GuildId = ? AND UserId = ? AND JoinedAt < ? AND COALESCE(LeftAt, ?) > ?
+        GROUP BY ChannelId
+        ORDER BY 2 DESC"#,
+        now,
+        now,
+        since,
+        guild_id,
+        user_id,
+        now,
+        now,
+        since
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (ChannelId(row.ChannelId as u64), VoiceTime {
+                seconds: row.seconds,
+                sessions: row.sessions,
+            })
+        })
+        .collect())
+}
+
+/// The members with the most time in voice between `since` and `now`
+pub async fn voice_leaderboard(
+    pool: &SqlitePool,
+    guild_id: GuildId,
+    since: i64,
+    now: i64,
+    limit: i64,
+) -> Result<Vec<(UserId, VoiceTime)>, SqlxError> {
+    let guild_id = guild_id.0 as i64;
+
+    let rows = query!(
+        r#"SELECT UserId AS "UserId!", SUM(MIN(COALESCE(LeftAt, ?), ?) - MAX(JoinedAt, ?)) AS "seconds!: i64", COUNT(*) AS "sessions!: i64"
+        FROM VoiceSession
+        WHERE GuildId = ? AND JoinedAt < ? AND COALESCE(LeftAt, ?) > ?
+        GROUP BY UserId
+        ORDER BY 2 DESC
+        LIMIT ?"#,
+        now,
+        now,
+        since,
+        guild_id,
+        now,
+        now,
+        since,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            (UserId(row.UserId as u64), VoiceTime {
+                seconds: row.seconds,
+                sessions: row.sessions,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serenity::model::id::{ChannelId, GuildId, UserId};
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+
+    use super::{format_voice_time, member_voice_time, reconcile_voice_sessions, record_voice_session, voice_leaderboard, VoiceTime};
+
+    const GUILD: GuildId = GuildId(1);
+    const OTHER_GUILD: GuildId = GuildId(2);
+    const LOBBY: ChannelId = ChannelId(10);
+    const GAMES: ChannelId = ChannelId(11);
+
+    #[tokio::test]
+    async fn sessions_are_clipped_to_the_period_and_survive_restarts() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(SqliteConnectOptions::from_str("sqlite::memory:").unwrap())
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+
+        let (alice, bob) = (UserId(100), UserId(200));
+        record_voice_session(&pool, GUILD, alice, Some(LOBBY), 1_000).await.unwrap();
+        record_voice_session(&pool, GUILD, alice, Some(GAMES), 1_600).await.unwrap();
+        record_voice_session(&pool, GUILD, alice, None, 2_200).await.unwrap();
+        record_voice_session(&pool, GUILD, bob, Some(LOBBY), 1_000).await.unwrap();
+        record_voice_session(&pool, OTHER_GUILD, bob, Some(ChannelId(20)), 1_000).await.unwrap();
+
+        //the bot was down, alice came back to the lobby and bob left. The other guild is unavailable
+        //so bob's session there stays open.
+        assert_eq!(
+            reconcile_voice_sessions(&pool, &[GUILD], &[(GUILD, alice, LOBBY)], 3_000).await.unwrap(),
+            (1, 1)
+        );
+        let elsewhere = voice_leaderboard(&pool, OTHER_GUILD, 0, 3_600, 10).await.unwrap();
+        assert_eq!(elsewhere, [(bob, VoiceTime { seconds: 2_600, sessions: 1 })]);
+
+        let alice_time = member_voice_time(&pool, GUILD, alice, 1_300, 3_600).await.unwrap();
+        assert_eq!(alice_time, [
+            (LOBBY, VoiceTime { seconds: 300 + 600, sessions: 2 }),
+            (GAMES, VoiceTime { seconds: 600, sessions: 1 }),
+        ]);
+
+        let leaderboard = voice_leaderboard(&pool, GUILD, 0, 3_600, 10).await.unwrap();
+        assert_eq!(leaderboard, [
+            (bob, VoiceTime { seconds: 2_000, sessions: 1 }),
+            (alice, VoiceTime { seconds: 1_800, sessions: 3 }),
+        ]);
+    }
+
+    #[test]
+    fn voice_time_is_hours_and_minutes() {
+        assert_eq!(format_voice_time(59), "0m");
+        assert_eq!(format_voice_time(12 * 60 + 30), "12m");
+        assert_eq!(format_voice_time(3 * 60 * 60 + 5 * 60), "3h 05m");
+    }
+}
@@ -0,0 +1,572 @@
+//! Outgoing webhooks. Guild admins register a URL with `/webhook add` and it gets a signed JSON
+//! POST whenever one of the events it picked happens. Every attempt is written to
+//! `WebhookDelivery`, failed ones are retried with growing delays.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::Utc;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use serde_json::{json, Value};
+use serenity::model::{id::GuildId, Timestamp};
+use sha2::{Digest, Sha256};
+use sqlx::{query, Error as SqlxError, SqlitePool};
+use tokio::{sync::broadcast::error::RecvError, task::JoinSet};
+use tracing::{error, warn};
+use url::{Host, Url};
+
+use crate::utils::{
+    guild_events::{GuildEventKind, GuildEvents},
+    shutdown::Shutdown,
+};
+
+/// What a webhook can subscribe to
+pub const WEBHOOK_EVENTS: [&str; 5] = ["member_join", "role_selector_change", "link_blocked", "voice_join", "voice_leave"];
+/// Sent by `/webhook test`, whatever the webhook subscribed to
+pub const TEST_EVENT: &str = "ping";
+
+//waits before the second, third... attempt, the first is right away
+const RETRY_DELAYS: [Duration; 4] = [
+    Duration::from_secs(10),
+    Duration::from_secs(60),
+    Duration::from_secs(5 * 60),
+    Duration::from_secs(30 * 60),
+];
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+//the delivery log is for debugging recent failures, older attempts are pruned by the scheduler
+const DELIVERY_RETENTION_DAYS: i64 = 14;
+
+#[derive(Debug, Clone)]
+pub struct OutgoingWebhook {
+    pub id: i64,
+    pub guild_id: GuildId,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+}
+
+/// How one POST went
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryOutcome {
+    Delivered(u16),
+    /// Worth trying again: no answer, a server error or too many requests
+    Retry { status: Option<u16>, error: String },
+    /// The receiver refused it, redirects included, or the URL isn't allowed any more. Sending it
+    /// again won't help.
+    Refused { status: Option<u16>, error: String },
+}
+
+impl DeliveryOutcome {
+    /// Response bodies are never kept, they'd be shown in Discord and could come from anywhere
+    fn from_status(status: u16) -> DeliveryOutcome {
+        let error = reqwest::StatusCode::from_u16(status)
+            .ok()
+            .and_then(|s| s.canonical_reason())
+            .unwrap_or("Unknown status")
+            .to_string();
+
+        match status {
+            200..=299 => DeliveryOutcome::Delivered(status),
+            429 | 500..=599 => DeliveryOutcome::Retry {
+                status: Some(status),
+                error,
+            },
+            _ => DeliveryOutcome::Refused {
+                status: Some(status),
+                error,
+            },
+        }
+    }
+}
+
+/// Resolves hosts like the system does but fails when any address isn't public. It runs for every
+/// connection, so a name can't be pointed at the bot's network after the webhook was added.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            //the connector fills in the port
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if let Some(private) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                return Err(format!("{} resolves to {}, which isn't a public address", name.as_str(), private.ip()).into());
+            }
+
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// The client deliveries are sent with. It gives up on slow receivers, doesn't follow redirects
+/// and only connects to public addresses.
+pub fn webhook_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .user_agent("Zangra-Webhook")
+        .redirect(reqwest::redirect::Policy::none())
+        .dns_resolver(Arc::new(PublicResolver))
+        .build()
+        .unwrap()
+}
+
+/// Only public http(s) addresses, the bot's own network isn't for guild admins to reach
+pub fn validate_webhook_url(url: &str) -> Result<Url, String> {
+    let url = Url::parse(url).map_err(|why| format!("`{url}` isn't a URL: {why}"))?;
+
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Err("Webhook URLs have to be http or https".to_string());
+    }
+
+    //names are checked again when they're resolved, see PublicResolver
+    let private = match url.host() {
+        None => true,
+        Some(Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost") || domain.ends_with(".localhost"),
+        Some(Host::Ipv4(ip)) => !is_public(IpAddr::V4(ip)),
+        Some(Host::Ipv6(ip)) => !is_public(IpAddr::V6(ip)),
+    };
+    if private {
+        return Err("Webhook URLs have to point at a public address".to_string());
+    }
+
+    Ok(url)
+}
+
+/// Whether the address is globally reachable, everything in IANA's special purpose registries is not
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                //this network 0.0.0.0/8, shared address space 100.64.0.0/10, protocol assignments
+                //192.0.0.0/24, benchmarking 198.18.0.0/15 and reserved 240.0.0.0/4
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64)
+                || (a == 192 && b == 0 && c == 0)
+                || (a == 198 && (b & 0xfe) == 18)
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+
+            let segments = ip.segments();
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                //unique local fc00::/7 and link local fe80::/10
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                //documentation 2001:db8::/32
+                || (segments[0] == 0x2001 && segments[1] == 0x0db8)
+                //IPv4 compatible ::/96, NAT64 64:ff9b::/96, Teredo 2001::/32 and 6to4 2002::/16 can
+                //all carry a private IPv4 address
+                || segments[..6] == [0; 6]
+                || (segments[0] == 0x64 && segments[1] == 0xff9b)
+                || (segments[0] == 0x2001 && segments[1] == 0)
+                || segments[0] == 0x2002)
+        }
+    }
+}
+
+/// Event names from a comma separated list, every event when it's empty
+pub fn parse_webhook_events(events: &str) -> Result<Vec<String>, String> {
+    let mut parsed = Vec::new();
+    for event in events.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        if !WEBHOOK_EVENTS.contains(&event) {
+            return Err(format!("`{event}` isn't an event, pick from {}", WEBHOOK_EVENTS.join(", ")));
+        }
+        if !parsed.iter().any(|p| p == event) {
+            parsed.push(event.to_string());
+        }
+    }
+
+    if parsed.is_empty() {
+        return Ok(WEBHOOK_EVENTS.iter().map(|e| e.to_string()).collect());
+    }
+
+    Ok(parsed)
+}
+
+/// The webhook event and its `data` for a guild event, `None` for events webhooks don't get.
+/// Moving between voice channels is neither a join nor a leave.
+pub fn webhook_event(kind: &GuildEventKind) -> Option<(&'static str, Value)> {
+    match kind {
+        GuildEventKind::MemberJoined { user_id, name } => Some((
+            "member_join",
+            json!({ "user_id": user_id.to_string(), "name": name }),
+        )),
+        GuildEventKind::SelectorRolesChanged {
+            channel_id,
+            message_id,
+            user_id,
+            added,
+            removed,
+        } => Some((
+            "role_selector_change",
+            json!({
+                "channel_id": channel_id.to_string(),
+                "message_id": message_id.to_string(),
+                "user_id": user_id.to_string(),
+                "added": added.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+                "removed": removed.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+            }),
+        )),
+        GuildEventKind::LinkBlocked {
+            channel_id,
+            message_id,
+            user_id,
+            deleted,
+        } => Some((
+            "link_blocked",
+            json!({
+                "channel_id": channel_id.to_string(),
+                "message_id": message_id.to_string(),
+                "user_id": user_id.to_string(),
+                "deleted": deleted,
+            }),
+        )),
+        GuildEventKind::VoiceMoved { user_id, from: None, to: Some(to) } => Some((
+            "voice_join",
+            json!({ "user_id": user_id.to_string(), "channel_id": to.to_string() }),
+        )),
+        GuildEventKind::VoiceMoved { user_id, from: Some(from), to: None } => Some((
+            "voice_leave",
+            json!({ "user_id": user_id.to_string(), "channel_id": from.to_string() }),
+        )),
+        _ => None,
+    }
+}
+
+const BLOCK_SIZE: usize = 64;
+
+/// HMAC-SHA256 (RFC 2104), small enough not to need a crate for
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let inner_key: Vec<u8> = block.iter().map(|b| b ^ 0x36).collect();
+    let outer_key: Vec<u8> = block.iter().map(|b| b ^ 0x5c).collect();
+
+    let inner = Sha256::new().chain_update(&inner_key).chain_update(message).finalize();
+    Sha256::new().chain_update(&outer_key).chain_update(inner).finalize().into()
+}
+
+/// `X-Zangra-Signature`, receivers recompute it over `<X-Zangra-Timestamp>.<body>` with their secret
+pub fn signature(secret: &str, timestamp: i64, body: &str) -> String {
+    let signed = format!("{timestamp}.{body}");
+    format!("sha256={}", hex::encode(hmac_sha256(secret.as_bytes(), signed.as_bytes())))
+}
+
+/// A random id for the delivery, the same for all of its attempts so receivers can drop repeats
+fn delivery_key() -> String {
+    format!("{:032x}", rand::random::<u128>())
+}
+
+async fn post(client: &reqwest::Client, webhook: &OutgoingWebhook, key: &str, event: &str, body: &str) -> DeliveryOutcome {
+    //webhooks added before a range was blocked are caught here
+    if let Err(error) = validate_webhook_url(&webhook.url) {
+        return DeliveryOutcome::Refused { status: None, error };
+    }
+
+    let timestamp = Utc::now().timestamp();
+
+    let response = client
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Zangra-Event", event)
+        .header("X-Zangra-Delivery", key)
+        .header("X-Zangra-Timestamp", timestamp.to_string())
+        .header("X-Zangra-Signature", signature(&webhook.secret, timestamp, body))
+        .body(body.to_string())
+        .send()
+        .await;
+
+    match response {
+        Ok(response) => DeliveryOutcome::from_status(response.status().as_u16()),
+        Err(why) => DeliveryOutcome::Retry {
+            status: None,
+            error: why.to_string(),
+        },
+    }
+}
+
+async fn log_attempt(
+    pool: &SqlitePool,
+    webhook_id: i64,
+    key: &str,
+    event: &str,
+    attempt: i64,
+    outcome: &DeliveryOutcome,
+) -> Result<(), SqlxError> {
+    let (status, error, succeeded) = match outcome {
+        DeliveryOutcome::Delivered(status) => (Some(*status as i64), None, true),
+        DeliveryOutcome::Retry { status, error } => (status.map(i64::from), Some(error.clone()), false),
+        DeliveryOutcome::Refused { status, error } => (status.map(i64::from), Some(error.clone()), false),
+    };
+    let attempted_at = Utc::now().timestamp();
+
+    query!(
+        "INSERT INTO WebhookDelivery (OutgoingWebhookId, DeliveryKey, Event, Attempt, StatusCode, Error, Succeeded, AttemptedAt)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        webhook_id,
+        key,
+        event,
+        attempt,
+        status,
+        error,
+        succeeded,
+        attempted_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Deletes logged attempts older than the retention, returns how many went
+pub async fn prune_webhook_deliveries(pool: &SqlitePool) -> Result<u64, SqlxError> {
+    let expired = Utc::now().timestamp() - chrono::Duration::days(DELIVERY_RETENTION_DAYS).num_seconds();
+    let pruned = query!("DELETE FROM WebhookDelivery WHERE AttemptedAt < ?", expired)
+        .execute(pool)
+        .await?;
+
+    Ok(pruned.rows_affected())
+}
+
+fn body(key: &str, event: &str, guild_id: GuildId, at: Timestamp, data: &Value) -> String {
+    json!({
+        "id": key,
+        "event": event,
+        "guild_id": guild_id.to_string(),
+        "timestamp": at.to_string(),
+        "data": data,
+    })
+    .to_string()
+}
+
+/// A single attempt without retries, for `/webhook test`
+pub async fn deliver_test(client: &reqwest::Client, pool: &SqlitePool, webhook: &OutgoingWebhook) -> Result<DeliveryOutcome, SqlxError> {
+    let key = delivery_key();
+    let data = json!({ "message": "Test delivery from /webhook test" });
+    let body = body(&key, TEST_EVENT, webhook.guild_id, Timestamp::now(), &data);
+
+    let outcome = post(client, webhook, &key, TEST_EVENT, &body).await;
+    log_attempt(pool, webhook.id, &key, TEST_EVENT, 1, &outcome).await?;
+
+    Ok(outcome)
+}
+
+/// Tries until delivered, refused or out of retries. Gives up early when the bot stops.
+async fn deliver(
+    client: reqwest::Client,
+    pool: SqlitePool,
+    webhook: OutgoingWebhook,
+    event: &'static str,
+    at: Timestamp,
+    data: Value,
+    shutdown: Shutdown,
+) {
+    let key = delivery_key();
+    let body = body(&key, event, webhook.guild_id, at, &data);
+
+    for attempt in 1..=RETRY_DELAYS.len() + 1 {
+        if attempt > 1 {
+            tokio::select! {
+                _ = tokio::time::sleep(RETRY_DELAYS[attempt - 2]) => {}
+                _ = shutdown.clone().wait() => return,
+            }
+        }
+
+        let outcome = post(&client, &webhook, &key, event, &body).await;
+        if let Err(why) = log_attempt(&pool, webhook.id, &key, event, attempt as i64, &outcome).await {
+            error!("Unable to log webhook delivery: {}", why);
+        }
+
+        match outcome {
+            DeliveryOutcome::Retry { .. } => continue,
+            DeliveryOutcome::Delivered(_) => return,
+            DeliveryOutcome::Refused { error, .. } => {
+                warn!("Webhook #{} refused {}: {}", webhook.id, event, error);
+                return;
+            }
+        }
+    }
+
+    warn!("Webhook #{} gave up on {} after {} attempts", webhook.id, event, RETRY_DELAYS.len() + 1);
+}
+
+pub async fn guild_webhooks(pool: &SqlitePool, guild_id: GuildId) -> Result<Vec<OutgoingWebhook>, SqlxError> {
+    let guild_id_i64 = guild_id.0 as i64;
+    let rows = query!(
+        "SELECT OutgoingWebhookId, Url, Secret, Events FROM OutgoingWebhook WHERE GuildId = ? ORDER BY OutgoingWebhookId",
+        guild_id_i64
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| OutgoingWebhook {
+            id: row.OutgoingWebhookId,
+            guild_id,
+            url: row.Url,
+            secret: row.Secret,
+            events: row.Events.split(',').map(str::to_string).collect(),
+        })
+        .collect())
+}
+
+/// Hands every guild event a webhook subscribed to over to a delivery of its own, started once from
+/// main. Returns once the shutdown is triggered and the deliveries under way have stopped.
+pub async fn run_webhook_dispatcher(events: GuildEvents, pool: SqlitePool, shutdown: Shutdown) {
+    let client = webhook_client();
+    let mut receiver = events.subscribe();
+    let mut deliveries = JoinSet::new();
+
+    loop {
+        let event = tokio::select! {
+            event = receiver.recv() => event,
+            Some(_) = deliveries.join_next() => continue,
+            _ = shutdown.clone().wait() => break,
+        };
+
+        let event = match event {
+            Ok(event) => event,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Webhooks missed {} events", skipped);
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        let (name, data) = match webhook_event(&event.kind) {
+            Some(webhook_event) => webhook_event,
+            None => continue,
+        };
+
+        let webhooks = match guild_webhooks(&pool, event.guild_id).await {
+            Ok(webhooks) => webhooks,
+            Err(why) => {
+                error!("Unable to read webhooks: {}", why);
+                continue;
+            }
+        };
+
+        for webhook in webhooks.into_iter().filter(|w| w.events.iter().any(|e| e == name)) {
+            deliveries.spawn(deliver(client.clone(), pool.clone(), webhook, name, event.at, data.clone(), shutdown.clone()));
+        }
+    }
+
+    //retries stop on shutdown, attempts in flight are still logged before the pool closes
+    while deliveries.join_next().await.is_some() {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::IpAddr, str::FromStr};
+
+    use chrono::Utc;
+    use sqlx::{
+        query,
+        sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+    };
+
+    use super::{hmac_sha256, is_public, log_attempt, parse_webhook_events, prune_webhook_deliveries, validate_webhook_url, DeliveryOutcome};
+
+    #[tokio::test]
+    async fn old_deliveries_are_pruned() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(SqliteConnectOptions::from_str("sqlite::memory:").unwrap())
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+
+        query!("INSERT INTO OutgoingWebhook (OutgoingWebhookId, GuildId, Url, Secret, Events, CreatedBy, CreatedAt) VALUES (1, 1, 'https://example.com', 'secret', 'voice_join', 2, 0)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        log_attempt(&pool, 1, "recent", "voice_join", 1, &DeliveryOutcome::Delivered(204)).await.unwrap();
+        let month_ago = Utc::now().timestamp() - 30 * 24 * 60 * 60;
+        query!(
+            "INSERT INTO WebhookDelivery (OutgoingWebhookId, DeliveryKey, Event, Attempt, Succeeded, AttemptedAt) VALUES (1, 'old', 'voice_join', 1, 1, ?)",
+            month_ago
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(prune_webhook_deliveries(&pool).await.unwrap(), 1);
+        let kept = query!("SELECT DeliveryKey FROM WebhookDelivery").fetch_all(&pool).await.unwrap();
+        assert_eq!(kept.into_iter().map(|r| r.DeliveryKey).collect::<Vec<_>>(), ["recent"]);
+    }
+
+    #[test]
+    fn hmac_matches_rfc_4231() {
+        //test case 2
+        let mac = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        assert_eq!(hex::encode(mac), "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+
+        //test case 6, a key longer than the block
+        let mac = hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First");
+        assert_eq!(hex::encode(mac), "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54");
+    }
+
+    #[test]
+    fn only_server_errors_are_retried() {
+        assert_eq!(DeliveryOutcome::from_status(204), DeliveryOutcome::Delivered(204));
+        assert!(matches!(DeliveryOutcome::from_status(503), DeliveryOutcome::Retry { .. }));
+        assert!(matches!(DeliveryOutcome::from_status(429), DeliveryOutcome::Retry { .. }));
+        assert_eq!(
+            DeliveryOutcome::from_status(302),
+            DeliveryOutcome::Refused { status: Some(302), error: "Found".to_string() },
+            "redirects aren't followed"
+        );
+    }
+
+    #[test]
+    fn urls_and_events_are_checked() {
+        assert!(validate_webhook_url("https://hooks.example.com/zangra").is_ok());
+        for url in ["ftp://example.com", "http://localhost:8080", "http://127.0.0.1", "http://10.0.0.2", "http://[::1]/", "http://[fd00::1]/"] {
+            assert!(validate_webhook_url(url).is_err(), "{url}");
+        }
+        for ip in [
+            "169.254.169.254",
+            "100.64.0.1",
+            "100.127.255.255",
+            "0.1.2.3",
+            "192.0.0.8",
+            "198.18.0.1",
+            "240.0.0.1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a00:1",
+            "2002:a00:1::",
+            "ff02::1",
+        ] {
+            assert!(!is_public(ip.parse::<IpAddr>().unwrap()), "{ip}");
+        }
+        for ip in ["1.1.1.1", "100.128.0.1", "198.20.0.1", "2606:4700:4700::1111"] {
+            assert!(is_public(ip.parse::<IpAddr>().unwrap()), "{ip}");
+        }
+
+        assert_eq!(parse_webhook_events(" voice_join,voice_join, member_join").unwrap(), ["voice_join", "member_join"]);
+        assert_eq!(parse_webhook_events("").unwrap().len(), 5, "every event by default");
+        assert!(parse_webhook_events("voice_move").is_err());
+    }
+}